@@ -0,0 +1,380 @@
+use crate::process::{Process, ProcessBuilder, ProcessError, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// Starts the processes of `ProcessBuilder::spawn`
+///
+/// The operating system is used unless a backend is set on the builder or
+/// installed as the default, which allows running code that spawns tools
+/// against scripted fakes in tests.
+pub trait ProcessBackend: Send + Sync + fmt::Debug {
+    /// Start a process as configured by `builder`
+    fn spawn(&self, builder: &ProcessBuilder) -> Result<Process>;
+}
+
+/// A child started by a backend other than the operating system, see `Process::from_spawned`
+pub trait BackendChild: Send {
+    /// Identifier of the child, only used for display
+    fn id(&self) -> u32;
+
+    /// Get the exit status if the child has exited
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+
+    /// Wait for the child to exit
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+
+    /// Terminate the child
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+/// A child started by a backend together with its standard streams
+pub struct Spawned {
+    pub child: Box<dyn BackendChild>,
+
+    /// Input of the child; dropping it signals EOF
+    pub stdin: Box<dyn Write + Send>,
+
+    pub stdout: Box<dyn Read + Send>,
+    pub stderr: Box<dyn Read + Send>,
+}
+
+/// Backend used by builders without one of their own
+static DEFAULT: RwLock<Option<Arc<dyn ProcessBackend>>> = RwLock::new(None);
+
+/// Install the backend used by all builders without one of their own, `None` for the operating system
+pub fn set_default(backend: Option<Arc<dyn ProcessBackend>>) {
+    *DEFAULT.write().unwrap() = backend;
+}
+
+/// The installed default backend, `None` for the operating system
+pub fn default_backend() -> Option<Arc<dyn ProcessBackend>> {
+    DEFAULT.read().unwrap().clone()
+}
+
+/// Starts real processes with every option of the builder
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsBackend;
+
+impl ProcessBackend for OsBackend {
+    fn spawn(&self, builder: &ProcessBuilder) -> Result<Process> {
+        builder.spawn_connected(None, None)
+    }
+}
+
+/// One step of a fake process's script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeStep {
+    /// Print to stdout
+    Stdout(Vec<u8>),
+
+    /// Print to stderr
+    Stderr(Vec<u8>),
+
+    /// Do nothing for a while
+    Sleep(Duration),
+}
+
+/// What a fake process does, from spawn to exit
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FakeScript {
+    /// Steps carried out in order
+    pub steps: Vec<FakeStep>,
+
+    /// Code the process exits with after the last step
+    pub exit_code: i32,
+
+    /// Keep running after the last step until stdin is closed
+    pub until_stdin_closed: bool,
+}
+
+impl FakeScript {
+    /// A script exiting with code 0 right away
+    pub fn new() -> Self {
+        FakeScript::default()
+    }
+
+    /// Print `data` to stdout
+    pub fn stdout(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(FakeStep::Stdout(data.into()));
+        self
+    }
+
+    /// Print `data` to stderr
+    pub fn stderr(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(FakeStep::Stderr(data.into()));
+        self
+    }
+
+    /// Wait before the next step
+    pub fn sleep(mut self, duration: Duration) -> Self {
+        self.steps.push(FakeStep::Sleep(duration));
+        self
+    }
+
+    /// Set the exit code
+    pub fn exit_code(mut self, code: i32) -> Self {
+        self.exit_code = code;
+        self
+    }
+
+    /// Only exit once stdin is closed, like a recorder stopped by its host
+    pub fn until_stdin_closed(mut self) -> Self {
+        self.until_stdin_closed = true;
+        self
+    }
+}
+
+/// A spawn of a fake process, as seen by the backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeInvocation {
+    pub program: String,
+    pub args: Vec<String>,
+
+    /// Everything written to stdin so far
+    pub stdin: Vec<u8>,
+}
+
+/// A spawn of a fake process, with stdin still being captured
+#[derive(Debug)]
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    stdin: Arc<Mutex<Vec<u8>>>,
+}
+
+/// Simulates processes in memory from scripts, for testing code that runs tools
+///
+/// Programs are matched by their exact name or file name; spawning a
+/// program without a script fails with `ProcessError::Unscripted`, so a
+/// test never runs a real tool by accident.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    scripts: Mutex<HashMap<String, FakeScript>>,
+    invocations: Mutex<Vec<Invocation>>,
+    next_id: AtomicU32,
+}
+
+impl FakeBackend {
+    /// A backend without scripts
+    pub fn new() -> Self {
+        FakeBackend::default()
+    }
+
+    /// Simulate `program` with `script` from now on
+    pub fn set_script(&self, program: &str, script: FakeScript) {
+        self.scripts.lock().unwrap().insert(program.to_string(), script);
+    }
+
+    /// The script `program` is simulated with, if any
+    pub fn script(&self, program: &str) -> Option<FakeScript> {
+        self.scripts.lock().unwrap().get(program).cloned()
+    }
+
+    /// Stop simulating `program`
+    pub fn remove_script(&self, program: &str) {
+        self.scripts.lock().unwrap().remove(program);
+    }
+
+    /// Get the fake processes spawned so far, oldest first
+    pub fn invocations(&self) -> Vec<FakeInvocation> {
+        let invocations = self.invocations.lock().unwrap();
+        invocations
+            .iter()
+            .map(|invocation| FakeInvocation {
+                program: invocation.program.clone(),
+                args: invocation.args.clone(),
+                stdin: invocation.stdin.lock().unwrap().clone(),
+            })
+            .collect()
+    }
+
+    /// Forget the fake processes spawned so far
+    pub fn clear_invocations(&self) {
+        self.invocations.lock().unwrap().clear();
+    }
+
+    fn script_for(&self, program: &str) -> Option<FakeScript> {
+        let scripts = self.scripts.lock().unwrap();
+        if let Some(script) = scripts.get(program) {
+            return Some(script.clone());
+        }
+        let name = Path::new(program).file_name()?.to_str()?;
+        scripts.get(name).cloned()
+    }
+}
+
+impl ProcessBackend for FakeBackend {
+    fn spawn(&self, builder: &ProcessBuilder) -> Result<Process> {
+        let Some(script) = self.script_for(builder.get_program()) else {
+            return Err(ProcessError::Unscripted(builder.get_program().to_string()));
+        };
+
+        let stdin = Arc::new(Mutex::new(Vec::new()));
+        self.invocations.lock().unwrap().push(Invocation {
+            program: builder.get_program().to_string(),
+            args: builder.get_args().to_vec(),
+            stdin: Arc::clone(&stdin),
+        });
+
+        // Fake IDs stay clear of real ones, so they are never signalled by mistake
+        let id = u32::MAX - self.next_id.fetch_add(1, Ordering::Relaxed);
+        Process::from_spawned(builder, FakeChild::start(id, script, stdin)?)
+    }
+}
+
+/// State of a fake process shared with its script thread
+#[derive(Default)]
+struct FakeState {
+    exit: Option<ExitStatus>,
+    killed: bool,
+    stdin_closed: bool,
+}
+
+#[derive(Default)]
+struct FakeShared {
+    state: Mutex<FakeState>,
+    changed: Condvar,
+}
+
+/// A process simulated by a script thread
+struct FakeChild {
+    id: u32,
+    shared: Arc<FakeShared>,
+}
+
+impl FakeChild {
+    /// Start running `script`, capturing stdin into `stdin`
+    fn start(id: u32, script: FakeScript, stdin: Arc<Mutex<Vec<u8>>>) -> io::Result<Spawned> {
+        let shared = Arc::new(FakeShared::default());
+        let (stdout, mut stdout_writer) = io::pipe()?;
+        let (stderr, mut stderr_writer) = io::pipe()?;
+
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let shared = thread_shared;
+            for step in &script.steps {
+                // A reader that went away is ignored, like a closed pipe by a real process
+                match step {
+                    FakeStep::Stdout(data) => {
+                        let _ = stdout_writer.write_all(data);
+                    }
+                    FakeStep::Stderr(data) => {
+                        let _ = stderr_writer.write_all(data);
+                    }
+                    FakeStep::Sleep(duration) => {
+                        let state = shared.state.lock().unwrap();
+                        drop(shared.changed.wait_timeout_while(state, *duration, |state| !state.killed).unwrap());
+                    }
+                }
+                if shared.state.lock().unwrap().killed {
+                    break;
+                }
+            }
+
+            if script.until_stdin_closed {
+                let state = shared.state.lock().unwrap();
+                drop(shared.changed.wait_while(state, |state| !state.killed && !state.stdin_closed).unwrap());
+            }
+
+            // Output ends before the exit is reported, as with a real process
+            drop((stdout_writer, stderr_writer));
+            shared.state.lock().unwrap().exit.get_or_insert(exit_status(script.exit_code));
+            shared.changed.notify_all();
+        });
+
+        Ok(Spawned {
+            child: Box::new(FakeChild {
+                id,
+                shared: Arc::clone(&shared),
+            }),
+            stdin: Box::new(FakeStdin { data: stdin, shared }),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+        })
+    }
+}
+
+impl BackendChild for FakeChild {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.shared.state.lock().unwrap().exit)
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        let state = self.shared.state.lock().unwrap();
+        let state = self.shared.changed.wait_while(state, |state| state.exit.is_none()).unwrap();
+        Ok(state.exit.unwrap())
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.exit.is_none() {
+            state.killed = true;
+            state.exit = Some(killed_status());
+        }
+        drop(state);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+/// Stdin of a fake process, recording everything written
+struct FakeStdin {
+    data: Arc<Mutex<Vec<u8>>>,
+    shared: Arc<FakeShared>,
+}
+
+impl Write for FakeStdin {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.data.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for FakeStdin {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stdin_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Status of a process that exited with `code`
+fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::from_raw((code & 0xff) << 8)
+    }
+
+    #[cfg(windows)]
+    {
+        std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+    }
+}
+
+/// Status of a process that was killed
+fn killed_status() -> ExitStatus {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::from_raw(libc::SIGKILL)
+    }
+
+    // What `TerminateProcess` leaves as exit code
+    #[cfg(windows)]
+    {
+        std::os::windows::process::ExitStatusExt::from_raw(1)
+    }
+}
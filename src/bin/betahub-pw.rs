@@ -0,0 +1,566 @@
+//! Runs a command through the library, for reproducing host integrations by hand
+//!
+//! Captured output is streamed to this process's stdout and stderr while the
+//! command runs. Afterwards a JSON report with the exit info and resource
+//! usage is printed to stderr, or written to the `--report` file. The exit
+//! code is the command's, 128 + the signal number if it was terminated by a
+//! signal, or 124 if it timed out. The driver itself exits with 2 for a
+//! malformed command line, 127 if the command could not be started and 74
+//! if reading or writing one of its own files failed.
+
+use betahub_process_wrapper::cgroup::{CgroupConfig, CgroupStats};
+use betahub_process_wrapper::exit::{ExitInfo, TerminationReason};
+use betahub_process_wrapper::limits::{Resource, UNLIMITED};
+use betahub_process_wrapper::logging;
+use betahub_process_wrapper::output::{FileSink, OutputSink};
+use betahub_process_wrapper::priority::{IoPriority, SchedPolicy};
+use betahub_process_wrapper::process::{Process, ProcessBuilder, StdinSource};
+use betahub_process_wrapper::pty::PtySize;
+use betahub_process_wrapper::usage::ResourceUsage;
+use betahub_process_wrapper::watchdog::{Watchdog, WatchdogAction};
+use log::LevelFilter;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+usage: betahub-pw [OPTIONS] PROGRAM [ARGS...]
+
+Process:
+  --env KEY=VALUE          set an environment variable
+  --env-remove KEY         remove an environment variable
+  --env-clear              start from an empty environment
+  --cwd DIR                run in DIR
+  --process-group          run in a new process group
+  --pty COLSxROWS          run on a pseudo-terminal
+  --limit NAME=SOFT[:HARD] limit as, cpu, nofile, core, nproc or fsize; values may be 'unlimited'
+  --nice N                 set the niceness
+  --sched POLICY           scheduling policy other, batch or idle
+  --io-priority CLASS      I/O class realtime:LEVEL, best-effort:LEVEL or idle
+  --cpus LIST              run only on these CPUs, e.g. 0,2-3
+  --cgroup PARENT          run in a new cgroup v2 under PARENT and report its usage
+  --memory-max BYTES       limit the memory of the cgroup
+  --cpu-max QUOTA:PERIOD   allow QUOTA of CPU time per PERIOD milliseconds in the cgroup
+
+Streams:
+  --stdin MODE             null (default), file:PATH or data:TEXT
+  --feed FILE              write FILE ('-' for stdin) to the process's stdin in chunks
+  --chunk-size BYTES       size of each written chunk (default 65536)
+  --chunk-interval MS      delay between chunks, e.g. 33 for 30 frames a second (default 0)
+  --stdout MODE            capture (default), null, file:PATH or append:PATH
+  --stderr MODE            capture (default), null, file:PATH or append:PATH
+  --rotate BYTES:BACKUPS   rotate file outputs at BYTES, keeping BACKUPS old files
+  --tee                    also stream file outputs here
+  --events FILE            record timestamped output events to FILE as JSON lines
+
+Supervision:
+  --timeout MS             kill the process after MS milliseconds
+  --watchdog MS            report stalls of MS milliseconds without activity
+  --watchdog-action ACTION notify (default), kill or a signal number
+  --report FILE            write the JSON report to FILE instead of stderr
+  --log LEVEL              print library logs up to error, warn, info, debug or trace to stderr
+";
+
+/// Exit code for a malformed command line
+const USAGE_ERROR: i32 = 2;
+
+/// Exit code when the command could not be started, as in shells
+const SPAWN_FAILED: i32 = 127;
+
+/// Exit code when a file of the driver itself could not be read or written, as `EX_IOERR`
+const IO_ERROR: i32 = 74;
+
+/// Exit code when the command was killed for exceeding `--timeout`, as with `timeout(1)`
+const TIMED_OUT: i32 = 124;
+
+/// How often output is copied while nothing else is due
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Number of most recent events kept for `--events`
+const EVENT_CAPACITY: usize = 65536;
+
+/// Everything given on the command line
+struct Options {
+    builder: ProcessBuilder,
+    feed: Option<String>,
+    chunk_size: usize,
+    chunk_interval: Duration,
+    watchdog: Option<Duration>,
+    watchdog_action: WatchdogAction,
+    report: Option<PathBuf>,
+    events: Option<PathBuf>,
+    log: Option<LevelFilter>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("betahub-pw: {}\n\n{}", err, USAGE);
+            process::exit(USAGE_ERROR);
+        }
+    };
+
+    if let Some(level) = options.log {
+        let print: logging::LogCallback = Arc::new(|record| {
+            eprintln!("betahub-pw: [{}] {}: {}", record.level(), record.target(), record.args());
+        });
+        if let Err(err) = logging::set_log_callback(level, Some(print)) {
+            eprintln!("betahub-pw: logging unavailable: {}", err);
+        }
+    }
+
+    match run(options) {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("betahub-pw: {}", err);
+            process::exit(IO_ERROR);
+        }
+    }
+}
+
+/// Parse the options up to the program, which starts the command
+fn parse(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let mut builder = None;
+    let mut settings = Vec::new();
+    let mut rotate = None;
+    let mut tee = false;
+    let mut cgroup: Option<CgroupConfig> = None;
+    let mut memory_max = None;
+    let mut cpu_max = None;
+    let mut options = Options {
+        builder: ProcessBuilder::new("-"),
+        feed: None,
+        chunk_size: 65536,
+        chunk_interval: Duration::ZERO,
+        watchdog: None,
+        watchdog_action: WatchdogAction::Notify,
+        report: None,
+        events: None,
+        log: None,
+    };
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            print!("{}", USAGE);
+            process::exit(0);
+        }
+        if arg == "--" || !arg.starts_with("--") {
+            let program = if arg == "--" { args.next() } else { Some(arg) };
+            let program = program.ok_or("missing program")?;
+            let mut command = ProcessBuilder::new(program);
+            command.args(&args.by_ref().map(String::as_str).collect::<Vec<_>>());
+            builder = Some(command);
+            break;
+        }
+
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--env-clear" | "--process-group" => settings.push((arg.as_str(), String::new())),
+            "--feed" => options.feed = Some(value()?.clone()),
+            "--chunk-size" => options.chunk_size = number(arg, value()?)?.max(1) as usize,
+            "--chunk-interval" => options.chunk_interval = Duration::from_millis(number(arg, value()?)?),
+            "--watchdog" => options.watchdog = Some(Duration::from_millis(number(arg, value()?)?)),
+            "--watchdog-action" => {
+                options.watchdog_action = match value()?.as_str() {
+                    "notify" => WatchdogAction::Notify,
+                    "kill" => WatchdogAction::Kill,
+                    signal => WatchdogAction::Signal(signal.parse().map_err(|_| format!("bad action {}", signal))?),
+                }
+            }
+            "--report" => options.report = Some(PathBuf::from(value()?)),
+            "--events" => options.events = Some(PathBuf::from(value()?)),
+            "--rotate" => {
+                let (bytes, backups) = value()?.split_once(':').ok_or("--rotate needs BYTES:BACKUPS")?;
+                rotate = Some((number(arg, bytes)?, number(arg, backups)? as usize));
+            }
+            "--tee" => tee = true,
+            "--cgroup" => cgroup = Some(CgroupConfig::new(value()?)),
+            "--memory-max" => memory_max = Some(number(arg, value()?)?),
+            "--cpu-max" => {
+                let (quota, period) = value()?.split_once(':').ok_or("--cpu-max needs QUOTA:PERIOD")?;
+                cpu_max = Some((
+                    Duration::from_millis(number(arg, quota)?),
+                    Duration::from_millis(number(arg, period)?),
+                ));
+            }
+            "--log" => options.log = Some(value()?.parse().map_err(|_| format!("bad level for {}", arg))?),
+            "--env" | "--env-remove" | "--cwd" | "--pty" | "--limit" | "--nice" | "--sched" | "--io-priority"
+            | "--cpus" | "--timeout" | "--stdin" | "--stdout" | "--stderr" => {
+                settings.push((arg.as_str(), value()?.clone()))
+            }
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    options.builder = builder.ok_or("missing program")?;
+
+    // Applied once the builder exists, in the order given
+    let builder = &mut options.builder;
+    builder.capture_stdout(true);
+    builder.stdin(StdinSource::Null);
+    for (option, value) in settings {
+        match option {
+            "--env" => {
+                let (key, value) = value.split_once('=').ok_or("--env needs KEY=VALUE")?;
+                builder.env(key, value);
+            }
+            "--env-remove" => {
+                builder.env_remove(&value);
+            }
+            "--env-clear" => {
+                builder.env_clear();
+            }
+            "--cwd" => {
+                builder.current_dir(value);
+            }
+            "--process-group" => {
+                builder.process_group(true);
+            }
+            "--pty" => {
+                let (cols, rows) = value.split_once('x').ok_or("--pty needs COLSxROWS")?;
+                builder.pty(PtySize {
+                    cols: cols.parse().map_err(|_| "bad --pty columns")?,
+                    rows: rows.parse().map_err(|_| "bad --pty rows")?,
+                });
+            }
+            "--limit" => {
+                let (resource, soft, hard) = limit(&value)?;
+                builder.limit(resource, soft, hard);
+            }
+            "--nice" => {
+                builder.nice(value.parse().map_err(|_| "bad --nice value")?);
+            }
+            "--sched" => {
+                builder.sched_policy(match value.as_str() {
+                    "other" => SchedPolicy::Other,
+                    "batch" => SchedPolicy::Batch,
+                    "idle" => SchedPolicy::Idle,
+                    _ => return Err(format!("bad --sched policy {}", value)),
+                });
+            }
+            "--io-priority" => {
+                builder.io_priority(io_priority(&value)?);
+            }
+            "--cpus" => {
+                builder.cpu_affinity(&cpus(&value)?);
+            }
+            "--timeout" => {
+                builder.timeout(Duration::from_millis(number(option, &value)?));
+            }
+            "--stdin" => {
+                builder.stdin(match value.split_once(':') {
+                    None if value == "null" => StdinSource::Null,
+                    Some(("file", path)) => StdinSource::File(PathBuf::from(path)),
+                    Some(("data", text)) => StdinSource::Bytes(text.as_bytes().into()),
+                    _ => return Err(format!("bad --stdin mode {}", value)),
+                });
+            }
+            "--stdout" => {
+                builder.stdout(sink(option, &value, rotate, tee)?);
+            }
+            "--stderr" => {
+                builder.stderr(sink(option, &value, rotate, tee)?);
+            }
+            _ => unreachable!(),
+        }
+    }
+    if options.feed.is_some() {
+        builder.stdin(StdinSource::Piped);
+    }
+    if cgroup.is_none() && (memory_max.is_some() || cpu_max.is_some()) {
+        return Err("--memory-max and --cpu-max need --cgroup".to_string());
+    }
+    if let Some(mut config) = cgroup {
+        if let Some(bytes) = memory_max {
+            config = config.memory_max(bytes);
+        }
+        if let Some((quota, period)) = cpu_max {
+            config = config.cpu_max(quota, period);
+        }
+        builder.cgroup(config);
+    }
+    if options.events.is_some() {
+        builder.record_events(EVENT_CAPACITY);
+    }
+    if let Some(timeout) = options.watchdog {
+        builder.watchdog(Watchdog::new(timeout).action(options.watchdog_action));
+    }
+    Ok(options)
+}
+
+fn number(option: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|_| format!("{} needs a number", option))
+}
+
+/// Parse `NAME=SOFT[:HARD]`, the hard limit defaulting to the soft one
+fn limit(value: &str) -> Result<(Resource, u64, u64), String> {
+    let (name, values) = value.split_once('=').ok_or("--limit needs NAME=SOFT[:HARD]")?;
+    let resource = match name {
+        "as" => Resource::AddressSpace,
+        "cpu" => Resource::CpuTime,
+        "nofile" => Resource::OpenFiles,
+        "core" => Resource::CoreSize,
+        "nproc" => Resource::Processes,
+        "fsize" => Resource::FileSize,
+        _ => return Err(format!("unknown resource {}", name)),
+    };
+    let amount = |value: &str| match value {
+        "unlimited" => Ok(UNLIMITED),
+        _ => value.parse().map_err(|_| format!("bad limit {}", value)),
+    };
+    let (soft, hard) = match values.split_once(':') {
+        Some((soft, hard)) => (amount(soft)?, amount(hard)?),
+        None => (amount(values)?, amount(values)?),
+    };
+    Ok((resource, soft, hard))
+}
+
+/// Parse `realtime:LEVEL`, `best-effort:LEVEL` or `idle`
+fn io_priority(value: &str) -> Result<IoPriority, String> {
+    let level = |level: &str| level.parse().map_err(|_| format!("bad I/O priority level {}", level));
+    match value.split_once(':') {
+        None if value == "idle" => Ok(IoPriority::Idle),
+        Some(("realtime", value)) => Ok(IoPriority::RealTime(level(value)?)),
+        Some(("best-effort", value)) => Ok(IoPriority::BestEffort(level(value)?)),
+        _ => Err(format!("bad --io-priority class {}", value)),
+    }
+}
+
+/// Parse a list of CPU indices and ranges such as `0,2-3`
+fn cpus(value: &str) -> Result<Vec<usize>, String> {
+    let index = |index: &str| index.parse::<usize>().map_err(|_| format!("bad CPU {}", index));
+    let mut cpus = Vec::new();
+    for item in value.split(',') {
+        match item.split_once('-') {
+            Some((first, last)) => cpus.extend(index(first)?..=index(last)?),
+            None => cpus.push(index(item)?),
+        }
+    }
+    Ok(cpus)
+}
+
+/// Parse an output mode, applying `--rotate` and `--tee` to files
+fn sink(option: &str, value: &str, rotate: Option<(u64, usize)>, tee: bool) -> Result<OutputSink, String> {
+    let file = match value.split_once(':') {
+        None if value == "capture" => return Ok(OutputSink::Capture),
+        None if value == "null" => return Ok(OutputSink::Null),
+        Some(("file", path)) => FileSink::new(path).append(false),
+        Some(("append", path)) => FileSink::new(path),
+        _ => return Err(format!("bad {} mode {}", option, value)),
+    };
+    let file = match rotate {
+        Some((max_bytes, backups)) => file.rotate(max_bytes, backups),
+        None => file,
+    };
+    Ok(OutputSink::File(file.tee(tee)))
+}
+
+/// Writes a file to stdin in chunks at a fixed pace, like frames of a recording
+struct Feeder {
+    source: Box<dyn Read>,
+    chunk: Vec<u8>,
+    interval: Duration,
+    next_at: Instant,
+    written: u64,
+}
+
+impl Feeder {
+    fn open(path: &str, chunk_size: usize, interval: Duration) -> io::Result<Self> {
+        let source: Box<dyn Read> = match path {
+            "-" => Box::new(io::stdin()),
+            _ => Box::new(File::open(path)?),
+        };
+        Ok(Feeder {
+            source,
+            chunk: vec![0; chunk_size],
+            interval,
+            next_at: Instant::now(),
+            written: 0,
+        })
+    }
+
+    /// Write the next chunk if it is due, returning false once the source is exhausted
+    fn feed(&mut self, process: &mut Process) -> io::Result<bool> {
+        if Instant::now() < self.next_at {
+            return Ok(true);
+        }
+
+        // Fill whole chunks, so short reads from a pipe do not change their size
+        let mut len = 0;
+        while len < self.chunk.len() {
+            match self.source.read(&mut self.chunk[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if len == 0 {
+            return Ok(false);
+        }
+
+        process.write_stdin(&self.chunk[..len]).map_err(io::Error::other)?;
+        self.written += len as u64;
+        self.next_at += self.interval;
+        Ok(len == self.chunk.len())
+    }
+}
+
+/// Run the command to completion and report on it, returning the exit code to use
+fn run(options: Options) -> io::Result<i32> {
+    // Checked before spawning, so a missing file is not mistaken for a failed start
+    let mut feeder = match &options.feed {
+        Some(path) => Some(
+            Feeder::open(path, options.chunk_size, options.chunk_interval)
+                .map_err(|err| io::Error::new(err.kind(), format!("cannot open --feed {}: {}", path, err)))?,
+        ),
+        None => None,
+    };
+    let mut process = match options.builder.spawn() {
+        Ok(process) => process,
+        Err(err) => {
+            eprintln!("betahub-pw: {}", err);
+            return Ok(SPAWN_FAILED);
+        }
+    };
+    let pid = process.id();
+
+    let mut stdin_bytes = 0;
+    let mut stdin_error = None;
+    loop {
+        if let Some(feed) = &mut feeder {
+            let more = feed.feed(&mut process).unwrap_or_else(|err| {
+                stdin_error = Some(err.to_string());
+                false
+            });
+            stdin_bytes = feed.written;
+            if !more {
+                process.close_stdin();
+                feeder = None;
+            }
+        }
+
+        copy_output(&mut process)?;
+        if !process.is_running() {
+            break;
+        }
+
+        let mut wait = POLL_INTERVAL;
+        if let Some(feed) = &feeder {
+            wait = wait.min(feed.next_at.saturating_duration_since(Instant::now()));
+        }
+        thread::sleep(wait);
+    }
+
+    if let Ok(output) = process.wait_with_output() {
+        io::stdout().write_all(&output.stdout)?;
+        io::stderr().write_all(&output.stderr)?;
+    }
+    io::stdout().flush()?;
+
+    let exit = process.exit_info();
+    let timed_out = exit.as_ref().is_some_and(|exit| exit.reason == TerminationReason::TimedOut);
+    let usage = process.resource_usage();
+    let _ = process.close();
+    let cgroup = process.cgroup_stats().ok();
+    if let Some(path) = &options.events {
+        process.write_events_jsonl(File::create(path)?).map_err(io::Error::other)?;
+    }
+
+    let report = json!({
+        "program": options.builder.get_program(),
+        "args": options.builder.get_args(),
+        "pid": pid,
+        "timed_out": timed_out,
+        "stdin_bytes": stdin_bytes,
+        "stdin_error": stdin_error,
+        "exit": exit.as_ref().map(exit_json),
+        "usage": usage.as_ref().map(usage_json),
+        "cgroup": cgroup.as_ref().map(cgroup_json),
+    });
+    match &options.report {
+        Some(path) => std::fs::write(path, format!("{:#}\n", report))?,
+        None => eprintln!("{:#}", report),
+    }
+
+    if timed_out {
+        return Ok(TIMED_OUT);
+    }
+    Ok(match exit {
+        Some(ExitInfo { code: Some(code), .. }) => code,
+        Some(ExitInfo { signal: Some(signal), .. }) => 128 + signal,
+        _ => 1,
+    })
+}
+
+/// Copy output captured so far to this process's streams
+fn copy_output(process: &mut Process) -> io::Result<()> {
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = process.read_stdout(&mut buf).map_err(io::Error::other)?;
+        if n == 0 {
+            break;
+        }
+        io::stdout().write_all(&buf[..n])?;
+    }
+    io::stdout().flush()?;
+    loop {
+        let n = process.read_stderr(&mut buf).map_err(io::Error::other)?;
+        if n == 0 {
+            break;
+        }
+        io::stderr().write_all(&buf[..n])?;
+    }
+    Ok(())
+}
+
+fn exit_json(exit: &ExitInfo) -> Value {
+    let (reason, limit) = match exit.reason {
+        TerminationReason::Exited => ("exited", None),
+        TerminationReason::Signaled => ("signaled", None),
+        TerminationReason::LimitExceeded(resource) => ("limit_exceeded", Some(format!("{:?}", resource))),
+        TerminationReason::Stalled => ("stalled", None),
+        TerminationReason::TimedOut => ("timed_out", None),
+    };
+    json!({
+        "code": exit.code,
+        "signal": exit.signal,
+        "core_dumped": exit.core_dumped,
+        "reason": reason,
+        "limit": limit,
+        "stall": exit.stall.map(|stall| json!({
+            "at_ms": stall.at.as_millis() as u64,
+            "idle_ms": stall.idle.as_millis() as u64,
+        })),
+    })
+}
+
+fn usage_json(usage: &ResourceUsage) -> Value {
+    json!({
+        "wall_ms": usage.wall_time.as_millis() as u64,
+        "user_ms": usage.user_time.as_millis() as u64,
+        "system_ms": usage.system_time.as_millis() as u64,
+        "max_rss": usage.max_rss,
+        "minor_faults": usage.minor_faults,
+        "major_faults": usage.major_faults,
+        "voluntary_switches": usage.voluntary_switches,
+        "involuntary_switches": usage.involuntary_switches,
+    })
+}
+
+fn cgroup_json(stats: &CgroupStats) -> Value {
+    json!({
+        "memory_current": stats.memory_current,
+        "memory_peak": stats.memory_peak,
+        "cpu_usage_ms": stats.cpu_usage.as_millis() as u64,
+        "cpu_user_ms": stats.cpu_user.as_millis() as u64,
+        "cpu_system_ms": stats.cpu_system.as_millis() as u64,
+    })
+}
@@ -0,0 +1,142 @@
+//! Deterministic child process for the tests
+//!
+//! The arguments are a script of actions carried out in order, after which
+//! the helper exits with code 0:
+//!
+//! - `out TEXT` / `err TEXT`: print a line to stdout / stderr
+//! - `emit N` / `emit-err N`: print N bytes to stdout / stderr
+//! - `echo` / `echo-err`: copy stdin to stdout / stderr until EOF
+//! - `read-line`: wait for a line on stdin
+//! - `sleep MS`: sleep for MS milliseconds
+//! - `ignore-term`: ignore SIGTERM (Unix only)
+//! - `signal N`: raise signal N (Unix only)
+//! - `fork N`: start N grandchildren that hang, printing their IDs to stdout
+//! - `hang`: sleep forever
+//! - `exit CODE`: exit with CODE right away
+
+use std::io::{self, BufRead, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Exit code for a malformed script
+const USAGE: i32 = 2;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut args = args.iter().map(String::as_str);
+
+    while let Some(action) = args.next() {
+        if let Err(err) = run(action, &mut args) {
+            eprintln!("pw-testchild: {}: {}", action, err);
+            process::exit(USAGE);
+        }
+    }
+}
+
+/// Carry out one action, taking its operands from `args`
+fn run<'a>(action: &str, args: &mut impl Iterator<Item = &'a str>) -> io::Result<()> {
+    let mut operand = || {
+        args.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing operand"))
+    };
+    let mut number = || -> io::Result<u64> {
+        operand()?
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "not a number"))
+    };
+
+    match action {
+        "out" => {
+            let text = operand()?;
+            let mut stdout = io::stdout();
+            writeln!(stdout, "{}", text)?;
+            stdout.flush()?;
+        }
+        "err" => writeln!(io::stderr(), "{}", operand()?)?,
+        "emit" => emit(&mut io::stdout(), number()?)?,
+        "emit-err" => emit(&mut io::stderr(), number()?)?,
+        "echo" => echo(&mut io::stdout())?,
+        "echo-err" => echo(&mut io::stderr())?,
+        "read-line" => {
+            io::stdin().lock().read_line(&mut String::new())?;
+        }
+        "sleep" => thread::sleep(Duration::from_millis(number()?)),
+        "ignore-term" => ignore_term()?,
+        "signal" => raise(number()? as i32)?,
+        "fork" => {
+            let count = number()?;
+            let program = std::env::current_exe()?;
+            let mut stdout = io::stdout();
+            for _ in 0..count {
+                let child = Command::new(&program)
+                    .arg("hang")
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+                writeln!(stdout, "{}", child.id())?;
+            }
+            stdout.flush()?;
+        }
+        "hang" => loop {
+            thread::sleep(Duration::from_secs(3600));
+        },
+        "exit" => process::exit(number()? as i32),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "unknown action")),
+    }
+    Ok(())
+}
+
+/// Write `count` bytes of a repeating pattern
+fn emit(out: &mut impl Write, count: u64) -> io::Result<()> {
+    let chunk: Vec<u8> = (0..8192u32).map(|i| b'a' + (i % 26) as u8).collect();
+    let mut left = count;
+    while left > 0 {
+        let n = left.min(chunk.len() as u64) as usize;
+        out.write_all(&chunk[..n])?;
+        left -= n as u64;
+    }
+    out.flush()
+}
+
+/// Copy stdin to `out` as it arrives, until EOF
+fn echo(out: &mut impl Write) -> io::Result<()> {
+    let mut stdin = io::stdin().lock();
+    let mut buf = [0u8; 8192];
+    loop {
+        match stdin.read(&mut buf)? {
+            0 => return Ok(()),
+            n => {
+                out.write_all(&buf[..n])?;
+                out.flush()?;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn ignore_term() -> io::Result<()> {
+    if unsafe { libc::signal(libc::SIGTERM, libc::SIG_IGN) } == libc::SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn raise(signal: i32) -> io::Result<()> {
+    if unsafe { libc::raise(signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn ignore_term() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "signals are not supported"))
+}
+
+#[cfg(not(unix))]
+fn raise(_signal: i32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "signals are not supported"))
+}
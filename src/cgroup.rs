@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Placement of a spawned process in its own cgroup v2 (Linux only)
+#[derive(Debug, Clone)]
+pub struct CgroupConfig {
+    /// Existing cgroup under which a child cgroup is created for the process
+    pub parent: PathBuf,
+
+    /// Memory limit in bytes (`memory.max`)
+    pub memory_max: Option<u64>,
+
+    /// CPU bandwidth as quota and period (`cpu.max`)
+    pub cpu_max: Option<(Duration, Duration)>,
+}
+
+impl CgroupConfig {
+    /// Create the process's cgroup under `parent`, without limits
+    pub fn new(parent: impl Into<PathBuf>) -> Self {
+        CgroupConfig {
+            parent: parent.into(),
+            memory_max: None,
+            cpu_max: None,
+        }
+    }
+
+    /// Limit the memory of the process and its descendants
+    pub fn memory_max(mut self, bytes: u64) -> Self {
+        self.memory_max = Some(bytes);
+        self
+    }
+
+    /// Allow `quota` of CPU time per `period`, e.g. 50ms per 100ms for half a core
+    pub fn cpu_max(mut self, quota: Duration, period: Duration) -> Self {
+        self.cpu_max = Some((quota, period));
+        self
+    }
+}
+
+/// Resource usage accounted to a process's cgroup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CgroupStats {
+    /// Current memory usage in bytes, if the memory controller is enabled
+    pub memory_current: Option<u64>,
+
+    /// Highest memory usage in bytes, if the memory controller is enabled and reports it
+    pub memory_peak: Option<u64>,
+
+    /// Total CPU time consumed
+    pub cpu_usage: Duration,
+
+    /// CPU time spent in user mode
+    pub cpu_user: Duration,
+
+    /// CPU time spent in the kernel
+    pub cpu_system: Duration,
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::linux::*;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{CgroupConfig, CgroupStats};
+    use std::fs::{self, File, OpenOptions};
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Number of cgroups created by this process, used to name them uniquely
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+    /// A cgroup created for one process, removed again when dropped
+    pub(crate) struct Cgroup {
+        /// Directory of the cgroup
+        path: PathBuf,
+
+        /// `cgroup.procs` of the cgroup, written by the child to move itself in
+        procs: File,
+    }
+
+    impl Cgroup {
+        /// Create a child cgroup under the configured parent and apply the limits
+        pub(crate) fn create(config: &CgroupConfig) -> io::Result<Self> {
+            let name = format!(
+                "betahub_pw_{}_{}",
+                std::process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = config.parent.join(name);
+
+            // Limits need their controller enabled for the parent's children
+            let mut controllers = Vec::new();
+            if config.memory_max.is_some() {
+                controllers.push("memory");
+            }
+            if config.cpu_max.is_some() {
+                controllers.push("cpu");
+            }
+            enable_controllers(&config.parent, &controllers)?;
+
+            fs::create_dir(&path)?;
+            let procs = match Self::configure(&path, config) {
+                Ok(procs) => procs,
+                Err(err) => {
+                    let _ = fs::remove_dir(&path);
+                    return Err(err);
+                }
+            };
+
+            Ok(Cgroup { path, procs })
+        }
+
+        fn configure(path: &Path, config: &CgroupConfig) -> io::Result<File> {
+            if let Some(bytes) = config.memory_max {
+                fs::write(path.join("memory.max"), bytes.to_string())?;
+            }
+            if let Some((quota, period)) = config.cpu_max {
+                fs::write(
+                    path.join("cpu.max"),
+                    format!("{} {}", quota.as_micros(), period.as_micros()),
+                )?;
+            }
+
+            OpenOptions::new().write(true).open(path.join("cgroup.procs"))
+        }
+
+        /// Directory of the cgroup
+        pub(crate) fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Move the child into the cgroup between fork and exec
+        ///
+        /// Done before any other setup, so everything the child does is accounted.
+        pub(crate) fn attach(&self, command: &mut Command) -> io::Result<()> {
+            // The hook must own its descriptor
+            let procs = self.procs.try_clone()?;
+
+            unsafe {
+                command.pre_exec(move || {
+                    // "0" moves the writing process
+                    if libc::write(procs.as_raw_fd(), b"0".as_ptr().cast(), 1) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            Ok(())
+        }
+
+        /// Read the accounted resource usage
+        pub(crate) fn stats(&self) -> io::Result<CgroupStats> {
+            let mut stats = CgroupStats {
+                memory_current: read_number(&self.path.join("memory.current")),
+                memory_peak: read_number(&self.path.join("memory.peak")),
+                ..CgroupStats::default()
+            };
+
+            for line in fs::read_to_string(self.path.join("cpu.stat"))?.lines() {
+                let Some((key, value)) = line.split_once(' ') else {
+                    continue;
+                };
+                let Ok(micros) = value.trim().parse() else {
+                    continue;
+                };
+                match key {
+                    "usage_usec" => stats.cpu_usage = Duration::from_micros(micros),
+                    "user_usec" => stats.cpu_user = Duration::from_micros(micros),
+                    "system_usec" => stats.cpu_system = Duration::from_micros(micros),
+                    _ => {}
+                }
+            }
+
+            Ok(stats)
+        }
+
+        /// Kill every process left in the cgroup, such as descendants of the child, and remove it
+        pub(crate) fn remove(self) {
+            // cgroup.kill needs Linux 5.14; older kernels keep stragglers alive
+            let _ = fs::write(self.path.join("cgroup.kill"), "1");
+
+            // Killed processes leave the cgroup asynchronously, so removal is retried briefly
+            for _ in 0..50 {
+                match fs::remove_dir(&self.path) {
+                    Err(err) if err.raw_os_error() == Some(libc::EBUSY) => thread::sleep(Duration::from_millis(10)),
+                    _ => return,
+                }
+            }
+        }
+    }
+
+    impl Drop for Cgroup {
+        fn drop(&mut self) {
+            // Fails while processes are still inside, which are left running
+            let _ = fs::remove_dir(&self.path);
+        }
+    }
+
+    /// Enable controllers for the children of `parent` that are not enabled yet
+    fn enable_controllers(parent: &Path, controllers: &[&str]) -> io::Result<()> {
+        if controllers.is_empty() {
+            return Ok(());
+        }
+
+        let subtree_control = parent.join("cgroup.subtree_control");
+        let enabled = fs::read_to_string(&subtree_control)?;
+        for controller in controllers {
+            if !enabled.split_whitespace().any(|enabled| enabled == *controller) {
+                fs::write(&subtree_control, format!("+{}", controller))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_number(path: &Path) -> Option<u64> {
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+}
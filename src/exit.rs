@@ -0,0 +1,152 @@
+use crate::limits::{Limit, Resource};
+use crate::usage::ResourceUsage;
+use crate::watchdog::{Stall, WatchdogAction};
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// How far the CPU time reported for a process may fall short of the limit that killed it
+///
+/// The kernel enforces the limit on its scheduler clock, while the usage is
+/// reported from tick-based accounting that can lag slightly behind.
+#[cfg(unix)]
+const CPU_LIMIT_SLACK: Duration = Duration::from_millis(100);
+
+/// Why a process terminated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// The process exited on its own with an exit code
+    Exited,
+
+    /// The process was terminated by a signal
+    Signaled,
+
+    /// The process was terminated for exceeding a resource limit
+    ///
+    /// CPU time and file size limits are detected from their dedicated
+    /// signals, and from a `SIGKILL` once the hard CPU time limit was used
+    /// up. A crash while an address space limit is set is attributed
+    /// to that limit, since failing allocations are its usual cause.
+    LimitExceeded(Resource),
+
+    /// The process was terminated by the watchdog after stalling
+    Stalled,
+
+    /// The process was killed for exceeding the builder's timeout
+    TimedOut,
+}
+
+/// How a process ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitInfo {
+    /// Exit code, if the process exited on its own
+    pub code: Option<i32>,
+
+    /// Number of the terminating signal (Unix only)
+    pub signal: Option<i32>,
+
+    /// Whether a core dump was written (Unix only)
+    pub core_dumped: bool,
+
+    /// Why the process terminated
+    pub reason: TerminationReason,
+
+    /// The most recent stall detected by the watchdog, if any
+    pub stall: Option<Stall>,
+}
+
+impl ExitInfo {
+    /// Describe an exit status, taking the limits the process ran under into account
+    ///
+    /// A `SIGKILL` is only attributed to the hard CPU time limit if the
+    /// library did not kill the process itself, as `killed` tells, and its
+    /// `usage` shows it used up that much CPU time.
+    pub(crate) fn new(
+        status: ExitStatus,
+        limits: &[(Resource, Limit)],
+        killed: bool,
+        usage: Option<&ResourceUsage>,
+    ) -> Self {
+        let code = status.code();
+
+        #[cfg(unix)]
+        let (signal, core_dumped) = {
+            use std::os::unix::process::ExitStatusExt;
+            (status.signal(), status.core_dumped())
+        };
+        #[cfg(not(unix))]
+        let (signal, core_dumped) = (None, false);
+
+        let cpu_time = usage.map(|usage| usage.user_time + usage.system_time).unwrap_or_default();
+        let reason = match signal {
+            None => TerminationReason::Exited,
+            Some(signal) => limit_for_signal(signal, limits, killed, cpu_time)
+                .map(TerminationReason::LimitExceeded)
+                .unwrap_or(TerminationReason::Signaled),
+        };
+
+        ExitInfo {
+            code,
+            signal,
+            core_dumped,
+            reason,
+            stall: None,
+        }
+    }
+
+    /// Add the watchdog's last stall, attributing the termination to it if its action caused it
+    pub(crate) fn with_stall(mut self, stall: Option<Stall>, acted: Option<WatchdogAction>) -> Self {
+        self.stall = stall;
+
+        #[cfg(unix)]
+        let terminated = match acted {
+            Some(WatchdogAction::Signal(signal)) => self.signal == Some(signal),
+            Some(WatchdogAction::Kill) => self.signal == Some(libc::SIGKILL),
+            _ => false,
+        };
+        #[cfg(not(unix))]
+        let terminated = acted == Some(WatchdogAction::Kill);
+
+        if terminated {
+            self.reason = TerminationReason::Stalled;
+        }
+        self
+    }
+
+    /// Attribute the termination to the timeout if the process was killed for it
+    pub(crate) fn with_timeout(mut self, expired: bool) -> Self {
+        #[cfg(unix)]
+        let terminated = expired && self.signal == Some(libc::SIGKILL);
+        #[cfg(not(unix))]
+        let terminated = expired;
+
+        if terminated {
+            self.reason = TerminationReason::TimedOut;
+        }
+        self
+    }
+}
+
+#[cfg(unix)]
+fn limit_for_signal(signal: i32, limits: &[(Resource, Limit)], killed: bool, cpu_time: Duration) -> Option<Resource> {
+    let limited = |resource| limits.iter().any(|(limited, _)| *limited == resource);
+
+    // Other SIGKILLs, such as from the OOM killer or `kill -9`, can hit a process under a CPU limit too
+    let cpu_exhausted = limits.iter().any(|(limited, limit)| {
+        *limited == Resource::CpuTime && limit.hard != crate::limits::UNLIMITED && cpu_time + CPU_LIMIT_SLACK >= Duration::from_secs(limit.hard)
+    });
+
+    match signal {
+        libc::SIGXCPU => Some(Resource::CpuTime),
+        libc::SIGXFSZ => Some(Resource::FileSize),
+        libc::SIGKILL if !killed && cpu_exhausted => Some(Resource::CpuTime),
+        libc::SIGSEGV | libc::SIGABRT | libc::SIGBUS if limited(Resource::AddressSpace) => {
+            Some(Resource::AddressSpace)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_for_signal(_signal: i32, _limits: &[(Resource, Limit)], _killed: bool, _cpu_time: Duration) -> Option<Resource> {
+    None
+}
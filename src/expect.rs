@@ -0,0 +1,143 @@
+use crate::output::StreamId;
+use crate::process::{ProcessError, Result};
+use regex::bytes::Regex;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Most unmatched output kept per stream; older bytes are dropped
+const MAX_PENDING: usize = 64 * 1024;
+
+/// Text to wait for in a process's output
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    /// Match the given text literally
+    pub fn literal(text: &str) -> Self {
+        Pattern {
+            regex: Regex::new(&regex::escape(text)).expect("escaped literal is a valid regex"),
+        }
+    }
+
+    /// Match a regular expression, whose groups are returned as captures
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Ok(Pattern {
+            regex: Regex::new(pattern)?,
+        })
+    }
+
+    /// Number of capture groups, not counting the whole match
+    pub fn groups(&self) -> usize {
+        self.regex.captures_len() - 1
+    }
+}
+
+/// Output matched by `Process::wait_for_output`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputMatch {
+    /// Stream the match was found in
+    pub stream: StreamId,
+
+    /// The matched text
+    pub text: String,
+
+    /// Capture groups of a regex pattern, `None` for groups that did not participate
+    pub captures: Vec<Option<String>>,
+}
+
+/// Output of one stream not yet consumed by a match
+#[derive(Default)]
+struct PendingOutput {
+    data: Vec<u8>,
+    open: bool,
+}
+
+/// Output seen by the stream readers, searched by `wait_for_output`
+#[derive(Default)]
+pub(crate) struct OutputWatch {
+    /// Pending stdout and stderr output
+    streams: Mutex<[PendingOutput; 2]>,
+
+    /// Signalled whenever output arrives or a stream closes
+    changed: Condvar,
+}
+
+fn slot(stream: StreamId) -> usize {
+    match stream {
+        StreamId::Stdout => 0,
+        _ => 1,
+    }
+}
+
+impl OutputWatch {
+    /// Mark a stream as being drained by a reader
+    pub(crate) fn open(&self, stream: StreamId) {
+        self.streams.lock().unwrap()[slot(stream)].open = true;
+    }
+
+    /// Append output read from a stream
+    pub(crate) fn push(&self, stream: StreamId, data: &[u8]) {
+        let mut streams = self.streams.lock().unwrap();
+        let pending = &mut streams[slot(stream)].data;
+
+        pending.extend_from_slice(data);
+        if pending.len() > MAX_PENDING {
+            let excess = pending.len() - MAX_PENDING;
+            pending.drain(..excess);
+        }
+
+        self.changed.notify_all();
+    }
+
+    /// Mark a stream as having reached EOF
+    pub(crate) fn close(&self, stream: StreamId) {
+        self.streams.lock().unwrap()[slot(stream)].open = false;
+        self.changed.notify_all();
+    }
+
+    /// Block until `pattern` appears in stdout or stderr
+    ///
+    /// Output up to the end of the match is consumed, so repeated calls find
+    /// successive occurrences.
+    pub(crate) fn wait_for(&self, pattern: &Pattern, timeout: Duration) -> Result<OutputMatch> {
+        let deadline = Instant::now() + timeout;
+        let mut streams = self.streams.lock().unwrap();
+
+        loop {
+            for stream in [StreamId::Stdout, StreamId::Stderr] {
+                let pending = &mut streams[slot(stream)].data;
+                let Some(captures) = pattern.regex.captures(pending) else {
+                    continue;
+                };
+
+                let whole = captures.get(0).expect("group 0 always participates");
+                let found = OutputMatch {
+                    stream,
+                    text: String::from_utf8_lossy(whole.as_bytes()).into_owned(),
+                    captures: captures
+                        .iter()
+                        .skip(1)
+                        .map(|group| group.map(|group| String::from_utf8_lossy(group.as_bytes()).into_owned()))
+                        .collect(),
+                };
+
+                let end = whole.end();
+                pending.drain(..end);
+                return Ok(found);
+            }
+
+            if streams.iter().all(|pending| !pending.open) {
+                return Err(ProcessError::OutputClosed);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ProcessError::Timeout);
+            }
+
+            streams = self.changed.wait_timeout(streams, deadline - now).unwrap().0;
+        }
+    }
+}
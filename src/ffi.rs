@@ -1,7 +1,549 @@
-use crate::process::Process;
-use libc::{c_char, c_int, size_t};
-use std::ffi::CStr;
+use crate::cgroup::CgroupConfig;
+use crate::exit::{ExitInfo, TerminationReason};
+use crate::expect::Pattern;
+use crate::limits::Resource;
+use crate::metrics::MetricsSample;
+use crate::output::{FileSink, OutputSink};
+use crate::pipeline::{pipefail, Pipeline, RunningPipeline};
+use crate::pool::{JobResult, JobStatus, PoolProgress, ProcessPool};
+use crate::process::ProcessError;
+use crate::profile::Profile;
+use crate::priority::{IoPriority, Priority, SchedPolicy};
+use crate::probe;
+use crate::reaper;
+use crate::process::{Process, ProcessBuilder, Shutdown, StdinSource};
+use crate::pty::PtySize;
+use crate::replay::{ReplayBuffer, ReplayConfig};
+use crate::sandbox::SandboxConfig;
+use crate::supervisor::{RestartPolicy, Supervisor, SupervisorConfig, SupervisorEvent};
+use crate::watchdog::{Watchdog, WatchdogAction};
+use crate::which::Resolver;
+use crate::logging::{self, Redactor};
+use libc::{c_char, c_int, c_uint, c_void, size_t};
+use log::{warn, Level, LevelFilter};
+use std::ffi::{CStr, CString};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::Arc;
+
+/// `kind` value of `process_probe_supports` querying encoders
+pub const PROBE_ENCODER: c_int = 0;
+
+/// `kind` value of `process_probe_supports` querying muxers
+pub const PROBE_MUXER: c_int = 1;
+
+/// `kind` value of `process_probe_supports` querying pixel formats
+pub const PROBE_PIX_FMT: c_int = 2;
+
+/// Result of `process_write_stdin` for a process whose stdin is not piped
+pub const WRITE_STDIN_NOT_PIPED: isize = -2;
+
+/// `resource` value of `process_builder_limit` limiting the address space in bytes
+pub const LIMIT_ADDRESS_SPACE: c_int = 0;
+
+/// `resource` value of `process_builder_limit` limiting CPU time in seconds
+pub const LIMIT_CPU_TIME: c_int = 1;
+
+/// `resource` value of `process_builder_limit` limiting open file descriptors
+pub const LIMIT_OPEN_FILES: c_int = 2;
+
+/// `resource` value of `process_builder_limit` limiting core dump size in bytes
+pub const LIMIT_CORE_SIZE: c_int = 3;
+
+/// `resource` value of `process_builder_limit` limiting the user's processes and threads
+pub const LIMIT_PROCESSES: c_int = 4;
+
+/// `resource` value of `process_builder_limit` limiting written file size in bytes
+pub const LIMIT_FILE_SIZE: c_int = 5;
+
+/// `reason` of a `ProcessExitInfo` for a process that exited on its own
+pub const EXIT_REASON_EXITED: c_int = 0;
+
+/// `reason` of a `ProcessExitInfo` for a process terminated by a signal
+pub const EXIT_REASON_SIGNALED: c_int = 1;
+
+/// `reason` of a `ProcessExitInfo` for a process terminated for exceeding a limit
+pub const EXIT_REASON_LIMIT_EXCEEDED: c_int = 2;
+
+/// `reason` of a `ProcessExitInfo` for a process terminated by the watchdog after stalling
+pub const EXIT_REASON_STALLED: c_int = 3;
+
+/// `reason` of a `ProcessExitInfo` for a process killed for exceeding its timeout
+pub const EXIT_REASON_TIMED_OUT: c_int = 4;
+
+/// `action` of `process_builder_watchdog` only reporting stalls
+pub const WATCHDOG_NOTIFY: c_int = 0;
+
+/// `action` of `process_builder_watchdog` sending a signal on a stall
+pub const WATCHDOG_SIGNAL: c_int = 1;
+
+/// `action` of `process_builder_watchdog` killing the process on a stall
+pub const WATCHDOG_KILL: c_int = 2;
+
+/// Callback of `process_builder_watchdog`, given the user data and the inactivity in milliseconds
+pub type StallCallbackFn = extern "C" fn(user_data: *mut c_void, idle_ms: u64);
+
+/// User data handed back to a C callback
+struct UserData(*mut c_void);
+
+// Sharing the pointer across threads is the caller's responsibility, as documented
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Value of a `process_set_priority` argument leaving that setting unchanged
+pub const PRIORITY_UNCHANGED: c_int = c_int::MIN;
+
+/// `policy` value of `process_set_priority` for the default time-sharing policy
+pub const SCHED_POLICY_OTHER: c_int = 0;
+
+/// `policy` value of `process_set_priority` for batch (non-interactive) scheduling
+pub const SCHED_POLICY_BATCH: c_int = 1;
+
+/// `policy` value of `process_set_priority` for running only when the CPU is otherwise idle
+pub const SCHED_POLICY_IDLE: c_int = 2;
+
+/// `io_class` value of `process_set_priority` for the real-time I/O class
+pub const IO_CLASS_REALTIME: c_int = 1;
+
+/// `io_class` value of `process_set_priority` for the default best-effort I/O class
+pub const IO_CLASS_BEST_EFFORT: c_int = 2;
+
+/// `io_class` value of `process_set_priority` for the idle I/O class
+pub const IO_CLASS_IDLE: c_int = 3;
+
+/// `policy` value of `process_supervisor_start` never restarting the process
+pub const RESTART_NEVER: c_int = 0;
+
+/// `policy` value of `process_supervisor_start` restarting the process unless it exited with code 0
+pub const RESTART_ON_FAILURE: c_int = 1;
+
+/// `policy` value of `process_supervisor_start` restarting the process after every exit
+pub const RESTART_ALWAYS: c_int = 2;
+
+/// `kind` of a `ProcessSupervisorEvent` for a spawned instance of the process
+pub const SUPERVISOR_EVENT_STARTED: c_int = 0;
+
+/// `kind` of a `ProcessSupervisorEvent` for an instance of the process that ended
+pub const SUPERVISOR_EVENT_EXITED: c_int = 1;
+
+/// `kind` of a `ProcessSupervisorEvent` for a restart about to be made
+pub const SUPERVISOR_EVENT_RESTARTING: c_int = 2;
+
+/// `kind` of a `ProcessSupervisorEvent` for a restart that failed to spawn the process
+pub const SUPERVISOR_EVENT_SPAWN_FAILED: c_int = 3;
+
+/// `kind` of a `ProcessSupervisorEvent` for supervision ending as the policy does not restart
+pub const SUPERVISOR_EVENT_FINISHED: c_int = 4;
+
+/// `kind` of a `ProcessSupervisorEvent` for supervision ending after too many restarts
+pub const SUPERVISOR_EVENT_GAVE_UP: c_int = 5;
+
+/// Status of a pool job waiting for a free slot
+pub const POOL_JOB_QUEUED: c_int = 0;
+
+/// Status of a pool job whose process is running
+pub const POOL_JOB_RUNNING: c_int = 1;
+
+/// Status of a pool job whose process exited
+pub const POOL_JOB_COMPLETED: c_int = 2;
+
+/// Status of a pool job whose process could not be spawned
+pub const POOL_JOB_FAILED: c_int = 3;
+
+/// Status of a pool job cancelled before or while running
+pub const POOL_JOB_CANCELLED: c_int = 4;
+
+/// `level` of `process_set_log_callback` turning logging off
+pub const LOG_LEVEL_OFF: c_int = 0;
+
+/// Log level of errors
+pub const LOG_LEVEL_ERROR: c_int = 1;
+
+/// Log level of warnings, such as failed spawns, I/O errors and stalls
+pub const LOG_LEVEL_WARN: c_int = 2;
+
+/// Log level of process lifecycle events, such as spawns, signals and exits
+pub const LOG_LEVEL_INFO: c_int = 3;
+
+/// Log level of debugging details
+pub const LOG_LEVEL_DEBUG: c_int = 4;
+
+/// Log level of tracing details
+pub const LOG_LEVEL_TRACE: c_int = 5;
+
+/// Callback of `process_set_log_callback`, given the user data, one of the
+/// `LOG_LEVEL_*` constants, the module the record comes from and the message
+pub type LogFn = extern "C" fn(user_data: *mut c_void, level: c_int, target: *const c_char, message: *const c_char);
+
+/// How a process ended, filled by `process_exit_info`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessExitInfo {
+    /// Exit code, -1 if the process was terminated by a signal
+    pub code: c_int,
+    
+    /// Terminating signal, 0 if the process exited on its own
+    pub signal: c_int,
+    
+    /// 1 if a core dump was written, 0 otherwise
+    pub core_dumped: c_int,
+    
+    /// One of the `EXIT_REASON_*` constants
+    pub reason: c_int,
+    
+    /// The exceeded `LIMIT_*` resource if `reason` is `EXIT_REASON_LIMIT_EXCEEDED`, -1 otherwise
+    pub limit: c_int,
+    
+    /// 1 if the watchdog detected a stall, 0 otherwise
+    pub stalled: c_int,
+}
+
+impl From<&ExitInfo> for ProcessExitInfo {
+    fn from(exit: &ExitInfo) -> Self {
+        let (reason, limit) = match exit.reason {
+            TerminationReason::Exited => (EXIT_REASON_EXITED, -1),
+            TerminationReason::Signaled => (EXIT_REASON_SIGNALED, -1),
+            TerminationReason::LimitExceeded(resource) => (EXIT_REASON_LIMIT_EXCEEDED, resource_to_c(resource)),
+            TerminationReason::Stalled => (EXIT_REASON_STALLED, -1),
+            TerminationReason::TimedOut => (EXIT_REASON_TIMED_OUT, -1),
+        };
+        
+        ProcessExitInfo {
+            code: exit.code.unwrap_or(-1),
+            signal: exit.signal.unwrap_or(0),
+            core_dumped: exit.core_dumped as c_int,
+            reason,
+            limit,
+            stalled: exit.stall.is_some() as c_int,
+        }
+    }
+}
+
+/// An event of a supervised process, passed to the `process_supervisor_start` callback
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessSupervisorEvent {
+    /// One of the `SUPERVISOR_EVENT_*` constants
+    pub kind: c_int,
+    
+    /// Restarts made so far, or the number of the restart for `RESTARTING` and `SPAWN_FAILED`
+    pub restarts: u64,
+    
+    /// Process ID of the spawned instance for `STARTED`, 0 otherwise
+    pub pid: c_uint,
+    
+    /// Delay before the restart in milliseconds for `RESTARTING`, 0 otherwise
+    pub delay_ms: u64,
+    
+    /// How the instance ended for `EXITED`, zeroed otherwise
+    pub exit: ProcessExitInfo,
+}
+
+impl From<&SupervisorEvent> for ProcessSupervisorEvent {
+    fn from(event: &SupervisorEvent) -> Self {
+        let (kind, restarts) = match *event {
+            SupervisorEvent::Started { restarts, .. } => (SUPERVISOR_EVENT_STARTED, restarts),
+            SupervisorEvent::Exited { restarts, .. } => (SUPERVISOR_EVENT_EXITED, restarts),
+            SupervisorEvent::Restarting { attempt, .. } => (SUPERVISOR_EVENT_RESTARTING, attempt),
+            SupervisorEvent::SpawnFailed { attempt, .. } => (SUPERVISOR_EVENT_SPAWN_FAILED, attempt),
+            SupervisorEvent::Finished { restarts } => (SUPERVISOR_EVENT_FINISHED, restarts),
+            SupervisorEvent::GaveUp { restarts } => (SUPERVISOR_EVENT_GAVE_UP, restarts),
+        };
+        
+        let mut converted = ProcessSupervisorEvent {
+            kind,
+            restarts: restarts as u64,
+            ..ProcessSupervisorEvent::default()
+        };
+        match event {
+            SupervisorEvent::Started { pid, .. } => converted.pid = *pid,
+            SupervisorEvent::Exited { exit, .. } => converted.exit = ProcessExitInfo::from(exit),
+            SupervisorEvent::Restarting { delay, .. } => converted.delay_ms = delay.as_millis() as u64,
+            _ => {}
+        }
+        converted
+    }
+}
+
+/// Callback of `process_supervisor_start`, given the user data and the event
+pub type SupervisorEventFn = extern "C" fn(user_data: *mut c_void, event: *const ProcessSupervisorEvent);
+
+fn job_status_to_c(status: JobStatus) -> c_int {
+    match status {
+        JobStatus::Queued => POOL_JOB_QUEUED,
+        JobStatus::Running => POOL_JOB_RUNNING,
+        JobStatus::Completed => POOL_JOB_COMPLETED,
+        JobStatus::Failed => POOL_JOB_FAILED,
+        JobStatus::Cancelled => POOL_JOB_CANCELLED,
+    }
+}
+
+/// Outcome of a finished pool job, filled by `process_pool_job_result`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessJobResult {
+    /// `POOL_JOB_COMPLETED`, `POOL_JOB_FAILED` or `POOL_JOB_CANCELLED`
+    pub status: c_int,
+    
+    /// 1 if the process ran and `exit` is filled, 0 otherwise
+    pub ran: c_int,
+    
+    /// How the process ended
+    pub exit: ProcessExitInfo,
+    
+    /// Time from spawn until the process exited in microseconds
+    pub duration_us: u64,
+    
+    /// Length of the captured stdout, see `process_pool_job_stdout`
+    pub stdout_len: u64,
+    
+    /// Length of the captured stderr, see `process_pool_job_stderr`
+    pub stderr_len: u64,
+}
+
+impl From<&JobResult> for ProcessJobResult {
+    fn from(result: &JobResult) -> Self {
+        ProcessJobResult {
+            status: job_status_to_c(result.status),
+            ran: result.exit.is_some() as c_int,
+            exit: result.exit.as_ref().map(ProcessExitInfo::from).unwrap_or_default(),
+            duration_us: result.duration.as_micros() as u64,
+            stdout_len: result.stdout.len() as u64,
+            stderr_len: result.stderr.len() as u64,
+        }
+    }
+}
+
+/// Number of pool jobs in each state, filled by `process_pool_progress`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessPoolProgress {
+    /// Jobs submitted so far
+    pub submitted: u64,
+    
+    /// Jobs waiting for a free slot
+    pub queued: u64,
+    
+    /// Jobs whose process is running
+    pub running: u64,
+    
+    /// Jobs whose process exited
+    pub completed: u64,
+    
+    /// Jobs whose process could not be spawned
+    pub failed: u64,
+    
+    /// Jobs cancelled before or while running
+    pub cancelled: u64,
+}
+
+impl From<PoolProgress> for ProcessPoolProgress {
+    fn from(progress: PoolProgress) -> Self {
+        ProcessPoolProgress {
+            submitted: progress.submitted,
+            queued: progress.queued,
+            running: progress.running,
+            completed: progress.completed,
+            failed: progress.failed,
+            cancelled: progress.cancelled,
+        }
+    }
+}
+
+/// Callback of `process_pool_new`, given the user data, the job, its new `POOL_JOB_*` status and the progress
+pub type PoolProgressFn =
+    extern "C" fn(user_data: *mut c_void, job: u64, status: c_int, progress: *const ProcessPoolProgress);
+
+/// Convert the arguments of `process_set_priority`, `None` for invalid values
+///
+/// # Safety
+///
+/// `cpus` must be null or a valid pointer to an array of `cpus_len` CPU indices.
+unsafe fn priority_from_c(
+    nice: c_int,
+    policy: c_int,
+    io_class: c_int,
+    io_level: c_int,
+    cpus: *const c_uint,
+    cpus_len: size_t,
+) -> Option<Priority> {
+    let level = || u8::try_from(io_level).ok().filter(|level| *level <= 7);
+    
+    Some(Priority {
+        nice: (nice != PRIORITY_UNCHANGED).then_some(nice),
+        policy: match policy {
+            PRIORITY_UNCHANGED => None,
+            SCHED_POLICY_OTHER => Some(SchedPolicy::Other),
+            SCHED_POLICY_BATCH => Some(SchedPolicy::Batch),
+            SCHED_POLICY_IDLE => Some(SchedPolicy::Idle),
+            _ => return None,
+        },
+        io: match io_class {
+            PRIORITY_UNCHANGED => None,
+            IO_CLASS_REALTIME => Some(IoPriority::RealTime(level()?)),
+            IO_CLASS_BEST_EFFORT => Some(IoPriority::BestEffort(level()?)),
+            IO_CLASS_IDLE => Some(IoPriority::Idle),
+            _ => return None,
+        },
+        cpus: if cpus.is_null() {
+            None
+        } else {
+            let cpus = unsafe { std::slice::from_raw_parts(cpus, cpus_len) };
+            Some(cpus.iter().map(|&cpu| cpu as usize).collect())
+        },
+    })
+}
+
+/// Resources used by a finished process, filled by `process_resource_usage`
+///
+/// Only the wall-clock time is measured on Windows.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessResourceUsage {
+    /// Microseconds from spawn until the process was reaped
+    pub wall_time_us: u64,
+    
+    /// User mode CPU time in microseconds
+    pub user_time_us: u64,
+    
+    /// Kernel mode CPU time in microseconds
+    pub system_time_us: u64,
+    
+    /// Peak resident set size in bytes
+    pub max_rss_bytes: u64,
+    
+    /// Page faults served without I/O
+    pub minor_faults: u64,
+    
+    /// Page faults that required I/O
+    pub major_faults: u64,
+    
+    /// Context switches while waiting for a resource
+    pub voluntary_switches: u64,
+    
+    /// Context switches by preemption
+    pub involuntary_switches: u64,
+}
+
+/// Sampled metrics of a process or process group, filled by `process_metrics`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessMetricsSample {
+    /// CPU usage, 100 per fully used core
+    pub cpu_percent: f64,
+    
+    /// Resident memory in bytes
+    pub rss_bytes: u64,
+    
+    /// Virtual memory in bytes
+    pub vm_bytes: u64,
+    
+    /// Number of threads
+    pub threads: u64,
+    
+    /// Number of processes covered
+    pub processes: u64,
+    
+    /// Bytes read from storage per second
+    pub read_bytes_per_sec: f64,
+    
+    /// Bytes written to storage per second
+    pub write_bytes_per_sec: f64,
+}
+
+impl From<MetricsSample> for ProcessMetricsSample {
+    fn from(sample: MetricsSample) -> Self {
+        ProcessMetricsSample {
+            cpu_percent: sample.cpu_percent,
+            rss_bytes: sample.rss_bytes,
+            vm_bytes: sample.vm_bytes,
+            threads: sample.threads,
+            processes: sample.processes,
+            read_bytes_per_sec: sample.read_bytes_per_sec,
+            write_bytes_per_sec: sample.write_bytes_per_sec,
+        }
+    }
+}
+
+/// Usage accounted to a process's cgroup, filled by `process_cgroup_stats`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessCgroupStats {
+    /// Current memory usage in bytes, -1 without the memory controller
+    pub memory_current: i64,
+    
+    /// Highest memory usage in bytes, -1 if not reported
+    pub memory_peak: i64,
+    
+    /// Total CPU time in microseconds
+    pub cpu_usage_us: u64,
+    
+    /// User mode CPU time in microseconds
+    pub cpu_user_us: u64,
+    
+    /// Kernel mode CPU time in microseconds
+    pub cpu_system_us: u64,
+}
+
+fn resource_from_c(resource: c_int) -> Option<Resource> {
+    match resource {
+        LIMIT_ADDRESS_SPACE => Some(Resource::AddressSpace),
+        LIMIT_CPU_TIME => Some(Resource::CpuTime),
+        LIMIT_OPEN_FILES => Some(Resource::OpenFiles),
+        LIMIT_CORE_SIZE => Some(Resource::CoreSize),
+        LIMIT_PROCESSES => Some(Resource::Processes),
+        LIMIT_FILE_SIZE => Some(Resource::FileSize),
+        _ => None,
+    }
+}
+
+fn resource_to_c(resource: Resource) -> c_int {
+    match resource {
+        Resource::AddressSpace => LIMIT_ADDRESS_SPACE,
+        Resource::CpuTime => LIMIT_CPU_TIME,
+        Resource::OpenFiles => LIMIT_OPEN_FILES,
+        Resource::CoreSize => LIMIT_CORE_SIZE,
+        Resource::Processes => LIMIT_PROCESSES,
+        Resource::FileSize => LIMIT_FILE_SIZE,
+    }
+}
+
+/// Copy `value` into `buf` as a null-terminated string if it fits, returning its length
+///
+/// # Safety
+///
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+unsafe fn copy_c_string(value: &str, buf: *mut c_char, len: size_t) -> isize {
+    let bytes = value.as_bytes();
+    
+    if bytes.len() < len {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+        *buf.add(bytes.len()) = 0;
+    }
+    
+    bytes.len() as isize
+}
+
+/// Convert an array of C strings, stopping early at a null element
+///
+/// # Safety
+///
+/// `array` must point to `len` pointers, each null or a valid null-terminated C string.
+unsafe fn c_str_array<'a>(array: *const *const c_char, len: size_t) -> Option<Vec<&'a str>> {
+    let mut strings = Vec::with_capacity(len);
+    
+    for i in 0..len {
+        let ptr = *array.add(i);
+        if ptr.is_null() {
+            break;
+        }
+        
+        strings.push(CStr::from_ptr(ptr).to_str().ok()?);
+    }
+    
+    Some(strings)
+}
 
 /// Start a new process with the given command line
 ///
@@ -9,7 +551,7 @@ use std::ptr;
 ///
 /// `cmd` must be a valid null-terminated C string.
 #[no_mangle]
-pub extern "C" fn process_start(cmd: *const c_char) -> *mut Process {
+pub unsafe extern "C" fn process_start(cmd: *const c_char) -> *mut Process {
     // Safety check
     if cmd.is_null() {
         return ptr::null_mut();
@@ -19,7 +561,10 @@ pub extern "C" fn process_start(cmd: *const c_char) -> *mut Process {
     let cmd_str = unsafe {
         match CStr::from_ptr(cmd).to_str() {
             Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+            Err(_) => {
+                warn!("process_start: command line is not valid UTF-8");
+                return ptr::null_mut();
+            }
         }
     };
     
@@ -39,7 +584,7 @@ pub extern "C" fn process_start(cmd: *const c_char) -> *mut Process {
 /// `args_len` must be the length of the `args` array.
 /// The last element of `args` must be a null pointer.
 #[no_mangle]
-pub extern "C" fn process_start_with_args(
+pub unsafe extern "C" fn process_start_with_args(
     program: *const c_char,
     args: *const *const c_char,
     args_len: size_t,
@@ -53,26 +598,21 @@ pub extern "C" fn process_start_with_args(
     let program_str = unsafe {
         match CStr::from_ptr(program).to_str() {
             Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+            Err(_) => {
+                warn!("process_start_with_args: program is not valid UTF-8");
+                return ptr::null_mut();
+            }
         }
     };
     
     // Convert args C strings to Rust strings
-    let mut args_vec = Vec::with_capacity(args_len);
-    
-    unsafe {
-        for i in 0..args_len {
-            let arg_ptr = *args.add(i);
-            if arg_ptr.is_null() {
-                break;
-            }
-            
-            match CStr::from_ptr(arg_ptr).to_str() {
-                Ok(s) => args_vec.push(s),
-                Err(_) => return ptr::null_mut(),
-            }
+    let args_vec = match unsafe { c_str_array(args, args_len) } {
+        Some(args) => args,
+        None => {
+            warn!("process_start_with_args: arguments of {} are not valid UTF-8", program_str);
+            return ptr::null_mut();
         }
-    }
+    };
     
     // Create the process
     match Process::new_with_args(program_str, &args_vec) {
@@ -81,14 +621,72 @@ pub extern "C" fn process_start_with_args(
     }
 }
 
+/// Start a process described by a profile
+///
+/// `profile` is either the JSON text of a profile or the path of a TOML or
+/// JSON profile file, see `Profile`. `vars` are `name=value` strings
+/// filling in the profile's `${name}` references. Returns null on error,
+/// which is logged, e.g. for an invalid profile or a missing variable.
+///
+/// # Safety
+///
+/// `profile` must be a valid null-terminated C string.
+/// `vars` must be an array of `vars_len` valid null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn process_start_profile(
+    profile: *const c_char,
+    vars: *const *const c_char,
+    vars_len: size_t,
+) -> *mut Process {
+    // Safety check
+    if profile.is_null() || (vars.is_null() && vars_len > 0) {
+        return ptr::null_mut();
+    }
+    
+    let Ok(profile) = (unsafe { CStr::from_ptr(profile).to_str() }) else {
+        warn!("process_start_profile: profile is not valid UTF-8");
+        return ptr::null_mut();
+    };
+    let Some(vars) = (unsafe { c_str_array(vars, vars_len) }) else {
+        warn!("process_start_profile: variables are not valid UTF-8");
+        return ptr::null_mut();
+    };
+    
+    let mut values = HashMap::new();
+    for var in vars {
+        let Some((name, value)) = var.split_once('=') else {
+            warn!("process_start_profile: variable {:?} is not name=value", var);
+            return ptr::null_mut();
+        };
+        values.insert(name.to_string(), value.to_string());
+    }
+    
+    let loaded = if profile.trim_start().starts_with('{') {
+        Profile::from_json(profile)
+    } else {
+        Profile::load(profile)
+    };
+    match loaded.and_then(|profile| profile.spawn(&values)) {
+        Ok(process) => Box::into_raw(Box::new(process)),
+        Err(err) => {
+            warn!("process_start_profile: {}", err);
+            ptr::null_mut()
+        }
+    }
+}
+
 /// Write data to the process's stdin
 ///
+/// Returns the number of bytes written, `WRITE_STDIN_NOT_PIPED` if stdin
+/// was sourced from a file, a buffer or the null device, and -1 on other
+/// errors such as a closed pipe.
+///
 /// # Safety
 ///
 /// `proc` must be a valid pointer returned by `process_start`.
 /// `data` must be a valid pointer to a buffer of at least `len` bytes.
 #[no_mangle]
-pub extern "C" fn process_write_stdin(
+pub unsafe extern "C" fn process_write_stdin(
     proc: *mut Process,
     data: *const u8,
     len: size_t,
@@ -107,6 +705,7 @@ pub extern "C" fn process_write_stdin(
     // Write to stdin
     match process.write_stdin(data_slice) {
         Ok(bytes_written) => bytes_written as isize,
+        Err(ProcessError::StdinNotPiped) => WRITE_STDIN_NOT_PIPED,
         Err(_) => -1,
     }
 }
@@ -118,7 +717,7 @@ pub extern "C" fn process_write_stdin(
 /// `proc` must be a valid pointer returned by `process_start`.
 /// `buf` must be a valid pointer to a buffer of at least `len` bytes.
 #[no_mangle]
-pub extern "C" fn process_read_stderr(
+pub unsafe extern "C" fn process_read_stderr(
     proc: *mut Process,
     buf: *mut u8,
     len: size_t,
@@ -141,66 +740,2389 @@ pub extern "C" fn process_read_stderr(
     }
 }
 
-/// Check if the process is still running
+/// Read data from the process's captured stdout
+///
+/// Returns 0 unless the process was started with stdout capture.
 ///
 /// # Safety
 ///
 /// `proc` must be a valid pointer returned by `process_start`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
 #[no_mangle]
-pub extern "C" fn process_is_running(proc: *mut Process) -> c_int {
-    // Safety check
-    if proc.is_null() {
-        return 0;
+pub unsafe extern "C" fn process_read_stdout(
+    proc: *mut Process,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if proc.is_null() || buf.is_null() || len == 0 {
+        return -1;
     }
     
-    // Get the process
     let process = unsafe { &mut *proc };
+    let buf_slice = unsafe { std::slice::from_raw_parts_mut(buf, len) };
     
-    // Check if running
-    if process.is_running() {
-        1
-    } else {
-        0
+    match process.read_stdout(buf_slice) {
+        Ok(bytes_read) => bytes_read as isize,
+        Err(_) => -1,
     }
 }
 
-/// Wait for the process to exit and return the exit code
+/// Change the window size of a process started in PTY mode
+///
+/// Returns 0 on success and -1 on failure.
 ///
 /// # Safety
 ///
 /// `proc` must be a valid pointer returned by `process_start`.
 #[no_mangle]
-pub extern "C" fn process_wait(proc: *mut Process) -> c_int {
+pub unsafe extern "C" fn process_resize_pty(proc: *mut Process, cols: u16, rows: u16) -> c_int {
     // Safety check
     if proc.is_null() {
         return -1;
     }
     
-    // Get the process
     let process = unsafe { &mut *proc };
-    
-    // Wait for the process
-    match process.wait() {
-        Ok(exit_code) => exit_code,
+    match process.resize_pty(PtySize { cols, rows }) {
+        Ok(()) => 0,
         Err(_) => -1,
     }
 }
 
-/// Close stdin, terminate the process, and clean up resources
+/// Change the scheduling priority of a running process and all its threads
+///
+/// `nice` ranges from -20 to 19, `policy` is one of the `SCHED_POLICY_*`
+/// constants and `io_class` one of the `IO_CLASS_*` constants, with
+/// `io_level` from 0 (highest) to 7. Pass `PRIORITY_UNCHANGED` to keep a
+/// setting, and a null `cpus` to keep the CPU affinity. Returns 0 on
+/// success and -1 on failure, e.g. when raising the priority needs privileges.
 ///
 /// # Safety
 ///
 /// `proc` must be a valid pointer returned by `process_start`.
+/// `cpus` must be null or a valid pointer to an array of `cpus_len` CPU indices.
 #[no_mangle]
-pub extern "C" fn process_close(proc: *mut Process) {
+pub unsafe extern "C" fn process_set_priority(
+    proc: *mut Process,
+    nice: c_int,
+    policy: c_int,
+    io_class: c_int,
+    io_level: c_int,
+    cpus: *const c_uint,
+    cpus_len: size_t,
+) -> c_int {
     // Safety check
     if proc.is_null() {
-        return;
+        return -1;
     }
     
-    // Get the process
+    let Some(priority) = (unsafe { priority_from_c(nice, policy, io_class, io_level, cpus, cpus_len) }) else {
+        return -1;
+    };
+    
     let process = unsafe { &mut *proc };
+    match process.set_priority(&priority) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Get the resources used by a finished process
+///
+/// Returns 1 and fills `usage` once the process has finished, 0 while it is
+/// still running, and -1 on error.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `usage` must be a valid pointer to a `ProcessResourceUsage`.
+#[no_mangle]
+pub unsafe extern "C" fn process_resource_usage(proc: *mut Process, usage: *mut ProcessResourceUsage) -> c_int {
+    // Safety checks
+    if proc.is_null() || usage.is_null() {
+        return -1;
+    }
     
-    // Close the process
-    let _ = process.close();
-} 
\ No newline at end of file
+    let process = unsafe { &mut *proc };
+    let Some(used) = process.resource_usage() else {
+        return 0;
+    };
+    
+    unsafe {
+        *usage = ProcessResourceUsage {
+            wall_time_us: used.wall_time.as_micros() as u64,
+            user_time_us: used.user_time.as_micros() as u64,
+            system_time_us: used.system_time.as_micros() as u64,
+            max_rss_bytes: used.max_rss,
+            minor_faults: used.minor_faults,
+            major_faults: used.major_faults,
+            voluntary_switches: used.voluntary_switches,
+            involuntary_switches: used.involuntary_switches,
+        };
+    }
+    1
+}
+
+/// Sample the process's CPU, memory and I/O usage every `interval_ms` (Linux only)
+///
+/// Averages are taken over the last `window` samples. Returns 0 on success
+/// and -1 on failure.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_start_sampler(proc: *mut Process, interval_ms: u64, window: size_t) -> c_int {
+    // Safety check
+    if proc.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &mut *proc };
+    match process.start_sampler(std::time::Duration::from_millis(interval_ms), window) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Get the latest and average sampled metrics of the process, or of its group if `group` is non-zero
+///
+/// Returns 1 and fills `latest` and `average` if a sample is available, 0
+/// before the first sample, and -1 on error, including a group request for
+/// a process not spawned in process-group mode.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `latest` and `average` must be valid pointers to a `ProcessMetricsSample`.
+#[no_mangle]
+pub unsafe extern "C" fn process_metrics(
+    proc: *mut Process,
+    group: c_int,
+    latest: *mut ProcessMetricsSample,
+    average: *mut ProcessMetricsSample,
+) -> c_int {
+    // Safety checks
+    if proc.is_null() || latest.is_null() || average.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &*proc };
+    let Some(metrics) = process.metrics() else {
+        return 0;
+    };
+    
+    let samples = if group != 0 {
+        metrics.group_latest.zip(metrics.group_average)
+    } else {
+        Some((metrics.latest, metrics.average))
+    };
+    let Some((last, mean)) = samples else {
+        return -1;
+    };
+    
+    unsafe {
+        *latest = last.into();
+        *average = mean.into();
+    }
+    1
+}
+
+/// Read the memory and CPU usage accounted to the process's cgroup
+///
+/// After `process_close` the usage at removal of the cgroup is returned.
+/// Returns 0 on success and -1 on failure, e.g. for a process spawned
+/// without a cgroup.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `stats` must be a valid pointer to a `ProcessCgroupStats`.
+#[no_mangle]
+pub unsafe extern "C" fn process_cgroup_stats(proc: *mut Process, stats: *mut ProcessCgroupStats) -> c_int {
+    // Safety checks
+    if proc.is_null() || stats.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &*proc };
+    match process.cgroup_stats() {
+        Ok(usage) => {
+            unsafe {
+                *stats = ProcessCgroupStats {
+                    memory_current: usage.memory_current.map_or(-1, |bytes| bytes as i64),
+                    memory_peak: usage.memory_peak.map_or(-1, |bytes| bytes as i64),
+                    cpu_usage_us: usage.cpu_usage.as_micros() as u64,
+                    cpu_user_us: usage.cpu_user.as_micros() as u64,
+                    cpu_system_us: usage.cpu_system.as_micros() as u64,
+                };
+            }
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Check if the watchdog currently considers the process stalled
+///
+/// Returns 1 if stalled, 0 if not or without a watchdog, and -1 on error.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_is_stalled(proc: *mut Process) -> c_int {
+    // Safety check
+    if proc.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &*proc };
+    process.is_stalled() as c_int
+}
+
+/// Check if the process is still running
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_is_running(proc: *mut Process) -> c_int {
+    // Safety check
+    if proc.is_null() {
+        return 0;
+    }
+    
+    // Get the process
+    let process = unsafe { &mut *proc };
+    
+    // Check if running
+    if process.is_running() {
+        1
+    } else {
+        0
+    }
+}
+
+/// Wait for the process to exit and return the exit code
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_wait(proc: *mut Process) -> c_int {
+    // Safety check
+    if proc.is_null() {
+        return -1;
+    }
+    
+    // Get the process
+    let process = unsafe { &mut *proc };
+    
+    // Wait for the process
+    process.wait().unwrap_or(-1)
+}
+
+/// Wait up to `timeout_ms` until any of `procs` has exited
+///
+/// Returns the index of the process that exited, the first one if several
+/// already did, -1 on timeout and -2 on error.
+///
+/// # Safety
+///
+/// `procs` must be a valid pointer to `count` distinct pointers returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_wait_any(procs: *const *mut Process, count: size_t, timeout_ms: u64) -> c_int {
+    // Safety checks
+    if procs.is_null() || count == 0 {
+        return -2;
+    }
+    
+    let pointers = unsafe { std::slice::from_raw_parts(procs, count) };
+    if pointers.iter().any(|proc| proc.is_null()) {
+        return -2;
+    }
+    let mut processes: Vec<&mut Process> = pointers.iter().map(|&proc| unsafe { &mut *proc }).collect();
+    
+    match reaper::wait_any(&mut processes, Some(std::time::Duration::from_millis(timeout_ms))) {
+        Ok(index) => index as c_int,
+        Err(ProcessError::Timeout) => -1,
+        Err(_) => -2,
+    }
+}
+
+/// Close stdin, terminate the process, and clean up resources
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_close(proc: *mut Process) {
+    // Safety check
+    if proc.is_null() {
+        return;
+    }
+    
+    // Get the process
+    let process = unsafe { &mut *proc };
+    
+    // Close the process
+    let id = process.id();
+    if let Err(err) = process.close() {
+        warn!("Closing process {} failed: {}", id.unwrap_or_default(), err);
+    }
+}
+
+/// Start a replay buffer recording that keeps the last `buffer_seconds` on disk
+///
+/// `args` are the ffmpeg input and encoder arguments; the segment output is
+/// appended by the library.
+///
+/// # Safety
+///
+/// `ffmpeg` and `segment_dir` must be valid null-terminated C strings.
+/// `args` must be an array of valid null-terminated C strings.
+/// `args_len` must be the length of the `args` array.
+#[no_mangle]
+pub unsafe extern "C" fn process_replay_start(
+    ffmpeg: *const c_char,
+    args: *const *const c_char,
+    args_len: size_t,
+    segment_dir: *const c_char,
+    buffer_seconds: c_uint,
+    segment_seconds: c_uint,
+) -> *mut ReplayBuffer {
+    // Safety check
+    if ffmpeg.is_null() || segment_dir.is_null() || (args.is_null() && args_len > 0) {
+        return ptr::null_mut();
+    }
+    
+    // Convert C strings to Rust strings
+    let (ffmpeg_str, segment_dir_str) = unsafe {
+        match (CStr::from_ptr(ffmpeg).to_str(), CStr::from_ptr(segment_dir).to_str()) {
+            (Ok(ffmpeg), Ok(segment_dir)) => (ffmpeg, segment_dir),
+            _ => return ptr::null_mut(),
+        }
+    };
+    
+    let args_vec = match unsafe { c_str_array(args, args_len) } {
+        Some(args) => args,
+        None => return ptr::null_mut(),
+    };
+    
+    let mut config = ReplayConfig::new(ffmpeg_str, &args_vec, segment_dir_str);
+    config.buffer_seconds = buffer_seconds;
+    config.segment_seconds = segment_seconds;
+    
+    // Start recording
+    match ReplayBuffer::start(config) {
+        Ok(replay) => Box::into_raw(Box::new(replay)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Write data to the replay recorder's stdin
+///
+/// # Safety
+///
+/// `replay` must be a valid pointer returned by `process_replay_start`.
+/// `data` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_replay_write_stdin(
+    replay: *mut ReplayBuffer,
+    data: *const u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if replay.is_null() || data.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let replay = unsafe { &mut *replay };
+    let data_slice = unsafe { std::slice::from_raw_parts(data, len) };
+    
+    match replay.write_stdin(data_slice) {
+        Ok(bytes_written) => bytes_written as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Save the last `seconds` of the recording as a single clip at `path`
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `replay` must be a valid pointer returned by `process_replay_start`.
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_replay_save_last(
+    replay: *mut ReplayBuffer,
+    seconds: c_uint,
+    path: *const c_char,
+) -> c_int {
+    // Safety checks
+    if replay.is_null() || path.is_null() {
+        return -1;
+    }
+    
+    let replay = unsafe { &*replay };
+    let path_str = unsafe {
+        match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(_) => return -1,
+        }
+    };
+    
+    match replay.save_last(seconds, Path::new(path_str)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Stop the replay recording, remove its segments and free the handle
+///
+/// # Safety
+///
+/// `replay` must be a valid pointer returned by `process_replay_start`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_replay_stop(replay: *mut ReplayBuffer) {
+    // Safety check
+    if replay.is_null() {
+        return;
+    }
+    
+    let replay = unsafe { Box::from_raw(replay) };
+    let _ = replay.stop();
+}
+
+/// Check if an ffmpeg build supports an encoder, muxer or pixel format
+///
+/// `kind` is one of `PROBE_ENCODER`, `PROBE_MUXER` or `PROBE_PIX_FMT`.
+/// Returns 1 if supported, 0 if not and -1 if probing failed.
+/// Probe results are cached per executable path and modification time.
+///
+/// # Safety
+///
+/// `ffmpeg` and `name` must be valid null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn process_probe_supports(
+    ffmpeg: *const c_char,
+    kind: c_int,
+    name: *const c_char,
+) -> c_int {
+    // Safety check
+    if ffmpeg.is_null() || name.is_null() {
+        return -1;
+    }
+    
+    let (ffmpeg_str, name_str) = unsafe {
+        match (CStr::from_ptr(ffmpeg).to_str(), CStr::from_ptr(name).to_str()) {
+            (Ok(ffmpeg), Ok(name)) => (ffmpeg, name),
+            _ => return -1,
+        }
+    };
+    
+    let capabilities = match probe::probe(ffmpeg_str) {
+        Ok(capabilities) => capabilities,
+        Err(_) => return -1,
+    };
+    
+    let supported = match kind {
+        PROBE_ENCODER => capabilities.has_encoder(name_str),
+        PROBE_MUXER => capabilities.has_muxer(name_str),
+        PROBE_PIX_FMT => capabilities.has_pix_fmt(name_str),
+        _ => return -1,
+    };
+    
+    supported as c_int
+}
+
+/// Pick the first encoder supported by an ffmpeg build from a preference list
+///
+/// Returns the index of the chosen candidate, or -1 if none is supported or probing failed.
+///
+/// # Safety
+///
+/// `ffmpeg` must be a valid null-terminated C string.
+/// `candidates` must be an array of valid null-terminated C strings.
+/// `candidates_len` must be the length of the `candidates` array.
+#[no_mangle]
+pub unsafe extern "C" fn process_probe_pick_encoder(
+    ffmpeg: *const c_char,
+    candidates: *const *const c_char,
+    candidates_len: size_t,
+) -> c_int {
+    // Safety check
+    if ffmpeg.is_null() || candidates.is_null() {
+        return -1;
+    }
+    
+    let ffmpeg_str = unsafe {
+        match CStr::from_ptr(ffmpeg).to_str() {
+            Ok(s) => s,
+            Err(_) => return -1,
+        }
+    };
+    
+    let candidates_vec = match unsafe { c_str_array(candidates, candidates_len) } {
+        Some(candidates) => candidates,
+        None => return -1,
+    };
+    
+    let capabilities = match probe::probe(ffmpeg_str) {
+        Ok(capabilities) => capabilities,
+        Err(_) => return -1,
+    };
+    
+    capabilities
+        .pick_encoder(&candidates_vec)
+        .and_then(|picked| candidates_vec.iter().position(|candidate| *candidate == picked))
+        .map_or(-1, |index| index as c_int)
+}
+
+/// Resolve a program to the path of an executable file before starting it
+///
+/// `bundled_dir` may be null; otherwise it is searched first, relative paths
+/// being taken relative to the directory containing this library.
+/// `search_dirs` replaces `PATH` as the search list when `search_dirs_len` is non-zero.
+///
+/// Writes the null-terminated path into `buf` if it fits and returns its
+/// length without the terminator, so a result `>= len` means the buffer was too small.
+/// Returns -1 if no executable was found, in which case `buf` holds the
+/// error, e.g. the directories searched, truncated to fit.
+///
+/// # Safety
+///
+/// `program` must be a valid null-terminated C string.
+/// `bundled_dir` must be null or a valid null-terminated C string.
+/// `search_dirs` must be an array of `search_dirs_len` valid null-terminated C strings.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_which(
+    program: *const c_char,
+    bundled_dir: *const c_char,
+    search_dirs: *const *const c_char,
+    search_dirs_len: size_t,
+    buf: *mut c_char,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if program.is_null() || buf.is_null() || (search_dirs.is_null() && search_dirs_len > 0) {
+        return -1;
+    }
+    
+    let program_str = unsafe {
+        match CStr::from_ptr(program).to_str() {
+            Ok(s) => s,
+            Err(_) => return -1,
+        }
+    };
+    
+    let mut resolver = Resolver::new();
+    
+    if !bundled_dir.is_null() {
+        match unsafe { CStr::from_ptr(bundled_dir).to_str() } {
+            Ok(dir) => resolver.bundled_dir(dir),
+            Err(_) => return -1,
+        };
+    }
+    
+    if search_dirs_len > 0 {
+        match unsafe { c_str_array(search_dirs, search_dirs_len) } {
+            Some(dirs) => resolver.search_paths(dirs),
+            None => return -1,
+        };
+    }
+    
+    let resolved = match resolver.resolve(program_str) {
+        Ok(path) => path,
+        Err(err) => {
+            let message = err.to_string();
+            warn!("process_which: {}", message);
+            if len > 0 {
+                let mut end = message.len().min(len - 1);
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                unsafe { copy_c_string(&message[..end], buf, len) };
+            }
+            return -1;
+        }
+    };
+    
+    unsafe { copy_c_string(&resolved.to_string_lossy(), buf, len) }
+}
+
+/// Create a builder for starting a process with additional options
+///
+/// The builder can spawn any number of processes and must be released with
+/// `process_builder_free`.
+///
+/// # Safety
+///
+/// `program` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_new(program: *const c_char) -> *mut ProcessBuilder {
+    // Safety check
+    if program.is_null() {
+        return ptr::null_mut();
+    }
+    
+    match unsafe { CStr::from_ptr(program).to_str() } {
+        Ok(program) => Box::into_raw(Box::new(ProcessBuilder::new(program))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Append an argument to the builder
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `arg` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_arg(builder: *mut ProcessBuilder, arg: *const c_char) -> c_int {
+    // Safety checks
+    if builder.is_null() || arg.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    match unsafe { CStr::from_ptr(arg).to_str() } {
+        Ok(arg) => {
+            builder.arg(arg);
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Set an environment variable of the process, or remove it if `value` is null
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `key` must be a valid null-terminated C string, `value` one or null.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_env(
+    builder: *mut ProcessBuilder,
+    key: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || key.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    let Ok(key) = (unsafe { CStr::from_ptr(key).to_str() }) else {
+        return -1;
+    };
+    if value.is_null() {
+        builder.env_remove(key);
+        return 0;
+    }
+    match unsafe { CStr::from_ptr(value).to_str() } {
+        Ok(value) => {
+            builder.env(key, value);
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Start the process with an empty environment, keeping variables set with `process_builder_env`
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_env_clear(builder: *mut ProcessBuilder) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.env_clear();
+}
+
+/// Run the process in the directory `dir`
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `dir` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_current_dir(builder: *mut ProcessBuilder, dir: *const c_char) -> c_int {
+    // Safety checks
+    if builder.is_null() || dir.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    match unsafe { CStr::from_ptr(dir).to_str() } {
+        Ok(dir) => {
+            builder.current_dir(dir);
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Enable or disable capturing stdout for `process_read_stdout`
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_capture_stdout(builder: *mut ProcessBuilder, capture: c_int) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.capture_stdout(capture != 0);
+}
+
+/// Read stdin from a file instead of a pipe
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_stdin_file(builder: *mut ProcessBuilder, path: *const c_char) -> c_int {
+    // Safety checks
+    if builder.is_null() || path.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    match unsafe { CStr::from_ptr(path).to_str() } {
+        Ok(path) => {
+            builder.stdin(StdinSource::File(PathBuf::from(path)));
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Feed stdin from a copy of the given buffer, followed by EOF
+///
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `data` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_stdin_bytes(
+    builder: *mut ProcessBuilder,
+    data: *const u8,
+    len: size_t,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || (data.is_null() && len > 0) {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    let data_slice = if len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(data, len) } };
+    builder.stdin(StdinSource::Bytes(data_slice.into()));
+    0
+}
+
+/// Connect stdin to the null device
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_stdin_null(builder: *mut ProcessBuilder) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.stdin(StdinSource::Null);
+}
+
+/// Build a file sink from FFI arguments, `max_bytes` 0 meaning no rotation
+///
+/// # Safety
+///
+/// `path` must be a valid null-terminated C string.
+unsafe fn file_sink(path: *const c_char, append: c_int, max_bytes: u64, backups: c_uint, tee: c_int) -> Option<OutputSink> {
+    let path = CStr::from_ptr(path).to_str().ok()?;
+    
+    let mut sink = FileSink::new(path).append(append != 0).tee(tee != 0);
+    if max_bytes > 0 {
+        sink = sink.rotate(max_bytes, backups as usize);
+    }
+    
+    Some(OutputSink::File(sink))
+}
+
+/// Write stdout to a file, optionally rotated once it reaches `max_bytes`
+///
+/// `max_bytes` 0 disables rotation; otherwise `backups` old files are kept
+/// as `<path>.1` to `<path>.N`. With `tee` non-zero the output also stays
+/// readable through `process_read_stdout`.
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_stdout_file(
+    builder: *mut ProcessBuilder,
+    path: *const c_char,
+    append: c_int,
+    max_bytes: u64,
+    backups: c_uint,
+    tee: c_int,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || path.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    match unsafe { file_sink(path, append, max_bytes, backups, tee) } {
+        Some(sink) => {
+            builder.stdout(sink);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Write stderr to a file, optionally rotated once it reaches `max_bytes`
+///
+/// Arguments behave as for `process_builder_stdout_file`; with `tee` non-zero
+/// the output also stays readable through `process_read_stderr`.
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_stderr_file(
+    builder: *mut ProcessBuilder,
+    path: *const c_char,
+    append: c_int,
+    max_bytes: u64,
+    backups: c_uint,
+    tee: c_int,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || path.is_null() {
+        return -1;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    match unsafe { file_sink(path, append, max_bytes, backups, tee) } {
+        Some(sink) => {
+            builder.stderr(sink);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Keep a timestamped log of the last `capacity` output chunks and stdin writes
+///
+/// 0 disables the log. See `process_write_event_log`.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_record_events(builder: *mut ProcessBuilder, capacity: size_t) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.record_events(capacity);
+}
+
+/// Run the process on a pseudo-terminal with the given window size (Unix only)
+///
+/// The terminal carries stdin, stdout and stderr: `process_write_stdin`
+/// writes to it and all output is read with `process_read_stdout`.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_pty(builder: *mut ProcessBuilder, cols: u16, rows: u16) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.pty(PtySize { cols, rows });
+}
+
+/// Limit a resource of the process with `setrlimit` (Unix only)
+///
+/// `resource` is one of the `LIMIT_*` constants; pass `UINT64_MAX` for no
+/// limit. Returns 0 on success and -1 for an unknown resource.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_limit(
+    builder: *mut ProcessBuilder,
+    resource: c_int,
+    soft: u64,
+    hard: u64,
+) -> c_int {
+    // Safety check
+    if builder.is_null() {
+        return -1;
+    }
+    
+    let Some(resource) = resource_from_c(resource) else {
+        return -1;
+    };
+    
+    let builder = unsafe { &mut *builder };
+    builder.limit(resource, soft, hard);
+    0
+}
+
+/// Set the scheduling priority the process starts with
+///
+/// Takes the same arguments as `process_set_priority`. Niceness is
+/// supported on Unix, everything else on Linux only; other platforms fail
+/// at spawn. Returns 0 on success and -1 for invalid values.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `cpus` must be null or a valid pointer to an array of `cpus_len` CPU indices.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_priority(
+    builder: *mut ProcessBuilder,
+    nice: c_int,
+    policy: c_int,
+    io_class: c_int,
+    io_level: c_int,
+    cpus: *const c_uint,
+    cpus_len: size_t,
+) -> c_int {
+    // Safety check
+    if builder.is_null() {
+        return -1;
+    }
+    
+    let Some(priority) = (unsafe { priority_from_c(nice, policy, io_class, io_level, cpus, cpus_len) }) else {
+        return -1;
+    };
+    
+    let builder = unsafe { &mut *builder };
+    if let Some(nice) = priority.nice {
+        builder.nice(nice);
+    }
+    if let Some(policy) = priority.policy {
+        builder.sched_policy(policy);
+    }
+    if let Some(io) = priority.io {
+        builder.io_priority(io);
+    }
+    if let Some(cpus) = &priority.cpus {
+        builder.cpu_affinity(cpus);
+    }
+    0
+}
+
+/// Run the process in its own cgroup v2 under `parent` (Linux only)
+///
+/// `memory_max` limits memory in bytes; `cpu_quota_us` of CPU time is
+/// allowed per `cpu_period_us`. Pass 0 for either to leave it unlimited.
+/// The cgroup is removed by `process_close`. Returns 0 on success and -1
+/// on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `parent` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_cgroup(
+    builder: *mut ProcessBuilder,
+    parent: *const c_char,
+    memory_max: u64,
+    cpu_quota_us: u64,
+    cpu_period_us: u64,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || parent.is_null() {
+        return -1;
+    }
+    
+    let parent = match unsafe { CStr::from_ptr(parent).to_str() } {
+        Ok(parent) => parent,
+        Err(_) => return -1,
+    };
+    
+    let mut config = CgroupConfig::new(parent);
+    if memory_max > 0 {
+        config = config.memory_max(memory_max);
+    }
+    if cpu_quota_us > 0 && cpu_period_us > 0 {
+        config = config.cpu_max(
+            std::time::Duration::from_micros(cpu_quota_us),
+            std::time::Duration::from_micros(cpu_period_us),
+        );
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.cgroup(config);
+    0
+}
+
+/// Run the process in new user, mount, PID and network namespaces (Linux only)
+///
+/// Only the host paths in `read_only` and `writable`, plus the default
+/// read-only system directories unless `defaults` is 0, are visible to the
+/// process; a non-zero `share_network` keeps the host's network. Spawning
+/// fails where the namespaces can not be created. Returns 0 on success and
+/// -1 on failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `read_only` and `writable` must be arrays of `read_only_len` and
+/// `writable_len` valid null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_sandbox(
+    builder: *mut ProcessBuilder,
+    defaults: c_int,
+    read_only: *const *const c_char,
+    read_only_len: size_t,
+    writable: *const *const c_char,
+    writable_len: size_t,
+    share_network: c_int,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || (read_only.is_null() && read_only_len > 0) || (writable.is_null() && writable_len > 0) {
+        return -1;
+    }
+    
+    let (Some(read_only), Some(writable)) =
+        (unsafe { c_str_array(read_only, read_only_len) }, unsafe { c_str_array(writable, writable_len) })
+    else {
+        return -1;
+    };
+    
+    let mut config = if defaults != 0 { SandboxConfig::new() } else { SandboxConfig::empty() };
+    for path in read_only {
+        config = config.read_only(path);
+    }
+    for path in writable {
+        config = config.writable(path);
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.sandbox(config.share_network(share_network != 0));
+    0
+}
+
+/// Start the process in a new process group it leads (Unix only)
+///
+/// The metrics sampler then also covers the group, and `process_close`
+/// terminates all of it.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_process_group(builder: *mut ProcessBuilder, enable: c_int) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.process_group(enable != 0);
+}
+
+/// Watch the process for stalls: no output and no consumed stdin for `timeout_ms`
+///
+/// `action` is one of the `WATCHDOG_*` constants; `signal` is sent with
+/// `WATCHDOG_SIGNAL` (Unix only). `callback`, if not null, is called with
+/// `user_data` on each stall from a background thread. Returns 0 on success
+/// and -1 for an unknown action.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `user_data` must remain valid, and usable from another thread, while
+/// processes spawned from the builder run.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_watchdog(
+    builder: *mut ProcessBuilder,
+    timeout_ms: u64,
+    action: c_int,
+    signal: c_int,
+    callback: Option<StallCallbackFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    // Safety check
+    if builder.is_null() {
+        return -1;
+    }
+    
+    let action = match action {
+        WATCHDOG_NOTIFY => WatchdogAction::Notify,
+        WATCHDOG_SIGNAL => WatchdogAction::Signal(signal),
+        WATCHDOG_KILL => WatchdogAction::Kill,
+        _ => return -1,
+    };
+    
+    let mut watchdog = Watchdog::new(std::time::Duration::from_millis(timeout_ms)).action(action);
+    if let Some(callback) = callback {
+        let user_data = UserData(user_data);
+        watchdog = watchdog.on_stall(move |stall| {
+            // Captures the wrapper rather than just the raw pointer
+            let user_data = &user_data;
+            callback(user_data.0, stall.idle.as_millis() as u64)
+        });
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.watchdog(watchdog);
+    0
+}
+
+/// Kill the process once it has run for `timeout_ms`, reported as `EXIT_REASON_TIMED_OUT`
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_timeout(builder: *mut ProcessBuilder, timeout_ms: u64) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.timeout(std::time::Duration::from_millis(timeout_ms));
+}
+
+/// Let `process_close` ask a running process to finish before killing it
+///
+/// `len` bytes of `data` are written to stdin before it is closed, then
+/// `signal` is sent unless it is 0 (Unix only), and the process is killed
+/// if it has not exited after `grace_ms`. Returns 0 on success and -1 on
+/// failure.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `data` must be a valid pointer to at least `len` bytes, or null with `len` 0.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_shutdown(
+    builder: *mut ProcessBuilder,
+    data: *const u8,
+    len: size_t,
+    signal: c_int,
+    grace_ms: u64,
+) -> c_int {
+    // Safety checks
+    if builder.is_null() || (data.is_null() && len > 0) {
+        return -1;
+    }
+    
+    let mut shutdown = Shutdown::new(std::time::Duration::from_millis(grace_ms));
+    if len > 0 {
+        shutdown = shutdown.stdin(unsafe { std::slice::from_raw_parts(data, len) });
+    }
+    if signal != 0 {
+        shutdown = shutdown.signal(signal);
+    }
+    
+    let builder = unsafe { &mut *builder };
+    builder.shutdown(shutdown);
+    0
+}
+
+/// Start a process from the builder's configuration
+///
+/// The returned handle is used with the other `process_*` functions.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_spawn(builder: *mut ProcessBuilder) -> *mut Process {
+    // Safety check
+    if builder.is_null() {
+        return ptr::null_mut();
+    }
+    
+    let builder = unsafe { &*builder };
+    match builder.spawn() {
+        Ok(process) => Box::into_raw(Box::new(process)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a builder created with `process_builder_new`
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_builder_free(builder: *mut ProcessBuilder) {
+    // Safety check
+    if builder.is_null() {
+        return;
+    }
+    
+    drop(unsafe { Box::from_raw(builder) });
+}
+
+/// Write the process's recorded output events to a file as JSON Lines
+///
+/// Each line holds `stream`, `time` (seconds since spawn), `len` and `data`.
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_write_event_log(proc: *mut Process, path: *const c_char) -> c_int {
+    // Safety checks
+    if proc.is_null() || path.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &*proc };
+    let path_str = unsafe {
+        match CStr::from_ptr(path).to_str() {
+            Ok(s) => s,
+            Err(_) => return -1,
+        }
+    };
+    
+    let result = std::fs::File::create(path_str)
+        .map_err(Into::into)
+        .and_then(|file| process.write_events_jsonl(std::io::BufWriter::new(file)));
+    
+    match result {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Wait until a pattern appears in the process's captured stdout or stderr
+///
+/// `pattern` is matched literally, or as a regular expression if `is_regex`
+/// is non-zero. Writes the text of capture group `group` (0 for the whole
+/// match) into `buf` as a null-terminated string if it fits, and returns its
+/// length, so a result `>= len` means the buffer was too small.
+/// Output up to the end of the match is consumed.
+/// Returns -1 on timeout and -2 if the output ended without a match or on error,
+/// including an invalid pattern or a `group` the pattern does not have, which
+/// are rejected before any output is consumed.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `pattern` must be a valid null-terminated C string.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_wait_for_output(
+    proc: *mut Process,
+    pattern: *const c_char,
+    is_regex: c_int,
+    timeout_ms: u64,
+    group: c_uint,
+    buf: *mut c_char,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if proc.is_null() || pattern.is_null() || buf.is_null() {
+        return -2;
+    }
+    
+    let process = unsafe { &*proc };
+    let pattern_str = unsafe {
+        match CStr::from_ptr(pattern).to_str() {
+            Ok(s) => s,
+            Err(_) => return -2,
+        }
+    };
+    
+    let pattern = if is_regex != 0 {
+        match Pattern::regex(pattern_str) {
+            Ok(pattern) => pattern,
+            Err(_) => return -2,
+        }
+    } else {
+        Pattern::literal(pattern_str)
+    };
+    if group as usize > pattern.groups() {
+        return -2;
+    }
+    
+    match process.wait_for_output(&pattern, std::time::Duration::from_millis(timeout_ms)) {
+        Ok(found) => {
+            let text = match group {
+                0 => Some(found.text),
+                n => found.captures.into_iter().nth(n as usize - 1).flatten(),
+            };
+            unsafe { copy_c_string(&text.unwrap_or_default(), buf, len) }
+        }
+        Err(ProcessError::Timeout) => -1,
+        Err(_) => -2,
+    }
+}
+
+/// Describe how the process ended
+///
+/// Returns 1 and fills `info` once the process has finished, 0 while it is
+/// still running, and -1 on error.
+///
+/// # Safety
+///
+/// `proc` must be a valid pointer returned by `process_start`.
+/// `info` must be a valid pointer to a `ProcessExitInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn process_exit_info(proc: *mut Process, info: *mut ProcessExitInfo) -> c_int {
+    // Safety checks
+    if proc.is_null() || info.is_null() {
+        return -1;
+    }
+    
+    let process = unsafe { &mut *proc };
+    let Some(exit) = process.exit_info() else {
+        return 0;
+    };
+    
+    unsafe {
+        *info = ProcessExitInfo::from(&exit);
+    }
+    1
+}
+
+/// Spawn a process from the builder's configuration and restart it according to `policy`
+///
+/// `policy` is one of the `RESTART_*` constants. Restarts are delayed by
+/// `initial_backoff_ms`, doubling for consecutive restarts up to
+/// `max_backoff_ms`. With `max_restarts` > 0, supervision gives up once the
+/// process would be restarted more often than that within `window_ms`.
+/// `callback`, if not null, is called with `user_data` for each event from a
+/// background thread. Returns null if the policy is unknown or the first
+/// instance cannot be spawned.
+///
+/// # Safety
+///
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+/// `user_data` must remain valid, and usable from another thread, until
+/// `process_supervisor_stop` returns.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_start(
+    builder: *mut ProcessBuilder,
+    policy: c_int,
+    initial_backoff_ms: u64,
+    max_backoff_ms: u64,
+    max_restarts: c_uint,
+    window_ms: u64,
+    callback: Option<SupervisorEventFn>,
+    user_data: *mut c_void,
+) -> *mut Supervisor {
+    // Safety check
+    if builder.is_null() {
+        return ptr::null_mut();
+    }
+    
+    let policy = match policy {
+        RESTART_NEVER => RestartPolicy::Never,
+        RESTART_ON_FAILURE => RestartPolicy::OnFailure,
+        RESTART_ALWAYS => RestartPolicy::Always,
+        _ => return ptr::null_mut(),
+    };
+    
+    let builder = unsafe { &*builder };
+    let mut config = SupervisorConfig::new(builder.clone()).policy(policy).backoff(
+        std::time::Duration::from_millis(initial_backoff_ms),
+        std::time::Duration::from_millis(max_backoff_ms),
+    );
+    if max_restarts > 0 {
+        config = config.max_restarts(max_restarts as usize, std::time::Duration::from_millis(window_ms));
+    }
+    if let Some(callback) = callback {
+        let user_data = UserData(user_data);
+        config = config.on_event(move |event| {
+            // Captures the wrapper rather than just the raw pointer
+            let user_data = &user_data;
+            let event = ProcessSupervisorEvent::from(event);
+            callback(user_data.0, &event)
+        });
+    }
+    
+    match Supervisor::start(config) {
+        Ok(supervisor) => Box::into_raw(Box::new(supervisor)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Write data to the stdin of the supervised process's current instance
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+/// `data` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_write_stdin(
+    supervisor: *mut Supervisor,
+    data: *const u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if supervisor.is_null() || data.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    let data_slice = unsafe { std::slice::from_raw_parts(data, len) };
+    
+    match supervisor.write_stdin(data_slice) {
+        Ok(bytes_written) => bytes_written as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Read data from the stdout of the supervised process's current instance
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_read_stdout(
+    supervisor: *mut Supervisor,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if supervisor.is_null() || buf.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    let buf_slice = unsafe { std::slice::from_raw_parts_mut(buf, len) };
+    
+    match supervisor.read_stdout(buf_slice) {
+        Ok(bytes_read) => bytes_read as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Read data from the stderr of the supervised process's current instance
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_read_stderr(
+    supervisor: *mut Supervisor,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if supervisor.is_null() || buf.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    let buf_slice = unsafe { std::slice::from_raw_parts_mut(buf, len) };
+    
+    match supervisor.read_stderr(buf_slice) {
+        Ok(bytes_read) => bytes_read as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Check if the supervised process's current instance is running
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_is_running(supervisor: *mut Supervisor) -> c_int {
+    // Safety check
+    if supervisor.is_null() {
+        return 0;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    supervisor.is_running() as c_int
+}
+
+/// Check if supervision ended, i.e. the process will not be restarted anymore
+///
+/// Returns 1 if it ended, 0 if not and -1 on error.
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_is_finished(supervisor: *mut Supervisor) -> c_int {
+    // Safety check
+    if supervisor.is_null() {
+        return -1;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    supervisor.is_finished() as c_int
+}
+
+/// Get the number of restarts made so far, including ones that failed to spawn
+///
+/// Returns -1 on error.
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_restarts(supervisor: *mut Supervisor) -> i64 {
+    // Safety check
+    if supervisor.is_null() {
+        return -1;
+    }
+    
+    let supervisor = unsafe { &*supervisor };
+    supervisor.restarts() as i64
+}
+
+/// Stop supervising, terminate the current instance and free the handle
+///
+/// # Safety
+///
+/// `supervisor` must be a valid pointer returned by `process_supervisor_start`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_supervisor_stop(supervisor: *mut Supervisor) {
+    // Safety check
+    if supervisor.is_null() {
+        return;
+    }
+    
+    let supervisor = unsafe { Box::from_raw(supervisor) };
+    let _ = supervisor.stop();
+}
+
+/// Create a pool running at most `max_concurrent` processes at a time
+///
+/// `callback`, if not null, is called with `user_data` from a worker thread
+/// whenever a job changes its status, including jobs cancelled by
+/// `process_pool_cancel` or `process_pool_free`.
+///
+/// # Safety
+///
+/// `user_data` must remain valid, and usable from another thread, until
+/// `process_pool_free` returns.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_new(
+    max_concurrent: size_t,
+    callback: Option<PoolProgressFn>,
+    user_data: *mut c_void,
+) -> *mut ProcessPool {
+    let pool = match callback {
+        Some(callback) => {
+            let user_data = UserData(user_data);
+            ProcessPool::with_progress(max_concurrent, move |job, status, progress| {
+                // Captures the wrapper rather than just the raw pointer
+                let user_data = &user_data;
+                let progress = ProcessPoolProgress::from(*progress);
+                callback(user_data.0, job, job_status_to_c(status), &progress)
+            })
+        }
+        None => ProcessPool::new(max_concurrent),
+    };
+    Box::into_raw(Box::new(pool))
+}
+
+/// Queue a process spawned from the builder's configuration
+///
+/// Returns the job ID, or -1 on error. The builder can be freed or reused afterwards.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_submit(pool: *mut ProcessPool, builder: *mut ProcessBuilder) -> i64 {
+    // Safety checks
+    if pool.is_null() || builder.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    let builder = unsafe { &*builder };
+    match pool.submit(builder) {
+        Ok(job) => job as i64,
+        Err(_) => -1,
+    }
+}
+
+/// Cancel a job, killing its process if it is running
+///
+/// Returns 0 on success and -1 if the job is unknown or already done.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_cancel(pool: *mut ProcessPool, job: u64) -> c_int {
+    // Safety check
+    if pool.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    if pool.cancel(job) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Get the `POOL_JOB_*` status of a job, or -1 if it is unknown
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_job_status(pool: *mut ProcessPool, job: u64) -> c_int {
+    // Safety check
+    if pool.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    pool.status(job).map(job_status_to_c).unwrap_or(-1)
+}
+
+/// Describe the outcome of a job
+///
+/// Returns 1 and fills `result` once the job is done, 0 while it is queued
+/// or running, and -1 if it is unknown.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// `result` must be a valid pointer to a `ProcessJobResult`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_job_result(
+    pool: *mut ProcessPool,
+    job: u64,
+    result: *mut ProcessJobResult,
+) -> c_int {
+    // Safety checks
+    if pool.is_null() || result.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    if pool.status(job).is_none() {
+        return -1;
+    }
+    let Some(job_result) = pool.result(job) else {
+        return 0;
+    };
+    
+    unsafe {
+        *result = ProcessJobResult::from(&job_result);
+    }
+    1
+}
+
+/// Copy `output` into `buf` if it fits, returning its length
+///
+/// # Safety
+///
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+unsafe fn copy_if_fits(output: &[u8], buf: *mut u8, len: size_t) -> isize {
+    if output.len() <= len {
+        ptr::copy_nonoverlapping(output.as_ptr(), buf, output.len());
+    }
+    output.len() as isize
+}
+
+/// Copy the captured stdout of a finished job into `buf`
+///
+/// Returns the length of the output, which is only copied if it fits in
+/// `len` bytes, or -1 if the job is unknown or not done.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_job_stdout(
+    pool: *mut ProcessPool,
+    job: u64,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if pool.is_null() || (buf.is_null() && len > 0) {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    match pool.result(job) {
+        Some(result) => unsafe { copy_if_fits(&result.stdout, buf, len) },
+        None => -1,
+    }
+}
+
+/// Copy the captured stderr of a finished job into `buf`
+///
+/// Returns the length of the output, which is only copied if it fits in
+/// `len` bytes, or -1 if the job is unknown or not done.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_job_stderr(
+    pool: *mut ProcessPool,
+    job: u64,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if pool.is_null() || (buf.is_null() && len > 0) {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    match pool.result(job) {
+        Some(result) => unsafe { copy_if_fits(&result.stderr, buf, len) },
+        None => -1,
+    }
+}
+
+/// Forget a finished job and free its captured output
+///
+/// Returns 0 on success and -1 if the job is unknown or not done.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_job_remove(pool: *mut ProcessPool, job: u64) -> c_int {
+    // Safety check
+    if pool.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    match pool.remove(job) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Count the pool's jobs in each state
+///
+/// Returns 0 on success and -1 on error.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// `progress` must be a valid pointer to a `ProcessPoolProgress`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_progress(pool: *mut ProcessPool, progress: *mut ProcessPoolProgress) -> c_int {
+    // Safety checks
+    if pool.is_null() || progress.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    unsafe {
+        *progress = ProcessPoolProgress::from(pool.progress());
+    }
+    0
+}
+
+/// Wait up to `timeout_ms` until no job is queued or running
+///
+/// Returns 0 once all jobs are done and -1 on timeout or error.
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_wait_all(pool: *mut ProcessPool, timeout_ms: u64) -> c_int {
+    // Safety check
+    if pool.is_null() {
+        return -1;
+    }
+    
+    let pool = unsafe { &*pool };
+    if pool.wait_all(Some(std::time::Duration::from_millis(timeout_ms))) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Cancel all queued and running jobs and free the pool
+///
+/// # Safety
+///
+/// `pool` must be a valid pointer returned by `process_pool_new`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_pool_free(pool: *mut ProcessPool) {
+    // Safety check
+    if pool.is_null() {
+        return;
+    }
+    
+    let pool = unsafe { Box::from_raw(pool) };
+    pool.close();
+}
+
+/// Create an empty pipeline; stages are added with `process_pipeline_add`
+#[no_mangle]
+pub extern "C" fn process_pipeline_new() -> *mut Pipeline {
+    Box::into_raw(Box::new(Pipeline::new()))
+}
+
+/// Append a stage configured by the builder, reading the previous stage's stdout
+///
+/// The first stage's stdin and the last stage's stdout are used as
+/// configured; the other connected streams are replaced by pipes. The
+/// builder can be freed or reused afterwards. Returns 0 on success and -1 on error.
+///
+/// # Safety
+///
+/// `pipeline` must be a valid pointer returned by `process_pipeline_new`.
+/// `builder` must be a valid pointer returned by `process_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_add(pipeline: *mut Pipeline, builder: *mut ProcessBuilder) -> c_int {
+    // Safety checks
+    if pipeline.is_null() || builder.is_null() {
+        return -1;
+    }
+    
+    let pipeline = unsafe { &mut *pipeline };
+    let builder = unsafe { &*builder };
+    pipeline.stage(builder.clone());
+    0
+}
+
+/// Spawn all stages of the pipeline, connected by pipes
+///
+/// Returns null if the pipeline is empty or a stage cannot be spawned.
+///
+/// # Safety
+///
+/// `pipeline` must be a valid pointer returned by `process_pipeline_new`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_spawn(pipeline: *mut Pipeline) -> *mut RunningPipeline {
+    // Safety check
+    if pipeline.is_null() {
+        return ptr::null_mut();
+    }
+    
+    let pipeline = unsafe { &*pipeline };
+    match pipeline.spawn() {
+        Ok(running) => Box::into_raw(Box::new(running)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a pipeline created with `process_pipeline_new`
+///
+/// Pipelines spawned from it keep running.
+///
+/// # Safety
+///
+/// `pipeline` must be a valid pointer returned by `process_pipeline_new`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_free(pipeline: *mut Pipeline) {
+    // Safety check
+    if pipeline.is_null() {
+        return;
+    }
+    
+    drop(unsafe { Box::from_raw(pipeline) });
+}
+
+/// Write data to the stdin of the pipeline's first stage
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+/// `data` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_write_stdin(
+    running: *mut RunningPipeline,
+    data: *const u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if running.is_null() || data.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let running = unsafe { &mut *running };
+    let data_slice = unsafe { std::slice::from_raw_parts(data, len) };
+    
+    match running.write_stdin(data_slice) {
+        Ok(bytes_written) => bytes_written as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Close the stdin of the pipeline's first stage, signalling EOF
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_close_stdin(running: *mut RunningPipeline) {
+    // Safety check
+    if running.is_null() {
+        return;
+    }
+    
+    let running = unsafe { &mut *running };
+    running.close_stdin();
+}
+
+/// Read data from the stdout of the pipeline's last stage
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_read_stdout(
+    running: *mut RunningPipeline,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if running.is_null() || buf.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let running = unsafe { &mut *running };
+    let buf_slice = unsafe { std::slice::from_raw_parts_mut(buf, len) };
+    
+    match running.read_stdout(buf_slice) {
+        Ok(bytes_read) => bytes_read as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Read data from the stderr of stage `stage`, counted from 0
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+/// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_read_stderr(
+    running: *mut RunningPipeline,
+    stage: size_t,
+    buf: *mut u8,
+    len: size_t,
+) -> isize {
+    // Safety checks
+    if running.is_null() || buf.is_null() || len == 0 {
+        return -1;
+    }
+    
+    let running = unsafe { &mut *running };
+    let buf_slice = unsafe { std::slice::from_raw_parts_mut(buf, len) };
+    
+    match running.read_stderr(stage, buf_slice) {
+        Ok(bytes_read) => bytes_read as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Check if any stage of the pipeline is still running
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_is_running(running: *mut RunningPipeline) -> c_int {
+    // Safety check
+    if running.is_null() {
+        return 0;
+    }
+    
+    let running = unsafe { &mut *running };
+    running.is_running() as c_int
+}
+
+/// Wait for all stages and return the combined exit code
+///
+/// Like a shell with `pipefail`, this is the exit code of the last stage
+/// that failed, or 0 if all succeeded; -1 stands for a stage terminated by
+/// a signal or an error. `codes`, if not null, receives the exit code of each
+/// stage and must have room for `codes_len` of them.
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+/// `codes` must be null or a valid pointer to an array of `codes_len` integers.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_wait(
+    running: *mut RunningPipeline,
+    codes: *mut c_int,
+    codes_len: size_t,
+) -> c_int {
+    // Safety check
+    if running.is_null() {
+        return -1;
+    }
+    
+    let running = unsafe { &mut *running };
+    let stage_codes = match running.wait_all() {
+        Ok(stage_codes) => stage_codes,
+        Err(_) => return -1,
+    };
+    
+    if !codes.is_null() {
+        for (index, code) in stage_codes.iter().take(codes_len).enumerate() {
+            unsafe { *codes.add(index) = *code };
+        }
+    }
+    pipefail(&stage_codes)
+}
+
+/// Terminate all stages of the pipeline, clean up and free the handle
+///
+/// # Safety
+///
+/// `running` must be a valid pointer returned by `process_pipeline_spawn`.
+/// The pointer must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn process_pipeline_close(running: *mut RunningPipeline) {
+    // Safety check
+    if running.is_null() {
+        return;
+    }
+    
+    let mut running = unsafe { Box::from_raw(running) };
+    let _ = running.close();
+}
+
+/// Route the library's log records up to `level` to `callback`
+///
+/// `level` is one of the `LOG_LEVEL_*` constants; `LOG_LEVEL_OFF` or a null
+/// `callback` stops logging. The callback is called on whichever thread
+/// logs, and its strings are only valid during the call. Command lines in
+/// records are redacted, see `process_log_redact_flag`. Returns 0 on
+/// success and -1 on error, e.g. if the application installed another
+/// logger for the `log` crate.
+///
+/// # Safety
+///
+/// `callback` must be safe to call from any thread with `user_data` until it is replaced.
+#[no_mangle]
+pub unsafe extern "C" fn process_set_log_callback(
+    level: c_int,
+    callback: Option<LogFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    let level = match level {
+        LOG_LEVEL_OFF => LevelFilter::Off,
+        LOG_LEVEL_ERROR => LevelFilter::Error,
+        LOG_LEVEL_WARN => LevelFilter::Warn,
+        LOG_LEVEL_INFO => LevelFilter::Info,
+        LOG_LEVEL_DEBUG => LevelFilter::Debug,
+        LOG_LEVEL_TRACE => LevelFilter::Trace,
+        _ => return -1,
+    };
+    
+    let callback = match callback {
+        Some(callback) if level != LevelFilter::Off => {
+            let user_data = UserData(user_data);
+            let callback: logging::LogCallback = Arc::new(move |record: &log::Record| {
+                // Captures the wrapper rather than just the raw pointer
+                let user_data = &user_data;
+                let level = match record.level() {
+                    Level::Error => LOG_LEVEL_ERROR,
+                    Level::Warn => LOG_LEVEL_WARN,
+                    Level::Info => LOG_LEVEL_INFO,
+                    Level::Debug => LOG_LEVEL_DEBUG,
+                    Level::Trace => LOG_LEVEL_TRACE,
+                };
+                let target = c_string_lossy(record.target());
+                let message = c_string_lossy(&record.args().to_string());
+                callback(user_data.0, level, target.as_ptr(), message.as_ptr());
+            });
+            Some(callback)
+        }
+        _ => None,
+    };
+    
+    match logging::set_log_callback(level, callback) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Convert text to a C string, dropping interior NUL bytes
+fn c_string_lossy(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap()
+}
+
+/// Also redact the argument following `flag` in logged command lines
+///
+/// Applies to the default rules or those set so far, which cover URL
+/// credentials, secret-looking query parameters and ffmpeg's `-headers`,
+/// `-password`, `-passphrase` and `-key`. Returns 0 on success and -1 on error.
+///
+/// # Safety
+///
+/// `flag` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_log_redact_flag(flag: *const c_char) -> c_int {
+    // Safety check
+    if flag.is_null() {
+        return -1;
+    }
+    
+    match unsafe { CStr::from_ptr(flag).to_str() } {
+        Ok(flag) => {
+            logging::set_redactor(logging::redactor().flag(flag));
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Also redact text matching the regular expression `pattern` in logged command lines
+///
+/// Only the capture groups of the pattern are redacted if it has any, the
+/// whole match otherwise. Returns 0 on success and -1 on error, e.g. for an
+/// invalid pattern.
+///
+/// # Safety
+///
+/// `pattern` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn process_log_redact_pattern(pattern: *const c_char) -> c_int {
+    // Safety check
+    if pattern.is_null() {
+        return -1;
+    }
+    
+    let pattern = match unsafe { CStr::from_ptr(pattern).to_str() } {
+        Ok(pattern) => pattern,
+        Err(_) => return -1,
+    };
+    match logging::redactor().pattern(pattern) {
+        Ok(redactor) => {
+            logging::set_redactor(redactor);
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Stop redacting logged command lines, until rules are added again
+#[no_mangle]
+pub extern "C" fn process_log_redact_none() {
+    logging::set_redactor(Redactor::none());
+}
+
+/// Scripted fake processes for tests of the host, see `FakeBackend`
+///
+/// Only built with the `fake-backend` feature, so release builds of the
+/// library can not be switched to fake processes.
+#[cfg(feature = "fake-backend")]
+pub use self::fake_backend::*;
+
+#[cfg(feature = "fake-backend")]
+mod fake_backend {
+    use super::copy_if_fits;
+    use crate::backend::{self, FakeBackend, FakeScript, FakeStep};
+    use libc::{c_char, c_int, size_t};
+    use std::ffi::CStr;
+    use std::sync::{Arc, Mutex};
+    
+    /// `kind` value of `process_fake_backend_step` printing to stdout
+    pub const FAKE_STEP_STDOUT: c_int = 0;
+
+    /// `kind` value of `process_fake_backend_step` printing to stderr
+    pub const FAKE_STEP_STDERR: c_int = 1;
+
+    /// `kind` value of `process_fake_backend_step` sleeping for `delay_ms`
+    pub const FAKE_STEP_SLEEP: c_int = 2;
+
+    /// Fake backend installed by `process_fake_backend_init`
+    static FAKE_BACKEND: Mutex<Option<Arc<FakeBackend>>> = Mutex::new(None);
+
+    /// Install a fake backend as default for all processes, for tests of the host (test only)
+    ///
+    /// Programs given a script with `process_fake_backend_script` are simulated
+    /// from then on; starting any other program fails. Replaces a fake
+    /// backend installed before, forgetting its scripts.
+    #[no_mangle]
+    pub extern "C" fn process_fake_backend_init() {
+        let fake = Arc::new(FakeBackend::new());
+        backend::set_default(Some(fake.clone()));
+        *FAKE_BACKEND.lock().unwrap() = Some(fake);
+    }
+
+    /// Remove the fake backend, so all processes run on the operating system again
+    #[no_mangle]
+    pub extern "C" fn process_fake_backend_reset() {
+        backend::set_default(None);
+        *FAKE_BACKEND.lock().unwrap() = None;
+    }
+
+    /// Simulate `program` with an empty script exiting with `exit_code`, extended by `process_fake_backend_step`
+    ///
+    /// With `until_stdin_closed` non-zero the fake process keeps running after
+    /// its last step until its stdin is closed. Returns 0 on success and -1
+    /// on error, including when no fake backend is installed.
+    ///
+    /// # Safety
+    ///
+    /// `program` must be a valid null-terminated C string.
+    #[no_mangle]
+    pub unsafe extern "C" fn process_fake_backend_script(
+        program: *const c_char,
+        exit_code: c_int,
+        until_stdin_closed: c_int,
+    ) -> c_int {
+        // Safety check
+        if program.is_null() {
+            return -1;
+        }
+        
+        let Some(fake) = FAKE_BACKEND.lock().unwrap().clone() else {
+            return -1;
+        };
+        let program = match unsafe { CStr::from_ptr(program).to_str() } {
+            Ok(s) => s,
+            Err(_) => return -1,
+        };
+        
+        let mut script = FakeScript::new().exit_code(exit_code);
+        if until_stdin_closed != 0 {
+            script = script.until_stdin_closed();
+        }
+        fake.set_script(program, script);
+        0
+    }
+
+    /// Append a step to the script of `program`
+    ///
+    /// `kind` is one of `FAKE_STEP_STDOUT` and `FAKE_STEP_STDERR`, printing
+    /// `len` bytes of `data`, or `FAKE_STEP_SLEEP`, waiting `delay_ms`.
+    /// Returns 0 on success and -1 on error, e.g. if `program` has no script.
+    ///
+    /// # Safety
+    ///
+    /// `program` must be a valid null-terminated C string.
+    /// `data` must be a valid pointer to at least `len` bytes, or null with `len` 0.
+    #[no_mangle]
+    pub unsafe extern "C" fn process_fake_backend_step(
+        program: *const c_char,
+        kind: c_int,
+        data: *const u8,
+        len: size_t,
+        delay_ms: u64,
+    ) -> c_int {
+        // Safety checks
+        if program.is_null() || (data.is_null() && len > 0) {
+            return -1;
+        }
+        
+        let Some(fake) = FAKE_BACKEND.lock().unwrap().clone() else {
+            return -1;
+        };
+        let program = match unsafe { CStr::from_ptr(program).to_str() } {
+            Ok(s) => s,
+            Err(_) => return -1,
+        };
+        let Some(mut script) = fake.script(program) else {
+            return -1;
+        };
+        
+        let bytes = if len > 0 { unsafe { std::slice::from_raw_parts(data, len) }.to_vec() } else { Vec::new() };
+        script.steps.push(match kind {
+            FAKE_STEP_STDOUT => FakeStep::Stdout(bytes),
+            FAKE_STEP_STDERR => FakeStep::Stderr(bytes),
+            FAKE_STEP_SLEEP => FakeStep::Sleep(std::time::Duration::from_millis(delay_ms)),
+            _ => return -1,
+        });
+        fake.set_script(program, script);
+        0
+    }
+
+    /// Copy what was written to the stdin of the latest fake `program` into `buf`
+    ///
+    /// Returns the length of the input, which is only copied if it fits in
+    /// `len` bytes, or -1 if no such fake process was spawned.
+    ///
+    /// # Safety
+    ///
+    /// `program` must be a valid null-terminated C string.
+    /// `buf` must be a valid pointer to a buffer of at least `len` bytes.
+    #[no_mangle]
+    pub unsafe extern "C" fn process_fake_backend_stdin(program: *const c_char, buf: *mut u8, len: size_t) -> isize {
+        // Safety checks
+        if program.is_null() || buf.is_null() {
+            return -1;
+        }
+        
+        let Some(fake) = FAKE_BACKEND.lock().unwrap().clone() else {
+            return -1;
+        };
+        let program = match unsafe { CStr::from_ptr(program).to_str() } {
+            Ok(s) => s,
+            Err(_) => return -1,
+        };
+        
+        let invocations = fake.invocations();
+        match invocations.iter().rev().find(|invocation| invocation.program == program) {
+            Some(invocation) => unsafe { copy_if_fits(&invocation.stdin, buf, len) },
+            None => -1,
+        }
+    }
+}
@@ -1,5 +1,28 @@
+pub mod backend;
+pub mod cgroup;
+pub mod exit;
+pub mod expect;
 mod ffi;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod output;
+pub mod pipeline;
+pub mod pool;
+pub mod priority;
+pub mod probe;
 pub mod process;
+pub mod profile;
+pub mod pty;
+#[cfg(target_os = "linux")]
+mod reactor;
+pub mod reaper;
+pub mod replay;
+pub mod sandbox;
+pub mod supervisor;
+pub mod usage;
+pub mod watchdog;
+pub mod which;
 
 pub use ffi::*;
 
@@ -0,0 +1,99 @@
+use std::process::Command;
+
+/// A resource whose usage can be limited for a spawned process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// Size of the virtual address space in bytes (`RLIMIT_AS`)
+    AddressSpace,
+
+    /// CPU time in seconds (`RLIMIT_CPU`)
+    CpuTime,
+
+    /// Number of open file descriptors (`RLIMIT_NOFILE`)
+    OpenFiles,
+
+    /// Size of core dumps in bytes (`RLIMIT_CORE`)
+    CoreSize,
+
+    /// Number of processes and threads of the user (`RLIMIT_NPROC`)
+    Processes,
+
+    /// Size of files the process may write in bytes (`RLIMIT_FSIZE`)
+    FileSize,
+}
+
+/// Value meaning "no limit"
+pub const UNLIMITED: u64 = u64::MAX;
+
+/// Soft and hard value of a resource limit
+///
+/// The soft limit is enforced; the hard limit caps how far the process may
+/// raise it. Exceeding the soft CPU time limit delivers `SIGXCPU`, the hard
+/// one `SIGKILL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    /// The enforced limit
+    pub soft: u64,
+
+    /// The ceiling for the soft limit
+    pub hard: u64,
+}
+
+#[cfg(unix)]
+impl Resource {
+    fn rlimit_resource(self) -> libc::c_int {
+        // The resource type differs between platforms, but all values fit a c_int
+        (match self {
+            Resource::AddressSpace => libc::RLIMIT_AS,
+            Resource::CpuTime => libc::RLIMIT_CPU,
+            Resource::OpenFiles => libc::RLIMIT_NOFILE,
+            Resource::CoreSize => libc::RLIMIT_CORE,
+            Resource::Processes => libc::RLIMIT_NPROC,
+            Resource::FileSize => libc::RLIMIT_FSIZE,
+        }) as libc::c_int
+    }
+}
+
+#[cfg(unix)]
+fn rlim(value: u64) -> libc::rlim_t {
+    if value == UNLIMITED {
+        libc::RLIM_INFINITY
+    } else {
+        value as libc::rlim_t
+    }
+}
+
+/// Apply the limits in the child between fork and exec
+#[cfg(unix)]
+pub(crate) fn apply_limits(command: &mut Command, limits: &[(Resource, Limit)]) {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+
+    // Converted up front, since the child may not allocate
+    let limits: Vec<(libc::c_int, libc::rlimit)> = limits
+        .iter()
+        .map(|(resource, limit)| {
+            (
+                resource.rlimit_resource(),
+                libc::rlimit {
+                    rlim_cur: rlim(limit.soft),
+                    rlim_max: rlim(limit.hard),
+                },
+            )
+        })
+        .collect();
+
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, rlimit) in &limits {
+                if libc::setrlimit(*resource as _, rlimit) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn apply_limits(_command: &mut Command, _limits: &[(Resource, Limit)]) {}
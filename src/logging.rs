@@ -0,0 +1,189 @@
+use crate::process::{ProcessError, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use regex::Regex;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Replacement for redacted text
+const MASK: &str = "***";
+
+/// Removes secrets from command lines before they are logged
+///
+/// An argument following one of the flags is replaced entirely, as is the
+/// value of `--flag=value`. Patterns replace what their capture groups
+/// match, or the whole match without groups.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    flags: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// A redactor leaving everything as it is
+    pub fn none() -> Self {
+        Redactor {
+            flags: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Also redact the argument following `flag`
+    pub fn flag(mut self, flag: &str) -> Self {
+        self.flags.push(flag.to_string());
+        self
+    }
+
+    /// Also redact the text matched by the regular expression `pattern`
+    pub fn pattern(mut self, pattern: &str) -> Result<Self> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Redact a list of arguments
+    pub fn redact(&self, args: &[String]) -> Vec<String> {
+        let mut redacted = Vec::with_capacity(args.len());
+        let mut after_flag = false;
+
+        for arg in args {
+            if after_flag {
+                redacted.push(MASK.to_string());
+                after_flag = false;
+                continue;
+            }
+
+            if self.flags.iter().any(|flag| flag == arg) {
+                after_flag = true;
+                redacted.push(arg.clone());
+                continue;
+            }
+
+            let inline = self
+                .flags
+                .iter()
+                .find(|flag| arg.len() > flag.len() && arg.starts_with(flag.as_str()) && arg[flag.len()..].starts_with('='));
+            match inline {
+                Some(flag) => redacted.push(format!("{}={}", flag, MASK)),
+                None => redacted.push(self.redact_text(arg)),
+            }
+        }
+        redacted
+    }
+
+    /// Apply the patterns to one piece of text
+    fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in &self.patterns {
+            text = pattern
+                .replace_all(&text, |captures: &regex::Captures| {
+                    let whole = captures.get(0).unwrap();
+                    if captures.len() == 1 {
+                        return MASK.to_string();
+                    }
+
+                    // Keep the text around the groups; groups nested in a masked one are already covered
+                    let mut replaced = String::new();
+                    let mut end = whole.start();
+                    for group in captures.iter().skip(1).flatten() {
+                        if group.start() < end {
+                            continue;
+                        }
+                        replaced.push_str(&text[end..group.start()]);
+                        replaced.push_str(MASK);
+                        end = group.end();
+                    }
+                    replaced.push_str(&text[end..whole.end()]);
+                    replaced
+                })
+                .into_owned();
+        }
+        text
+    }
+}
+
+impl Default for Redactor {
+    /// Redacts credentials in URLs, secret-looking query parameters, and ffmpeg's header and key options
+    fn default() -> Self {
+        Redactor::none()
+            .flag("-headers")
+            .flag("-password")
+            .flag("-passphrase")
+            .flag("-key")
+            .pattern(r"://([^/@\s]+)@")
+            .and_then(|redactor| {
+                redactor.pattern(r"(?i)(?:key|token|secret|password|passwd|auth|signature|sig)=([^&\s]+)")
+            })
+            .unwrap()
+    }
+}
+
+/// Redactor applied to logged command lines
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
+/// Replace the redactor applied to logged command lines
+pub fn set_redactor(redactor: Redactor) {
+    *REDACTOR.write().unwrap() = Some(redactor);
+}
+
+/// The redactor applied to logged command lines, `Redactor::default` unless replaced
+pub fn redactor() -> Redactor {
+    REDACTOR.read().unwrap().clone().unwrap_or_default()
+}
+
+/// A command line for the log, with secrets redacted and arguments quoted where needed
+pub(crate) fn command_line(program: &str, args: &[String]) -> String {
+    let mut line = program.to_string();
+    for arg in redactor().redact(args) {
+        line.push(' ');
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            line.push_str(&format!("{:?}", arg));
+        } else {
+            line.push_str(&arg);
+        }
+    }
+    line
+}
+
+/// Callback receiving the library's log records
+pub type LogCallback = Arc<dyn Fn(&Record) + Send + Sync>;
+
+/// Logger forwarding records to the callback set with `set_log_callback`
+struct CallbackLogger {
+    callback: RwLock<Option<LogCallback>>,
+}
+
+impl Log for CallbackLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        // Cloned out of the lock, so the callback may replace itself
+        let callback = self.callback.read().unwrap().clone();
+        if let Some(callback) = callback {
+            if self.enabled(record.metadata()) {
+                callback(record);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CallbackLogger = CallbackLogger {
+    callback: RwLock::new(None),
+};
+
+/// Route log records up to `level` to `callback`, or stop logging with `None`
+///
+/// Installs the library's logger on first use, so it fails with
+/// `InvalidState` if the application already installed another `log`
+/// logger; records then go to that one.
+pub fn set_log_callback(level: LevelFilter, callback: Option<LogCallback>) -> Result<()> {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+    if !*INSTALLED.get_or_init(|| log::set_logger(&LOGGER).is_ok()) {
+        return Err(ProcessError::InvalidState);
+    }
+
+    log::set_max_level(if callback.is_some() { level } else { LevelFilter::Off });
+    *LOGGER.callback.write().unwrap() = callback;
+    Ok(())
+}
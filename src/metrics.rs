@@ -0,0 +1,304 @@
+/// Resource usage of a process, or of its whole process group, at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MetricsSample {
+    /// CPU usage since the previous sample, 100 per fully used core
+    pub cpu_percent: f64,
+
+    /// Resident memory in bytes
+    pub rss_bytes: u64,
+
+    /// Virtual memory in bytes
+    pub vm_bytes: u64,
+
+    /// Number of threads
+    pub threads: u64,
+
+    /// Number of processes the sample covers
+    pub processes: u64,
+
+    /// Bytes read from storage per second since the previous sample
+    pub read_bytes_per_sec: f64,
+
+    /// Bytes written to storage per second since the previous sample
+    pub write_bytes_per_sec: f64,
+}
+
+/// Latest and rolling-average metrics of a sampled process
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessMetrics {
+    /// The most recent sample of the process
+    pub latest: MetricsSample,
+
+    /// Average over the sampling window
+    pub average: MetricsSample,
+
+    /// The most recent sample of the whole process group, in process-group mode
+    pub group_latest: Option<MetricsSample>,
+
+    /// Average of the process group over the sampling window, in process-group mode
+    pub group_average: Option<MetricsSample>,
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::linux::*;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{MetricsSample, ProcessMetrics};
+    use std::collections::{HashMap, VecDeque};
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    /// Mean of each metric over the samples, all zero without samples
+    fn average(samples: impl Iterator<Item = MetricsSample>) -> MetricsSample {
+        let mut sum = MetricsSample::default();
+        let mut count = 0;
+
+        for sample in samples {
+            sum.cpu_percent += sample.cpu_percent;
+            sum.rss_bytes += sample.rss_bytes;
+            sum.vm_bytes += sample.vm_bytes;
+            sum.threads += sample.threads;
+            sum.processes += sample.processes;
+            sum.read_bytes_per_sec += sample.read_bytes_per_sec;
+            sum.write_bytes_per_sec += sample.write_bytes_per_sec;
+            count += 1;
+        }
+
+        if count == 0 {
+            return sum;
+        }
+        MetricsSample {
+            cpu_percent: sum.cpu_percent / count as f64,
+            rss_bytes: sum.rss_bytes / count,
+            vm_bytes: sum.vm_bytes / count,
+            threads: sum.threads / count,
+            processes: sum.processes / count,
+            read_bytes_per_sec: sum.read_bytes_per_sec / count as f64,
+            write_bytes_per_sec: sum.write_bytes_per_sec / count as f64,
+        }
+    }
+
+    /// Counters of one process as read from /proc
+    #[derive(Default, Clone, Copy)]
+    struct Counters {
+        /// User and system CPU time in clock ticks
+        ticks: u64,
+        rss_bytes: u64,
+        vm_bytes: u64,
+        threads: u64,
+        read_bytes: u64,
+        write_bytes: u64,
+    }
+
+    /// Fields of /proc/<pid>/stat following the command name, starting with the state (field 3)
+    fn stat_fields(pid: u32) -> Option<Vec<String>> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let after_comm = &stat[stat.rfind(')')? + 1..];
+        Some(after_comm.split_whitespace().map(str::to_string).collect())
+    }
+
+    /// Value of a `Key: value` line, with a `kB` unit converted to bytes
+    fn field(text: &str, key: &str) -> Option<u64> {
+        let line = text.lines().find(|line| line.split(':').next() == Some(key))?;
+        let mut value = line.split(':').nth(1)?.split_whitespace();
+        let number: u64 = value.next()?.parse().ok()?;
+        Some(if value.next() == Some("kB") { number * 1024 } else { number })
+    }
+
+    fn read_counters(pid: u32) -> Option<Counters> {
+        let stat = stat_fields(pid)?;
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+
+        // io is only readable with ptrace access to the process
+        let io = fs::read_to_string(format!("/proc/{}/io", pid)).unwrap_or_default();
+
+        Some(Counters {
+            ticks: stat.get(14 - 3)?.parse::<u64>().ok()? + stat.get(15 - 3)?.parse::<u64>().ok()?,
+            rss_bytes: field(&status, "VmRSS").unwrap_or(0),
+            vm_bytes: field(&status, "VmSize").unwrap_or(0),
+            threads: field(&status, "Threads").unwrap_or(0),
+            read_bytes: field(&io, "read_bytes").unwrap_or(0),
+            write_bytes: field(&io, "write_bytes").unwrap_or(0),
+        })
+    }
+
+    /// Whether the process is gone or only waiting to be reaped
+    fn has_exited(pid: u32) -> bool {
+        match stat_fields(pid) {
+            Some(stat) => stat[0] == "Z" || stat[0] == "X",
+            None => true,
+        }
+    }
+
+    /// Live processes in the process group `pgid`, including its leader
+    fn group_members(pgid: u32) -> Vec<u32> {
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+
+        entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|&pid| {
+                stat_fields(pid).is_some_and(|stat| stat[0] != "Z" && stat.get(5 - 3) == Some(&pgid.to_string()))
+            })
+            .collect()
+    }
+
+    /// Turns counters of successive readings into samples
+    struct Tracker {
+        /// Clock ticks per second of CPU time
+        ticks_per_sec: f64,
+
+        /// Counters of each process at the previous reading
+        previous: HashMap<u32, Counters>,
+
+        /// Time of the previous reading
+        last: Instant,
+    }
+
+    impl Tracker {
+        fn new() -> Self {
+            Tracker {
+                ticks_per_sec: unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64,
+                previous: HashMap::new(),
+                last: Instant::now(),
+            }
+        }
+
+        /// Read the processes and compute a sample for each of the given sets
+        ///
+        /// Processes first seen in this reading count with everything they
+        /// used so far; processes that exited in between are no longer counted.
+        fn sample(&mut self, pid: u32, group: Option<&[u32]>) -> (MetricsSample, Option<MetricsSample>) {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.last).as_secs_f64().max(f64::EPSILON);
+            self.last = now;
+
+            let pids: Vec<u32> = group.map(<[u32]>::to_vec).unwrap_or_else(|| vec![pid]);
+            let mut current = HashMap::new();
+            for &member in &pids {
+                if let Some(counters) = read_counters(member) {
+                    current.insert(member, counters);
+                }
+            }
+
+            let sample_of = |members: &mut dyn Iterator<Item = &u32>| {
+                let mut sample = MetricsSample::default();
+                for member in members {
+                    let Some(counters) = current.get(member) else {
+                        continue;
+                    };
+                    let before = self.previous.get(member).copied().unwrap_or_default();
+
+                    sample.cpu_percent +=
+                        counters.ticks.saturating_sub(before.ticks) as f64 / self.ticks_per_sec / elapsed * 100.0;
+                    sample.rss_bytes += counters.rss_bytes;
+                    sample.vm_bytes += counters.vm_bytes;
+                    sample.threads += counters.threads;
+                    sample.processes += 1;
+                    sample.read_bytes_per_sec += counters.read_bytes.saturating_sub(before.read_bytes) as f64 / elapsed;
+                    sample.write_bytes_per_sec +=
+                        counters.write_bytes.saturating_sub(before.write_bytes) as f64 / elapsed;
+                }
+                sample
+            };
+
+            let process = sample_of(&mut [pid].iter());
+            let group = group.map(|members| sample_of(&mut members.iter()));
+
+            self.previous = current;
+            (process, group)
+        }
+    }
+
+    /// State shared with the sampling thread
+    struct Shared {
+        /// Set to stop the sampling thread
+        stop: AtomicBool,
+
+        /// Samples of the process and its group within the window, oldest first
+        window: Mutex<VecDeque<(MetricsSample, Option<MetricsSample>)>>,
+    }
+
+    /// Background thread sampling a process's /proc entries
+    pub(crate) struct Sampler {
+        shared: Arc<Shared>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Sampler {
+        /// Sample `pid` every `interval`, averaging over the last `window` samples
+        ///
+        /// With `group` set, the members of the process group led by `pid`
+        /// are sampled as well. Sampling ends once the process has exited.
+        pub(crate) fn start(pid: u32, group: bool, interval: Duration, window: usize) -> Self {
+            let shared = Arc::new(Shared {
+                stop: AtomicBool::new(false),
+                window: Mutex::new(VecDeque::new()),
+            });
+            let window = window.max(1);
+
+            let thread = {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let members = || group.then(|| group_members(pid));
+
+                    // A first reading as the baseline for CPU time and I/O rates
+                    let mut tracker = Tracker::new();
+                    tracker.sample(pid, members().as_deref());
+
+                    loop {
+                        thread::park_timeout(interval);
+                        if shared.stop.load(Ordering::Relaxed) || has_exited(pid) {
+                            break;
+                        }
+
+                        let sample = tracker.sample(pid, members().as_deref());
+                        let mut samples = shared.window.lock().unwrap();
+                        if samples.len() == window {
+                            samples.pop_front();
+                        }
+                        samples.push_back(sample);
+                    }
+                })
+            };
+
+            Sampler {
+                shared,
+                thread: Some(thread),
+            }
+        }
+
+        /// Latest and average metrics, `None` before the first sample
+        pub(crate) fn metrics(&self) -> Option<ProcessMetrics> {
+            let samples = self.shared.window.lock().unwrap();
+            let (latest, group_latest) = *samples.back()?;
+
+            Some(ProcessMetrics {
+                latest,
+                average: average(samples.iter().map(|(process, _)| *process)),
+                group_latest,
+                group_average: group_latest.map(|_| average(samples.iter().filter_map(|(_, group)| *group))),
+            })
+        }
+
+        /// Stop sampling, keeping the collected samples
+        pub(crate) fn stop(&mut self) {
+            self.shared.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                thread.thread().unpark();
+                let _ = thread.join();
+            }
+        }
+    }
+
+    impl Drop for Sampler {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+}
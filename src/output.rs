@@ -0,0 +1,478 @@
+use crate::expect::OutputWatch;
+use crate::watchdog::Activity;
+use log::warn;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Identifies one of a child's standard streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamId {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+impl StreamId {
+    /// Lowercase stream name as used in serialized event logs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamId::Stdin => "stdin",
+            StreamId::Stdout => "stdout",
+            StreamId::Stderr => "stderr",
+        }
+    }
+}
+
+/// A chunk of output, or a write to stdin, with the time it happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputEvent {
+    /// Stream the event belongs to
+    pub stream: StreamId,
+
+    /// Time since the process was spawned
+    pub timestamp: Duration,
+
+    /// Bytes read from stdout or stderr; empty for stdin, whose data is not kept
+    pub data: Vec<u8>,
+
+    /// Number of bytes read or written
+    pub len: usize,
+}
+
+impl OutputEvent {
+    /// Serialize the event as a single JSON object
+    ///
+    /// Output bytes are decoded as UTF-8, replacing invalid sequences.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "stream": self.stream.as_str(),
+            "time": self.timestamp.as_secs_f64(),
+            "len": self.len,
+            "data": String::from_utf8_lossy(&self.data),
+        })
+        .to_string()
+    }
+}
+
+/// Ordered, bounded log of the events of one process
+pub(crate) struct EventLog {
+    /// Time the process was spawned
+    start: Instant,
+
+    /// Maximum number of events kept; older events are dropped
+    capacity: usize,
+
+    /// The recorded events, oldest first
+    events: Mutex<VecDeque<OutputEvent>>,
+}
+
+impl EventLog {
+    pub(crate) fn new(start: Instant, capacity: usize) -> Self {
+        EventLog {
+            start,
+            capacity,
+            events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record an event; stdin events keep only the length of `data`
+    pub(crate) fn record(&self, stream: StreamId, data: &[u8]) {
+        let mut events = self.events.lock().unwrap();
+
+        // Timestamp under the lock so the log stays ordered across threads
+        let event = OutputEvent {
+            stream,
+            timestamp: self.start.elapsed(),
+            data: if stream == StreamId::Stdin { Vec::new() } else { data.to_vec() },
+            len: data.len(),
+        };
+
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<OutputEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Where a child's stdout or stderr goes
+#[derive(Debug, Clone, Default)]
+pub enum OutputSink {
+    /// Discard the output
+    #[default]
+    Null,
+
+    /// Keep the output in memory for `read_stdout` / `read_stderr`
+    Capture,
+
+    /// Write the output to a file
+    File(FileSink),
+}
+
+/// File destination of an output stream
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    /// Path of the log file
+    pub path: PathBuf,
+
+    /// Append to an existing file instead of truncating it
+    pub append: bool,
+
+    /// Rotate the file once it would grow beyond this many bytes
+    pub max_bytes: Option<u64>,
+
+    /// Number of rotated files kept as `<path>.1` (newest) to `<path>.N`
+    pub backups: usize,
+
+    /// Also keep the output in memory so it can still be read from the process
+    pub tee: bool,
+}
+
+impl FileSink {
+    /// Append to the file at `path` without rotation or teeing
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSink {
+            path: path.into(),
+            append: true,
+            max_bytes: None,
+            backups: 0,
+            tee: false,
+        }
+    }
+
+    /// Append to an existing file (the default) or truncate it
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Rotate the file once it reaches `max_bytes`, keeping `backups` old files
+    pub fn rotate(mut self, max_bytes: u64, backups: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self.backups = backups;
+        self
+    }
+
+    /// Also keep the output in memory
+    pub fn tee(mut self, tee: bool) -> Self {
+        self.tee = tee;
+        self
+    }
+
+    /// Whether the child can write to the file itself, without the library draining it
+    fn is_direct(&self) -> bool {
+        self.max_bytes.is_none() && !self.tee
+    }
+
+    fn open(&self) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)
+    }
+}
+
+/// Path of the `index`-th rotated backup of `path`
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// A log file that is rotated into numbered backups when it grows too large
+pub struct RotatingFile {
+    /// Location and rotation settings
+    sink: FileSink,
+
+    /// The currently open file
+    file: File,
+
+    /// Current size of the open file
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Open the file described by `sink`
+    pub fn open(sink: FileSink) -> io::Result<Self> {
+        let file = sink.open()?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { sink, file, size })
+    }
+
+    /// Shift the backups up by one and start a fresh file
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.sink.path;
+
+        if self.sink.backups > 0 {
+            for index in (1..self.sink.backups).rev() {
+                let from = backup_path(path, index);
+                if from.exists() {
+                    fs::rename(&from, backup_path(path, index + 1))?;
+                }
+            }
+            fs::rename(path, backup_path(path, 1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    /// Writes are never split, so a rotated file may exceed the limit by one write
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(max_bytes) = self.sink.max_bytes {
+            if self.size > 0 && self.size + data.len() as u64 > max_bytes {
+                self.rotate()?;
+            }
+        }
+
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// State shared between a process and the readers of its output streams
+pub(crate) struct OutputHooks {
+    /// Event log recording each chunk, if enabled
+    pub(crate) events: Option<Arc<EventLog>>,
+
+    /// Pending output searched by `wait_for_output`
+    pub(crate) watch: Arc<OutputWatch>,
+
+    /// Time of the last activity, tracked for the watchdog
+    pub(crate) activity: Option<Arc<Activity>>,
+}
+
+/// Destinations filled from one output stream by its reader
+pub(crate) struct StreamTarget {
+    /// Stream being drained
+    stream: StreamId,
+
+    /// In-memory buffer read with `read_stdout` / `read_stderr`
+    buffer: Option<Arc<Mutex<Vec<u8>>>>,
+
+    /// Log file the stream is written to
+    file: Option<RotatingFile>,
+
+    /// Whether writing the log file failed already, which is only logged once
+    file_failed: bool,
+
+    /// Event log recording each chunk
+    events: Option<Arc<EventLog>>,
+
+    /// Pending output searched by `wait_for_output`
+    watch: Arc<OutputWatch>,
+
+    /// Time of the last activity, tracked for the watchdog
+    activity: Option<Arc<Activity>>,
+}
+
+impl StreamTarget {
+    fn new(stream: StreamId, buffer: Option<Arc<Mutex<Vec<u8>>>>, file: Option<RotatingFile>, hooks: &OutputHooks) -> Self {
+        hooks.watch.open(stream);
+        StreamTarget {
+            stream,
+            buffer,
+            file,
+            file_failed: false,
+            events: hooks.events.clone(),
+            watch: Arc::clone(&hooks.watch),
+            activity: hooks.activity.clone(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) {
+        if let Some(file) = &mut self.file {
+            // Keep draining even if the log file fails, so the child never blocks
+            if let Err(err) = file.write(data) {
+                if !self.file_failed {
+                    warn!("Writing {} to {} failed: {}", self.stream.as_str(), file.sink.path.display(), err);
+                    self.file_failed = true;
+                }
+            }
+        }
+
+        if let Some(buffer) = &self.buffer {
+            buffer.lock().unwrap().extend_from_slice(data);
+        }
+
+        if let Some(events) = &self.events {
+            events.record(self.stream, data);
+        }
+
+        self.watch.push(self.stream, data);
+
+        if let Some(activity) = &self.activity {
+            activity.touch();
+        }
+    }
+
+    /// Report an error that ended reading the stream
+    pub(crate) fn read_failed(&self, err: &io::Error) {
+        // A pseudo-terminal reports EIO once the child side is closed, which is the usual end
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(libc::EIO) {
+            return;
+        }
+
+        warn!("Reading {} failed: {}", self.stream.as_str(), err);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.watch.close(self.stream);
+    }
+}
+
+/// Prepare the child's end of an output stream and the target its reader fills
+///
+/// With an event log or a watchdog, file output always passes through a
+/// reader so that it can be recorded and counted as activity.
+pub(crate) fn prepare_output(
+    sink: &OutputSink,
+    stream: StreamId,
+    buffer: &Arc<Mutex<Vec<u8>>>,
+    hooks: &OutputHooks,
+) -> io::Result<(Stdio, Option<StreamTarget>)> {
+    Ok(match sink {
+        OutputSink::Null => (Stdio::null(), None),
+        OutputSink::Capture => (
+            Stdio::piped(),
+            Some(StreamTarget::new(stream, Some(Arc::clone(buffer)), None, hooks)),
+        ),
+        OutputSink::File(file) if file.is_direct() && hooks.events.is_none() && hooks.activity.is_none() => {
+            (Stdio::from(file.open()?), None)
+        }
+        OutputSink::File(file) => {
+            let rotating = RotatingFile::open(file.clone())?;
+            let buffer = file.tee.then(|| Arc::clone(buffer));
+            (Stdio::piped(), Some(StreamTarget::new(stream, buffer, Some(rotating), hooks)))
+        }
+    })
+}
+
+/// Prepare the target filled from an output stream the library always drains itself
+///
+/// Used for children of a backend, whose streams can not be handed to a
+/// file directly. `None` means the stream is discarded.
+pub(crate) fn prepare_drained_output(
+    sink: &OutputSink,
+    stream: StreamId,
+    buffer: &Arc<Mutex<Vec<u8>>>,
+    hooks: &OutputHooks,
+) -> io::Result<Option<StreamTarget>> {
+    Ok(match sink {
+        OutputSink::Null => None,
+        OutputSink::Capture => Some(StreamTarget::new(stream, Some(Arc::clone(buffer)), None, hooks)),
+        OutputSink::File(file) => {
+            let rotating = RotatingFile::open(file.clone())?;
+            let buffer = file.tee.then(|| Arc::clone(buffer));
+            Some(StreamTarget::new(stream, buffer, Some(rotating), hooks))
+        }
+    })
+}
+
+/// Prepare the target filled from a pseudo-terminal, which carries both stdout and stderr
+///
+/// The output is always drained, and kept for `read_stdout` unless it goes to a file without tee.
+#[cfg(unix)]
+pub(crate) fn prepare_pty_output(
+    sink: &OutputSink,
+    buffer: &Arc<Mutex<Vec<u8>>>,
+    hooks: &OutputHooks,
+) -> io::Result<StreamTarget> {
+    Ok(match sink {
+        OutputSink::Null | OutputSink::Capture => {
+            StreamTarget::new(StreamId::Stdout, Some(Arc::clone(buffer)), None, hooks)
+        }
+        OutputSink::File(file) => {
+            let rotating = RotatingFile::open(file.clone())?;
+            let buffer = file.tee.then(|| Arc::clone(buffer));
+            StreamTarget::new(StreamId::Stdout, buffer, Some(rotating), hooks)
+        }
+    })
+}
+
+/// Completion of draining one output stream
+#[derive(Default)]
+pub(crate) struct Drained {
+    done: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl Drained {
+    /// Mark the stream as drained to EOF
+    pub(crate) fn set(&self) {
+        *self.done.lock().unwrap() = true;
+        self.changed.notify_all();
+    }
+
+    /// Wait until the stream is drained to EOF
+    pub(crate) fn wait(&self) {
+        let _done = self.changed.wait_while(self.done.lock().unwrap(), |done| !*done).unwrap();
+    }
+}
+
+/// Write everything read from `stream` to `target`
+///
+/// Streams are serviced by the shared reactor thread, falling back to a
+/// thread per stream if epoll is unavailable.
+#[cfg(target_os = "linux")]
+pub(crate) fn spawn_reader(stream: impl Into<std::os::fd::OwnedFd>, target: StreamTarget) -> Arc<Drained> {
+    let stream = stream.into();
+    let drained = Arc::new(Drained::default());
+
+    match crate::reactor::Reactor::get() {
+        Some(reactor) => reactor.register(stream, target, Arc::clone(&drained)),
+        None => spawn_reader_thread(File::from(stream), target, Arc::clone(&drained)),
+    }
+    drained
+}
+
+/// Write everything read from `stream` to `target`
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn spawn_reader<R: Read + Send + 'static>(stream: R, target: StreamTarget) -> Arc<Drained> {
+    let drained = Arc::new(Drained::default());
+    spawn_reader_thread(stream, target, Arc::clone(&drained));
+    drained
+}
+
+/// Write everything read from `stream` to `target` on a thread of its own
+pub(crate) fn spawn_reader_thread<R: Read + Send + 'static>(mut stream: R, mut target: StreamTarget, drained: Arc<Drained>) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break, // EOF
+                Ok(n) => target.write(&buf[..n]),
+                Err(err) => {
+                    target.read_failed(&err);
+                    break;
+                }
+            }
+        }
+        target.finish();
+        drained.set();
+    });
+}
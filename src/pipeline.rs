@@ -0,0 +1,164 @@
+use crate::process::{Process, ProcessBuilder, ProcessError, Result};
+use std::io;
+use std::process::Stdio;
+
+/// Combine the exit codes of a pipeline's stages like a shell with `pipefail`
+///
+/// Returns the code of the last stage that failed, or 0 if all succeeded.
+pub fn pipefail(codes: &[i32]) -> i32 {
+    codes.iter().rev().copied().find(|code| *code != 0).unwrap_or(0)
+}
+
+/// Configuration of a chain of processes, each stage's stdout feeding the next stage's stdin
+///
+/// The first stage's stdin and the last stage's stdout are configured by
+/// their builders as usual, so the host can write into the pipeline and
+/// read from it. The connected streams of the other stages are replaced by
+/// kernel pipes, and every stage's stderr is handled by its own builder.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    /// The stages, in data flow order
+    stages: Vec<ProcessBuilder>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Append a stage reading the output of the previous one
+    pub fn stage(&mut self, builder: ProcessBuilder) -> &mut Self {
+        self.stages.push(builder);
+        self
+    }
+
+    /// Number of stages
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Whether the pipeline has no stages
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Spawn all stages, connected by pipes
+    ///
+    /// Fails without stages, or with a stage in PTY mode. If a stage cannot
+    /// be spawned, the stages spawned before it are terminated.
+    pub fn spawn(&self) -> Result<RunningPipeline> {
+        if self.stages.is_empty() {
+            return Err(ProcessError::InvalidState);
+        }
+
+        let mut processes: Vec<Process> = Vec::with_capacity(self.stages.len());
+        let mut previous_output = None;
+
+        for (index, builder) in self.stages.iter().enumerate() {
+            let result = (|| -> Result<(Process, Option<io::PipeReader>)> {
+                let stdin = previous_output.take().map(Stdio::from);
+
+                // The write end moves into the stage, so it is closed here once the stage is spawned
+                let (stdout, next_input) = if index + 1 < self.stages.len() {
+                    let (reader, writer) = io::pipe()?;
+                    (Some(Stdio::from(writer)), Some(reader))
+                } else {
+                    (None, None)
+                };
+
+                let process = builder.spawn_connected(stdin, stdout)?;
+                Ok((process, next_input))
+            })();
+
+            match result {
+                Ok((process, next_input)) => {
+                    processes.push(process);
+                    previous_output = next_input;
+                }
+                Err(err) => {
+                    for process in &mut processes {
+                        let _ = process.close();
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(RunningPipeline { stages: processes })
+    }
+}
+
+/// The running stages of a pipeline
+pub struct RunningPipeline {
+    /// The stage processes, in data flow order
+    stages: Vec<Process>,
+}
+
+impl RunningPipeline {
+    /// Number of stages
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Whether the pipeline has no stages; never true for a spawned pipeline
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Access one stage, e.g. for its exit info or resource usage
+    pub fn stage(&mut self, index: usize) -> Option<&mut Process> {
+        self.stages.get_mut(index)
+    }
+
+    /// Write data to the first stage's stdin
+    pub fn write_stdin(&mut self, data: &[u8]) -> Result<usize> {
+        self.stages[0].write_stdin(data)
+    }
+
+    /// Close the first stage's stdin, signalling EOF to the pipeline
+    pub fn close_stdin(&mut self) {
+        self.stages[0].close_stdin();
+    }
+
+    /// Read data from the last stage's stdout buffer
+    pub fn read_stdout(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.stages.last_mut().ok_or(ProcessError::InvalidState)?.read_stdout(buf)
+    }
+
+    /// Read data from one stage's stderr buffer
+    pub fn read_stderr(&mut self, index: usize, buf: &mut [u8]) -> Result<usize> {
+        self.stages.get_mut(index).ok_or(ProcessError::InvalidState)?.read_stderr(buf)
+    }
+
+    /// Check if any stage is still running
+    pub fn is_running(&mut self) -> bool {
+        // Every stage is checked, so exited ones are reaped
+        let mut running = false;
+        for stage in &mut self.stages {
+            running |= stage.is_running();
+        }
+        running
+    }
+
+    /// Wait for all stages and return the exit code of each, in stage order
+    pub fn wait_all(&mut self) -> Result<Vec<i32>> {
+        self.stages.iter_mut().map(Process::wait).collect()
+    }
+
+    /// Wait for all stages and return their combined exit code, see `pipefail`
+    pub fn wait(&mut self) -> Result<i32> {
+        Ok(pipefail(&self.wait_all()?))
+    }
+
+    /// Close stdin, terminate all stages, and clean up resources
+    pub fn close(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for stage in &mut self.stages {
+            if let Err(err) = stage.close() {
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
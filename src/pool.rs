@@ -0,0 +1,485 @@
+use crate::exit::ExitInfo;
+use crate::process::{ProcessBuilder, ProcessError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a worker checks whether its running job has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Identifier of a job submitted to a pool, unique within the pool
+pub type JobId = u64;
+
+/// Where a job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Waiting for a free slot
+    Queued,
+
+    /// The process is running
+    Running,
+
+    /// The process ran and exited, successfully or not
+    Completed,
+
+    /// The process could not be spawned
+    Failed,
+
+    /// The job was cancelled before or while running
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job will not change anymore
+    pub fn is_done(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// Outcome of a finished job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobResult {
+    /// `Completed`, `Failed` or `Cancelled`
+    pub status: JobStatus,
+
+    /// How the process ended, `None` if it never ran
+    pub exit: Option<ExitInfo>,
+
+    /// Captured stdout, empty unless the builder captures it
+    pub stdout: Vec<u8>,
+
+    /// Captured stderr
+    pub stderr: Vec<u8>,
+
+    /// Time from spawn until the process exited
+    pub duration: Duration,
+
+    /// Why the process could not be spawned, for `Failed` jobs
+    pub error: Option<String>,
+}
+
+impl JobResult {
+    fn not_run(status: JobStatus, error: Option<String>) -> Self {
+        JobResult {
+            status,
+            exit: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            duration: Duration::ZERO,
+            error,
+        }
+    }
+}
+
+/// Number of jobs in each state, counting every job ever submitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolProgress {
+    /// Jobs submitted so far
+    pub submitted: u64,
+
+    /// Jobs waiting for a free slot
+    pub queued: u64,
+
+    /// Jobs whose process is running
+    pub running: u64,
+
+    /// Jobs whose process exited
+    pub completed: u64,
+
+    /// Jobs whose process could not be spawned
+    pub failed: u64,
+
+    /// Jobs cancelled before or while running
+    pub cancelled: u64,
+}
+
+impl PoolProgress {
+    /// Number of jobs that are done
+    pub fn done(&self) -> u64 {
+        self.completed + self.failed + self.cancelled
+    }
+}
+
+/// Callback invoked on a worker thread whenever a job changes its status
+///
+/// Cancelling a queued job, directly or by closing the pool, is reported from
+/// a worker thread too, never from the thread that cancelled it.
+pub type ProgressCallback = Arc<dyn Fn(JobId, JobStatus, &PoolProgress) + Send + Sync>;
+
+/// A submitted job
+struct Job {
+    /// Configuration of the job, taken by the worker that runs it
+    builder: Option<ProcessBuilder>,
+    status: JobStatus,
+
+    /// Set to make the worker running the job kill it
+    cancel: bool,
+    result: Option<JobResult>,
+}
+
+/// State shared with the worker threads
+#[derive(Default)]
+struct State {
+    jobs: HashMap<JobId, Job>,
+
+    /// Jobs waiting for a worker, in submission order
+    queue: VecDeque<JobId>,
+    next_id: JobId,
+    progress: PoolProgress,
+
+    /// Queued jobs that were cancelled, with the progress right after, waiting
+    /// for a worker to report them to the callback
+    cancellations: VecDeque<(JobId, PoolProgress)>,
+
+    /// Set when the workers should exit
+    closing: bool,
+}
+
+impl State {
+    /// Move a job to a new status and keep the counters in sync
+    fn set_status(&mut self, id: JobId, status: JobStatus) {
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return;
+        };
+
+        match job.status {
+            JobStatus::Queued => self.progress.queued -= 1,
+            JobStatus::Running => self.progress.running -= 1,
+            _ => {}
+        }
+        match status {
+            JobStatus::Queued => self.progress.queued += 1,
+            JobStatus::Running => self.progress.running += 1,
+            JobStatus::Completed => self.progress.completed += 1,
+            JobStatus::Failed => self.progress.failed += 1,
+            JobStatus::Cancelled => self.progress.cancelled += 1,
+        }
+        job.status = status;
+    }
+
+    /// Cancel a job that is still queued, leaving the callback to a worker
+    fn cancel_queued(&mut self, id: JobId, report: bool) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.builder = None;
+            job.result = Some(JobResult::not_run(JobStatus::Cancelled, None));
+        }
+        self.set_status(id, JobStatus::Cancelled);
+        if report {
+            self.cancellations.push_back((id, self.progress));
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    callback: Option<ProgressCallback>,
+}
+
+impl Shared {
+    /// Record a status change, notify waiters and report it to the callback
+    fn update(&self, mut state: MutexGuard<'_, State>, id: JobId, status: JobStatus) {
+        state.set_status(id, status);
+        let progress = state.progress;
+        drop(state);
+
+        self.changed.notify_all();
+        self.report(id, status, &progress);
+    }
+
+    /// Report a status change to the callback
+    fn report(&self, id: JobId, status: JobStatus, progress: &PoolProgress) {
+        if let Some(callback) = &self.callback {
+            callback(id, status, progress);
+        }
+    }
+
+    /// Report the oldest pending cancellation, if any, releasing the lock first
+    ///
+    /// Returns `false` if there was nothing to report.
+    fn report_cancellation(&self, mut state: MutexGuard<'_, State>) -> bool {
+        let Some((id, progress)) = state.cancellations.pop_front() else {
+            return false;
+        };
+        drop(state);
+
+        self.report(id, JobStatus::Cancelled, &progress);
+        true
+    }
+}
+
+/// Runs queued processes with at most a fixed number running at the same time
+///
+/// Jobs get no stdin unless their builder reads it from a file or buffer.
+/// Results are kept until removed with `remove`.
+pub struct ProcessPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ProcessPool {
+    /// Create a pool running at most `max_concurrent` processes at a time
+    pub fn new(max_concurrent: usize) -> Self {
+        Self::start(max_concurrent, None)
+    }
+
+    /// Create a pool that calls `callback` whenever a job changes its status
+    pub fn with_progress(
+        max_concurrent: usize,
+        callback: impl Fn(JobId, JobStatus, &PoolProgress) + Send + Sync + 'static,
+    ) -> Self {
+        Self::start(max_concurrent, Some(Arc::new(callback)))
+    }
+
+    fn start(max_concurrent: usize, callback: Option<ProgressCallback>) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                next_id: 1,
+                ..State::default()
+            }),
+            changed: Condvar::new(),
+            callback,
+        });
+
+        let workers = (0..max_concurrent.max(1))
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+
+        ProcessPool { shared, workers }
+    }
+
+    /// Queue a process to be spawned from the builder's configuration
+    pub fn submit(&self, builder: &ProcessBuilder) -> Result<JobId> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closing {
+            return Err(ProcessError::InvalidState);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            Job {
+                builder: Some(builder.clone()),
+                status: JobStatus::Queued,
+                cancel: false,
+                result: None,
+            },
+        );
+        state.queue.push_back(id);
+        state.progress.submitted += 1;
+        state.progress.queued += 1;
+        drop(state);
+
+        self.shared.changed.notify_all();
+        Ok(id)
+    }
+
+    /// Cancel a job, killing its process if it is running
+    ///
+    /// Returns `false` if the job is unknown or already done.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let Some(job) = state.jobs.get_mut(&id) else {
+            return false;
+        };
+
+        match job.status {
+            JobStatus::Queued => {
+                // A worker reports it, so the callback never runs inside this call
+                state.queue.retain(|queued| *queued != id);
+                state.cancel_queued(id, self.shared.callback.is_some());
+                drop(state);
+                self.shared.changed.notify_all();
+                true
+            }
+            JobStatus::Running => {
+                // The worker kills the process and records the result
+                job.cancel = true;
+                drop(state);
+                self.shared.changed.notify_all();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Status of a job, `None` if it is unknown or was removed
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.shared.state.lock().unwrap().jobs.get(&id).map(|job| job.status)
+    }
+
+    /// Result of a job, `None` while it is not done
+    pub fn result(&self, id: JobId) -> Option<JobResult> {
+        self.shared.state.lock().unwrap().jobs.get(&id)?.result.clone()
+    }
+
+    /// Remove a finished job, returning its result
+    pub fn remove(&self, id: JobId) -> Option<JobResult> {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.jobs.get(&id)?.status.is_done() {
+            return None;
+        }
+        state.jobs.remove(&id)?.result
+    }
+
+    /// Number of jobs in each state
+    pub fn progress(&self) -> PoolProgress {
+        self.shared.state.lock().unwrap().progress
+    }
+
+    /// Wait until a job is done and return its result
+    ///
+    /// Fails with `Timeout` if the job is not done in time, and with
+    /// `InvalidState` if it is unknown or was removed.
+    pub fn wait(&self, id: JobId, timeout: Option<Duration>) -> Result<JobResult> {
+        let state = self.shared.state.lock().unwrap();
+        let pending = |state: &mut State| state.jobs.get(&id).is_some_and(|job| job.result.is_none());
+
+        let state = match timeout {
+            Some(timeout) => self.shared.changed.wait_timeout_while(state, timeout, pending).unwrap().0,
+            None => self.shared.changed.wait_while(state, pending).unwrap(),
+        };
+
+        let job = state.jobs.get(&id).ok_or(ProcessError::InvalidState)?;
+        job.result.clone().ok_or(ProcessError::Timeout)
+    }
+
+    /// Wait until no job is queued or running
+    ///
+    /// Returns `false` if jobs are still pending after `timeout`.
+    pub fn wait_all(&self, timeout: Option<Duration>) -> bool {
+        let state = self.shared.state.lock().unwrap();
+        let pending = |state: &mut State| state.progress.queued + state.progress.running > 0;
+
+        let state = match timeout {
+            Some(timeout) => self.shared.changed.wait_timeout_while(state, timeout, pending).unwrap().0,
+            None => self.shared.changed.wait_while(state, pending).unwrap(),
+        };
+        state.progress.queued + state.progress.running == 0
+    }
+
+    /// Cancel all queued and running jobs and stop the workers
+    ///
+    /// Every cancellation is reported to the callback before this returns.
+    pub fn close(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closing = true;
+        for id in std::mem::take(&mut state.queue) {
+            state.cancel_queued(id, self.shared.callback.is_some());
+        }
+        for job in state.jobs.values_mut() {
+            job.cancel = true;
+        }
+        drop(state);
+        self.shared.changed.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ProcessPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Body of a worker thread
+fn work(shared: &Shared) {
+    loop {
+        let mut state = shared
+            .changed
+            .wait_while(shared.state.lock().unwrap(), |state| {
+                state.queue.is_empty() && state.cancellations.is_empty() && !state.closing
+            })
+            .unwrap();
+        if !state.cancellations.is_empty() {
+            shared.report_cancellation(state);
+            continue;
+        }
+        let Some(id) = state.queue.pop_front() else {
+            return;
+        };
+        let Some(builder) = state.jobs.get_mut(&id).and_then(|job| job.builder.take()) else {
+            continue;
+        };
+        shared.update(state, id, JobStatus::Running);
+
+        let result = run(shared, id, &builder);
+        let mut state = shared.state.lock().unwrap();
+        let status = result.status;
+        if let Some(job) = state.jobs.get_mut(&id) {
+            job.result = Some(result);
+        }
+        shared.update(state, id, status);
+    }
+}
+
+/// Run one job to completion or cancellation
+fn run(shared: &Shared, id: JobId, builder: &ProcessBuilder) -> JobResult {
+    let started = Instant::now();
+    let mut process = match builder.spawn() {
+        Ok(process) => process,
+        Err(err) => return JobResult::not_run(JobStatus::Failed, Some(err.to_string())),
+    };
+    process.close_stdin();
+
+    let cancelled = loop {
+        if !process.is_running() {
+            break false;
+        }
+
+        let state = shared.state.lock().unwrap();
+        let (state, _) = shared
+            .changed
+            .wait_timeout_while(state, POLL_INTERVAL, |state| {
+                !state.jobs.get(&id).is_some_and(|job| job.cancel) && state.cancellations.is_empty()
+            })
+            .unwrap();
+        if state.jobs.get(&id).is_some_and(|job| job.cancel) {
+            break true;
+        }
+        // Busy workers report cancellations too, so they are not held back by long jobs
+        shared.report_cancellation(state);
+    };
+
+    if cancelled {
+        let _ = process.close();
+        return JobResult {
+            exit: process.exit_info(),
+            duration: started.elapsed(),
+            ..JobResult::not_run(JobStatus::Cancelled, None)
+        };
+    }
+
+    let duration = started.elapsed();
+    let output = process.wait_with_output();
+    let exit = process.exit_info();
+    let _ = process.close();
+
+    match output {
+        Ok(output) => JobResult {
+            status: JobStatus::Completed,
+            exit,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            duration,
+            error: None,
+        },
+        Err(err) => JobResult {
+            exit,
+            duration,
+            ..JobResult::not_run(JobStatus::Failed, Some(err.to_string()))
+        },
+    }
+}
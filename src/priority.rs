@@ -0,0 +1,195 @@
+use crate::process::{ProcessError, Result};
+use std::process::Command;
+
+/// CPU scheduling policy of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    /// The default time-sharing policy (`SCHED_OTHER`)
+    Other,
+
+    /// Time-sharing with a penalty for waking up, for non-interactive work (`SCHED_BATCH`)
+    Batch,
+
+    /// Only runs when no other process wants the CPU (`SCHED_IDLE`)
+    Idle,
+}
+
+/// I/O scheduling class and level of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Served before all other classes, level 0 (highest) to 7; needs privileges
+    RealTime(u8),
+
+    /// The default class, level 0 (highest) to 7
+    BestEffort(u8),
+
+    /// Only served when no other process uses the disk
+    Idle,
+}
+
+/// Scheduling settings of a process; `None` leaves a setting unchanged
+///
+/// Everything but the niceness is only supported on Linux.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Priority {
+    /// Niceness, from -20 (highest priority) to 19
+    pub nice: Option<i32>,
+
+    /// CPU scheduling policy
+    pub policy: Option<SchedPolicy>,
+
+    /// I/O scheduling class
+    pub io: Option<IoPriority>,
+
+    /// Indices of the CPUs the process may run on
+    pub cpus: Option<Vec<usize>>,
+}
+
+impl Priority {
+    /// Whether no setting would be changed
+    pub fn is_empty(&self) -> bool {
+        self.nice.is_none() && self.policy.is_none() && self.io.is_none() && self.cpus.is_none()
+    }
+
+    /// Fail for settings the platform cannot apply
+    pub(crate) fn check_supported(&self) -> Result<()> {
+        if cfg!(not(unix)) && !self.is_empty() {
+            return Err(ProcessError::Unsupported("scheduling priority"));
+        }
+        if cfg!(not(target_os = "linux")) && (self.policy.is_some() || self.io.is_some() || self.cpus.is_some()) {
+            return Err(ProcessError::Unsupported("scheduling policy, I/O priority and CPU affinity"));
+        }
+        Ok(())
+    }
+}
+
+/// Apply the settings in the child between fork and exec, so every thread inherits them
+#[cfg(unix)]
+pub(crate) fn apply_priority(command: &mut Command, priority: &Priority) {
+    use std::os::unix::process::CommandExt;
+
+    // Prepared up front, since the child may not allocate
+    let settings = Settings::new(priority);
+
+    unsafe {
+        command.pre_exec(move || settings.apply(0));
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn apply_priority(_command: &mut Command, _priority: &Priority) {}
+
+/// Change the settings of a running process and all of its threads
+#[cfg(unix)]
+pub(crate) fn set_priority(pid: u32, priority: &Priority) -> Result<()> {
+    priority.check_supported()?;
+    let settings = Settings::new(priority);
+
+    // Linux schedules threads individually, so each one is adjusted
+    #[cfg(target_os = "linux")]
+    {
+        for entry in std::fs::read_dir(format!("/proc/{}/task", pid))? {
+            if let Some(tid) = entry?.file_name().to_str().and_then(|name| name.parse().ok()) {
+                match settings.apply(tid) {
+                    // The thread exited in the meantime
+                    Err(err) if err.raw_os_error() == Some(libc::ESRCH) => {}
+                    result => result?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    Ok(settings.apply(pid as libc::pid_t)?)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_priority(_pid: u32, priority: &Priority) -> Result<()> {
+    priority.check_supported()
+}
+
+/// Settings converted to the values passed to the system calls
+#[cfg(unix)]
+struct Settings {
+    nice: Option<libc::c_int>,
+    #[cfg(target_os = "linux")]
+    policy: Option<libc::c_int>,
+    #[cfg(target_os = "linux")]
+    ioprio: Option<libc::c_int>,
+    #[cfg(target_os = "linux")]
+    cpus: Option<libc::cpu_set_t>,
+}
+
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+#[cfg(unix)]
+impl Settings {
+    fn new(priority: &Priority) -> Self {
+        Settings {
+            nice: priority.nice,
+            #[cfg(target_os = "linux")]
+            policy: priority.policy.map(|policy| match policy {
+                SchedPolicy::Other => libc::SCHED_OTHER,
+                SchedPolicy::Batch => libc::SCHED_BATCH,
+                SchedPolicy::Idle => libc::SCHED_IDLE,
+            }),
+            #[cfg(target_os = "linux")]
+            ioprio: priority.io.map(|io| {
+                let (class, level) = match io {
+                    IoPriority::RealTime(level) => (1, level.min(7)),
+                    IoPriority::BestEffort(level) => (2, level.min(7)),
+                    IoPriority::Idle => (3, 0),
+                };
+                (class << IOPRIO_CLASS_SHIFT) | level as libc::c_int
+            }),
+            #[cfg(target_os = "linux")]
+            cpus: priority.cpus.as_ref().map(|cpus| {
+                let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+                for &cpu in cpus {
+                    if cpu < libc::CPU_SETSIZE as usize {
+                        unsafe { libc::CPU_SET(cpu, &mut set) };
+                    }
+                }
+                set
+            }),
+        }
+    }
+
+    /// Apply the settings to a thread or process, 0 meaning the calling one
+    fn apply(&self, pid: libc::pid_t) -> std::io::Result<()> {
+        use std::io::Error;
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(policy) = self.policy {
+                let param = libc::sched_param { sched_priority: 0 };
+                if unsafe { libc::sched_setscheduler(pid, policy, &param) } == -1 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if let Some(ioprio) = self.ioprio {
+                if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, pid, ioprio) } == -1 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if let Some(cpus) = &self.cpus {
+                if unsafe { libc::sched_setaffinity(pid, std::mem::size_of::<libc::cpu_set_t>(), cpus) } == -1 {
+                    return Err(Error::last_os_error());
+                }
+            }
+        }
+
+        if let Some(nice) = self.nice {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } == -1 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::process::{ProcessBuilder, ProcessError, Result};
+use crate::which::which;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Features supported by a particular ffmpeg build
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// Names of the available encoders, e.g. `libx264`
+    pub encoders: BTreeSet<String>,
+
+    /// Names of the formats that support muxing, e.g. `mp4`
+    pub muxers: BTreeSet<String>,
+
+    /// Names of the known pixel formats, e.g. `yuv420p`
+    pub pix_fmts: BTreeSet<String>,
+}
+
+impl Capabilities {
+    /// Build a capability set from the output of `-encoders`, `-muxers` and `-pix_fmts`
+    pub fn from_listings(encoders: &str, muxers: &str, pix_fmts: &str) -> Self {
+        Capabilities {
+            encoders: parse_listing(encoders),
+            muxers: parse_listing(muxers),
+            pix_fmts: parse_listing(pix_fmts),
+        }
+    }
+
+    /// Check if the named encoder is available
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    /// Check if the named format can be muxed
+    pub fn has_muxer(&self, name: &str) -> bool {
+        self.muxers.contains(name)
+    }
+
+    /// Check if the named pixel format is known
+    pub fn has_pix_fmt(&self, name: &str) -> bool {
+        self.pix_fmts.contains(name)
+    }
+
+    /// Pick the first available encoder from a list ordered by preference
+    pub fn pick_encoder<'a>(&self, candidates: &[&'a str]) -> Option<&'a str> {
+        candidates
+            .iter()
+            .copied()
+            .find(|candidate| self.has_encoder(candidate))
+    }
+}
+
+/// Parse an ffmpeg listing: a legend, a dashed separator line, then one
+/// `FLAGS NAME ...` entry per line
+///
+/// Entries naming several formats (`mov,mp4,m4a`) yield each name.
+pub fn parse_listing(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .skip_while(|line| !is_separator(line))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .flat_map(|names| names.split(','))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 2 && line.chars().all(|c| c == '-')
+}
+
+/// Cache key identifying an ffmpeg build by path and modification time
+type CacheKey = (PathBuf, Option<SystemTime>);
+
+fn cache() -> &'static Mutex<HashMap<CacheKey, Arc<Capabilities>>> {
+    static CACHE: OnceLock<Mutex<HashMap<CacheKey, Arc<Capabilities>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Probe the capabilities of the given ffmpeg executable
+///
+/// Results are cached per executable path and modification time, so
+/// replacing the executable triggers a fresh probe.
+pub fn probe(ffmpeg: &str) -> Result<Arc<Capabilities>> {
+    let path = which(ffmpeg).unwrap_or_else(|_| PathBuf::from(ffmpeg));
+    let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+    let key = (path, modified);
+
+    if let Some(capabilities) = cache().lock().unwrap().get(&key) {
+        return Ok(Arc::clone(capabilities));
+    }
+
+    // Probe without holding the lock; a concurrent probe of the same build is harmless
+    let capabilities = Arc::new(probe_uncached(ffmpeg)?);
+    cache()
+        .lock()
+        .unwrap()
+        .insert(key, Arc::clone(&capabilities));
+
+    Ok(capabilities)
+}
+
+/// Probe the capabilities of the given ffmpeg executable, bypassing the cache
+pub fn probe_uncached(ffmpeg: &str) -> Result<Capabilities> {
+    Ok(Capabilities::from_listings(
+        &run_listing(ffmpeg, "-encoders")?,
+        &run_listing(ffmpeg, "-muxers")?,
+        &run_listing(ffmpeg, "-pix_fmts")?,
+    ))
+}
+
+/// Run `ffmpeg -hide_banner <option>` and return its stdout
+fn run_listing(ffmpeg: &str, option: &str) -> Result<String> {
+    let output = ProcessBuilder::new(ffmpeg)
+        .args(&["-hide_banner", option])
+        .capture_stdout(true)
+        .spawn()?
+        .wait_with_output()?;
+
+    if output.exit_code != 0 {
+        return Err(ProcessError::CommandFailed {
+            code: output.exit_code,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -1,9 +1,40 @@
-use std::io::{self, Read, Write};
-use std::process::{Child, Command, Stdio};
+use crate::backend::{self, BackendChild, ProcessBackend, Spawned};
+use crate::cgroup::{CgroupConfig, CgroupStats};
+use crate::exit::ExitInfo;
+use crate::expect::{OutputMatch, OutputWatch, Pattern};
+use crate::limits::{apply_limits, Limit, Resource};
+use crate::logging::command_line;
+use crate::metrics::ProcessMetrics;
+use crate::output::{prepare_drained_output, prepare_output, spawn_reader, spawn_reader_thread, Drained, EventLog, OutputEvent, OutputHooks, OutputSink, StreamId};
+use crate::priority::{apply_priority, set_priority, IoPriority, Priority, SchedPolicy};
+use crate::pty::PtySize;
+use crate::reaper::{self, ExitWatch};
+use crate::sandbox::SandboxConfig;
+use crate::usage::ResourceUsage;
+use crate::watchdog::{Activity, Deadline, Monitor, Stall, Target, Watchdog};
+use log::{info, warn};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+#[cfg(target_os = "linux")]
+use crate::cgroup::Cgroup;
+#[cfg(target_os = "linux")]
+use crate::sandbox::Sandbox;
+#[cfg(target_os = "linux")]
+use crate::metrics::Sampler;
+#[cfg(unix)]
+use crate::output::prepare_pty_output;
+#[cfg(unix)]
+use crate::pty;
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt as _;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
@@ -20,23 +51,751 @@ pub enum ProcessError {
     
     #[error("Null pointer provided")]
     NullPointer,
+    
+    #[error("Command exited with code {code}: {stderr}")]
+    CommandFailed { code: i32, stderr: String },
+    
+    #[error("No recorded segments available")]
+    NothingRecorded,
+    
+    #[error("{program} not found in {searched}")]
+    NotFound { program: String, searched: String },
+    
+    #[error("{0} is not executable")]
+    NotExecutable(PathBuf),
+    
+    #[error("stdin not piped")]
+    StdinNotPiped,
+    
+    #[error("Timed out")]
+    Timeout,
+    
+    #[error("Output closed before the pattern appeared")]
+    OutputClosed,
+    
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    
+    #[error("{0} is not supported on this platform")]
+    Unsupported(&'static str),
+    
+    #[error("No fake script for {0}")]
+    Unscripted(String),
+    
+    #[error("Invalid profile: {0}")]
+    InvalidProfile(String),
 }
 
 pub type Result<T> = std::result::Result<T, ProcessError>;
 
+/// How often a process shutting down is checked for its exit
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Exit code and remaining captured output of a finished process
+#[derive(Debug, Clone)]
+pub struct ProcessOutput {
+    /// Exit code of the process, -1 if it was terminated by a signal
+    pub exit_code: i32,
+    
+    /// Captured stdout not yet read with `read_stdout`
+    pub stdout: Vec<u8>,
+    
+    /// Captured stderr not yet read with `read_stderr`
+    pub stderr: Vec<u8>,
+}
+
+/// Where a spawned process reads its stdin from
+#[derive(Debug, Clone, Default)]
+pub enum StdinSource {
+    /// A pipe written with `write_stdin`
+    #[default]
+    Piped,
+    
+    /// The contents of a file
+    File(PathBuf),
+    
+    /// An in-memory buffer, fed by a background thread and followed by EOF
+    Bytes(Arc<[u8]>),
+    
+    /// The null device, giving immediate EOF
+    Null,
+}
+
+/// How `Process::close` stops a process that is still running
+///
+/// The process is asked to finish first, by writing to and closing its
+/// stdin and optionally sending a signal, and only killed if it has not
+/// exited after the grace period.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Shutdown {
+    /// Written to stdin before it is closed, e.g. `q` to make ffmpeg finalize its output
+    pub stdin: Vec<u8>,
+    
+    /// Signal sent after closing stdin (Unix only)
+    pub signal: Option<i32>,
+    
+    /// How long the process may take to exit before it is killed
+    pub grace: Duration,
+}
+
+impl Shutdown {
+    /// Close stdin and kill the process if it is still running after `grace`
+    pub fn new(grace: Duration) -> Self {
+        Shutdown {
+            grace,
+            ..Shutdown::default()
+        }
+    }
+    
+    /// Write `data` to stdin before closing it
+    pub fn stdin(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin = data.into();
+        self
+    }
+    
+    /// Send `signal` after closing stdin
+    pub fn signal(mut self, signal: i32) -> Self {
+        self.signal = Some(signal);
+        self
+    }
+}
+
+/// Configuration for spawning a process
+#[derive(Debug, Clone)]
+pub struct ProcessBuilder {
+    /// Program to run
+    program: String,
+    
+    /// Arguments passed to the program
+    args: Vec<String>,
+    
+    /// Environment variables set, or removed with `None`, in order
+    env: Vec<(String, Option<String>)>,
+    
+    /// Whether the inherited environment is cleared first
+    env_clear: bool,
+    
+    /// Working directory of the process, the current one if unset
+    current_dir: Option<PathBuf>,
+    
+    /// Where stdout goes
+    stdout: OutputSink,
+    
+    /// Where stderr goes
+    stderr: OutputSink,
+    
+    /// Where stdin is read from
+    stdin: StdinSource,
+    
+    /// Number of output events to keep, 0 to disable the event log
+    event_capacity: usize,
+    
+    /// Window size of the pseudo-terminal the process runs on, if any
+    pty: Option<PtySize>,
+    
+    /// Resource limits applied to the process
+    limits: Vec<(Resource, Limit)>,
+    
+    /// Scheduling settings applied to the process
+    priority: Priority,
+    
+    /// cgroup the process is placed in, if any
+    cgroup: Option<CgroupConfig>,
+    
+    /// Namespaces the process is isolated in, if any
+    sandbox: Option<SandboxConfig>,
+    
+    /// Whether the process leads a new process group
+    process_group: bool,
+    
+    /// Stall detection for the process, if any
+    watchdog: Option<Watchdog>,
+    
+    /// Time after which the process is killed, if any
+    timeout: Option<Duration>,
+    
+    /// How `close` stops the process, if not by killing it right away
+    shutdown: Option<Shutdown>,
+    
+    /// Backend starting the process instead of the default one, if any
+    backend: Option<Arc<dyn ProcessBackend>>,
+}
+
+impl ProcessBuilder {
+    /// Create a builder for the given program
+    pub fn new(program: &str) -> Self {
+        ProcessBuilder {
+            program: program.to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+            env_clear: false,
+            current_dir: None,
+            stdout: OutputSink::Null,
+            stderr: OutputSink::Capture,
+            stdin: StdinSource::Piped,
+            event_capacity: 0,
+            pty: None,
+            limits: Vec::new(),
+            priority: Priority::default(),
+            cgroup: None,
+            sandbox: None,
+            process_group: false,
+            watchdog: None,
+            timeout: None,
+            shutdown: None,
+            backend: None,
+        }
+    }
+    
+    /// Program the process runs
+    pub fn get_program(&self) -> &str {
+        &self.program
+    }
+    
+    /// Arguments passed to the program
+    pub fn get_args(&self) -> &[String] {
+        &self.args
+    }
+    
+    /// Add an argument
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        self.args.push(arg.to_string());
+        self
+    }
+    
+    /// Add several arguments
+    pub fn args(&mut self, args: &[&str]) -> &mut Self {
+        self.args.extend(args.iter().map(|arg| arg.to_string()));
+        self
+    }
+    
+    /// Set an environment variable of the process
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.env.push((key.to_string(), Some(value.to_string())));
+        self
+    }
+    
+    /// Remove an inherited or earlier set environment variable
+    pub fn env_remove(&mut self, key: &str) -> &mut Self {
+        self.env.push((key.to_string(), None));
+        self
+    }
+    
+    /// Start from an empty environment instead of inheriting this process's
+    ///
+    /// Variables set with `env` are still passed, whether set before or after.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env_clear = true;
+        self.env.retain(|(_, value)| value.is_some());
+        self
+    }
+    
+    /// Environment variables set, or removed with `None`, in the order given
+    pub fn get_envs(&self) -> &[(String, Option<String>)] {
+        &self.env
+    }
+    
+    /// Run the process in `dir` instead of the current working directory
+    ///
+    /// A relative program path is resolved by the operating system, which
+    /// differs between platforms; use an absolute path to be safe.
+    pub fn current_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+    
+    /// Working directory of the process, if set
+    pub fn get_current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+    
+    /// Capture stdout for `read_stdout` instead of discarding it
+    pub fn capture_stdout(&mut self, capture: bool) -> &mut Self {
+        self.stdout = if capture { OutputSink::Capture } else { OutputSink::Null };
+        self
+    }
+    
+    /// Set where stdout goes, discarded by default
+    pub fn stdout(&mut self, sink: OutputSink) -> &mut Self {
+        self.stdout = sink;
+        self
+    }
+    
+    /// Set where stderr goes, captured for `read_stderr` by default
+    pub fn stderr(&mut self, sink: OutputSink) -> &mut Self {
+        self.stderr = sink;
+        self
+    }
+    
+    /// Set where stdin is read from
+    ///
+    /// `write_stdin` fails with `StdinNotPiped` unless the source is `StdinSource::Piped`.
+    pub fn stdin(&mut self, source: StdinSource) -> &mut Self {
+        self.stdin = source;
+        self
+    }
+    
+    /// Keep a timestamped log of the last `capacity` output chunks and stdin writes
+    ///
+    /// The log interleaves stdout and stderr in arrival order; 0 disables it.
+    pub fn record_events(&mut self, capacity: usize) -> &mut Self {
+        self.event_capacity = capacity;
+        self
+    }
+    
+    /// Run the process on a pseudo-terminal of the given size (Unix only)
+    ///
+    /// The terminal replaces stdin, stdout and stderr: `write_stdin` writes to
+    /// it and everything the process prints is read with `read_stdout`,
+    /// following the stdout sink. The stdin source and stderr sink are ignored.
+    pub fn pty(&mut self, size: PtySize) -> &mut Self {
+        self.pty = Some(size);
+        self
+    }
+    
+    /// Limit a resource of the process (Unix only)
+    ///
+    /// Applied with `setrlimit` right before the program is executed. Use
+    /// `limits::UNLIMITED` for no limit; setting a resource again replaces
+    /// its earlier limit. Terminations caused by a limit are reported by
+    /// `Process::exit_info`.
+    pub fn limit(&mut self, resource: Resource, soft: u64, hard: u64) -> &mut Self {
+        self.limits.retain(|(limited, _)| *limited != resource);
+        self.limits.push((resource, Limit { soft, hard }));
+        self
+    }
+    
+    /// Set the niceness, from -20 (highest priority) to 19 (Unix only)
+    pub fn nice(&mut self, nice: i32) -> &mut Self {
+        self.priority.nice = Some(nice);
+        self
+    }
+    
+    /// Set the CPU scheduling policy (Linux only)
+    pub fn sched_policy(&mut self, policy: SchedPolicy) -> &mut Self {
+        self.priority.policy = Some(policy);
+        self
+    }
+    
+    /// Set the I/O scheduling class (Linux only)
+    pub fn io_priority(&mut self, io: IoPriority) -> &mut Self {
+        self.priority.io = Some(io);
+        self
+    }
+    
+    /// Restrict the process to the CPUs with the given indices (Linux only)
+    pub fn cpu_affinity(&mut self, cpus: &[usize]) -> &mut Self {
+        self.priority.cpus = Some(cpus.to_vec());
+        self
+    }
+    
+    /// Run the process in its own cgroup v2, created under the configured parent (Linux only)
+    ///
+    /// The child moves itself into the cgroup before executing the program,
+    /// so its descendants are accounted and limited too. The cgroup is
+    /// removed by `Process::close`, killing anything still running in it.
+    pub fn cgroup(&mut self, config: CgroupConfig) -> &mut Self {
+        self.cgroup = Some(config);
+        self
+    }
+    
+    /// Run the process in new user, mount, PID and network namespaces (Linux only)
+    ///
+    /// Only the configured host paths are visible, see `SandboxConfig`. The
+    /// spawned process forwards signals to the program and exits like it,
+    /// so its ID, the exit info and signalling work as usual; the metrics
+    /// sampler reaches the program only with a process group. Fails to spawn
+    /// where unprivileged user namespaces are disabled.
+    pub fn sandbox(&mut self, config: SandboxConfig) -> &mut Self {
+        self.sandbox = Some(config);
+        self
+    }
+    
+    /// Start the process in a new process group it leads (Unix only)
+    ///
+    /// Descendants stay in the group unless they move themselves, so the
+    /// metrics sampler can account for them and `Process::close` terminates
+    /// all of them, as long as the process has not been reaped by `wait`.
+    /// A process in PTY mode always leads its own group.
+    pub fn process_group(&mut self, enable: bool) -> &mut Self {
+        self.process_group = enable;
+        self
+    }
+    
+    /// Watch the process for stalls, i.e. periods without output or consumed stdin
+    ///
+    /// Output written directly to a file can not be observed, so with a
+    /// watchdog file sinks are always drained by the library. Stalls are
+    /// reported by `Process::is_stalled` and in the exit info.
+    pub fn watchdog(&mut self, watchdog: Watchdog) -> &mut Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+    
+    /// Kill the process once it has run for `timeout`
+    ///
+    /// Such a termination is reported as `TerminationReason::TimedOut` by
+    /// the exit info. Not enforced for processes of other backends.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    
+    /// Let `Process::close` ask the process to finish before killing it
+    pub fn shutdown(&mut self, shutdown: Shutdown) -> &mut Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+    
+    /// Start the process with `backend` instead of the default backend
+    ///
+    /// See `backend::set_default`; without either the process is started
+    /// on the operating system.
+    pub fn backend(&mut self, backend: Arc<dyn ProcessBackend>) -> &mut Self {
+        self.backend = Some(backend);
+        self
+    }
+    
+    /// Spawn the process
+    pub fn spawn(&self) -> Result<Process> {
+        match self.backend.clone().or_else(backend::default_backend) {
+            Some(backend) => backend.spawn(self),
+            None => self.spawn_connected(None, None),
+        }
+    }
+    
+    /// Spawn the process on the operating system, with stdin or stdout connected to the given streams instead
+    ///
+    /// Used to chain processes, which always bypasses backends; not available in PTY mode.
+    pub(crate) fn spawn_connected(&self, stdin: Option<Stdio>, stdout: Option<Stdio>) -> Result<Process> {
+        let result = self.start(stdin, stdout);
+        match &result {
+            Ok(process) => info!(
+                "Spawned {} as process {}",
+                command_line(&self.program, &self.args),
+                process.id().unwrap_or(0)
+            ),
+            Err(err) => warn!("Failed to spawn {}: {}", command_line(&self.program, &self.args), err),
+        }
+        result
+    }
+    
+    fn start(&self, stdin: Option<Stdio>, stdout: Option<Stdio>) -> Result<Process> {
+        if self.program.is_empty() || (self.pty.is_some() && (stdin.is_some() || stdout.is_some())) {
+            return Err(ProcessError::InvalidState);
+        }
+        
+        #[cfg(not(unix))]
+        if self.pty.is_some() {
+            return Err(ProcessError::Unsupported("pseudo-terminal mode"));
+        }
+        
+        #[cfg(not(unix))]
+        if !self.limits.is_empty() {
+            return Err(ProcessError::Unsupported("resource limits"));
+        }
+        
+        #[cfg(not(target_os = "linux"))]
+        if self.cgroup.is_some() {
+            return Err(ProcessError::Unsupported("cgroups"));
+        }
+        
+        #[cfg(not(target_os = "linux"))]
+        if self.sandbox.is_some() {
+            return Err(ProcessError::Unsupported("sandboxing"));
+        }
+        
+        #[cfg(not(unix))]
+        if matches!(&self.watchdog, Some(Watchdog { action: crate::watchdog::WatchdogAction::Signal(_), .. })) {
+            return Err(ProcessError::Unsupported("watchdog signals"));
+        }
+        
+        #[cfg(not(unix))]
+        if matches!(&self.shutdown, Some(Shutdown { signal: Some(_), .. })) {
+            return Err(ProcessError::Unsupported("shutdown signals"));
+        }
+        
+        #[cfg(not(unix))]
+        if self.process_group {
+            return Err(ProcessError::Unsupported("process groups"));
+        }
+        
+        self.priority.check_supported()?;
+        
+        // Create the command
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if self.env_clear {
+            command.env_clear();
+        }
+        for (key, value) in &self.env {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        
+        // Joining the cgroup comes first, so the remaining setup is accounted to it
+        #[cfg(target_os = "linux")]
+        let cgroup = match &self.cgroup {
+            Some(config) => {
+                let cgroup = Cgroup::create(config)?;
+                cgroup.attach(&mut command)?;
+                Some(cgroup)
+            }
+            None => None,
+        };
+        if !self.limits.is_empty() {
+            apply_limits(&mut command, &self.limits);
+        }
+        if !self.priority.is_empty() {
+            apply_priority(&mut command, &self.priority);
+        }
+        
+        // In PTY mode the child becomes a session and group leader anyway
+        #[cfg(unix)]
+        if self.process_group && self.pty.is_none() {
+            command.process_group(0);
+        }
+        
+        // Create buffers for the captured output
+        let stdout_buffer = Arc::new(Mutex::new(Vec::new()));
+        let stderr_buffer = Arc::new(Mutex::new(Vec::new()));
+        let hooks = OutputHooks {
+            events: (self.event_capacity > 0).then(|| Arc::new(EventLog::new(Instant::now(), self.event_capacity))),
+            watch: Arc::new(OutputWatch::default()),
+            activity: self.watchdog.as_ref().map(|_| Arc::new(Activity::new(Instant::now()))),
+        };
+        
+        // Connect the standard streams, either to a pseudo-terminal or individually
+        #[cfg(unix)]
+        let mut pty_master = None;
+        let mut stdout_target = None;
+        let mut stderr_target = None;
+        
+        #[cfg(unix)]
+        if let Some(size) = self.pty {
+            let pty = pty::open(size)?;
+            command
+                .stdin(Stdio::from(pty.slave.try_clone()?))
+                .stdout(Stdio::from(pty.slave.try_clone()?))
+                .stderr(Stdio::from(pty.slave));
+            pty::set_controlling_terminal(&mut command);
+            
+            stdout_target = Some(prepare_pty_output(&self.stdout, &stdout_buffer, &hooks)?);
+            pty_master = Some(pty.master);
+        }
+        
+        let stdin_connected = stdin.is_some();
+        if self.pty.is_none() {
+            let stdin_stdio = match (stdin, &self.stdin) {
+                (Some(stdio), _) => stdio,
+                (None, StdinSource::Piped | StdinSource::Bytes(_)) => Stdio::piped(),
+                (None, StdinSource::File(path)) => Stdio::from(File::open(path)?),
+                (None, StdinSource::Null) => Stdio::null(),
+            };
+            let (stdout_stdio, target) = match stdout {
+                Some(stdio) => (stdio, None),
+                None => prepare_output(&self.stdout, StreamId::Stdout, &stdout_buffer, &hooks)?,
+            };
+            stdout_target = target;
+            let (stderr_stdio, target) = prepare_output(&self.stderr, StreamId::Stderr, &stderr_buffer, &hooks)?;
+            stderr_target = target;
+            
+            command.stdin(stdin_stdio).stdout(stdout_stdio).stderr(stderr_stdio);
+        }
+        
+        // On Windows, hide the console window
+        #[cfg(windows)]
+        {
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        
+        // Entering the sandbox forks, so it comes after all other setup
+        #[cfg(target_os = "linux")]
+        if let Some(config) = &self.sandbox {
+            Sandbox::prepare(config, &self.program, self.current_dir.as_deref())?.attach(&mut command);
+        }
+        
+        // Spawn the process, then drop the command so our copies of the child's ends are closed
+        let started = Instant::now();
+        let mut child = command.spawn()?;
+        drop(command);
+        
+        // Take ownership of the I/O handles; a buffer source is fed from its own thread
+        let mut stdin: Option<Box<dyn Write + Send>> = None;
+        if let Some(pipe) = child.stdin.take() {
+            match &self.stdin {
+                StdinSource::Bytes(bytes) => {
+                    let bytes = Arc::clone(bytes);
+                    let mut pipe = pipe;
+                    // A child exiting before reading everything is not an error
+                    thread::spawn(move || {
+                        let _ = pipe.write_all(&bytes);
+                    });
+                }
+                _ => stdin = Some(Box::new(pipe)),
+            }
+        }
+        
+        // Drain each piped output stream, on the shared reactor where available
+        let mut readers = Vec::new();
+        
+        #[cfg(unix)]
+        if let Some(master) = &pty_master {
+            stdin = Some(Box::new(pty::PtyWriter(master.try_clone()?)));
+            if let Some(target) = stdout_target.take() {
+                readers.push(spawn_reader(master.try_clone()?, target));
+            }
+        }
+        if let (Some(stdout), Some(target)) = (child.stdout.take(), stdout_target) {
+            readers.push(spawn_reader(stdout, target));
+        }
+        if let (Some(stderr), Some(target)) = (child.stderr.take(), stderr_target) {
+            readers.push(spawn_reader(stderr, target));
+        }
+        
+        // Let the reaper notice the exit without polling
+        let exit_watch = reaper::watch(child.id());
+        
+        // Watch for stalls once everything counting as activity is connected
+        let watchdog = match (&self.watchdog, &hooks.activity) {
+            (Some(watchdog), Some(activity)) => match Target::new(child.id()) {
+                Ok(target) => Some(Monitor::start(watchdog.clone(), Arc::clone(activity), target)),
+                Err(err) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(err.into());
+                }
+            },
+            _ => None,
+        };
+        let deadline = match self.timeout {
+            Some(timeout) => match Target::new(child.id()) {
+                Ok(target) => Some(Deadline::start(started + timeout, target)),
+                Err(err) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(err.into());
+                }
+            },
+            None => None,
+        };
+        
+        Ok(Process {
+            process: Some(child),
+            backend_child: None,
+            stdin: stdin.map(|stdin| Arc::new(Mutex::new(stdin))),
+            stdin_piped: self.pty.is_some() || (!stdin_connected && matches!(self.stdin, StdinSource::Piped)),
+            #[cfg(unix)]
+            pty_master,
+            stdout_buffer,
+            stderr_buffer,
+            readers,
+            events: hooks.events,
+            watch: hooks.watch,
+            limits: self.limits.clone(),
+            #[cfg(target_os = "linux")]
+            cgroup,
+            final_cgroup_stats: None,
+            #[cfg(unix)]
+            process_group: self.process_group || self.pty.is_some(),
+            #[cfg(target_os = "linux")]
+            sampler: None,
+            activity: hooks.activity,
+            watchdog,
+            deadline,
+            shutdown: self.shutdown.clone(),
+            killed: false,
+            exit_watch,
+            exit_status: None,
+            started,
+            usage: None,
+        })
+    }
+}
+
 /// Internal representation of a process
 pub struct Process {
     /// The child process handle
     process: Option<Child>,
     
-    /// Handle to the process's stdin
-    stdin: Option<Arc<Mutex<std::process::ChildStdin>>>,
+    /// The child started by a backend instead of the operating system
+    backend_child: Option<Box<dyn BackendChild>>,
+    
+    /// Handle to the process's stdin, or to the pseudo-terminal in PTY mode
+    stdin: Option<Arc<Mutex<Box<dyn Write + Send>>>>,
+    
+    /// Whether stdin was spawned as a pipe for `write_stdin`
+    stdin_piped: bool,
+    
+    /// Controlling end of the pseudo-terminal in PTY mode
+    #[cfg(unix)]
+    pty_master: Option<File>,
+    
+    /// Buffer for stdout output, filled only when stdout capture is enabled
+    stdout_buffer: Arc<Mutex<Vec<u8>>>,
     
     /// Buffer for stderr output
     stderr_buffer: Arc<Mutex<Vec<u8>>>,
     
-    /// Exit code if the process has finished
-    exit_code: Option<i32>,
+    /// Completion of draining each captured output stream
+    readers: Vec<Arc<Drained>>,
+    
+    /// Timestamped log of output chunks and stdin writes, if enabled
+    events: Option<Arc<EventLog>>,
+    
+    /// Output not yet consumed by `wait_for_output`
+    watch: Arc<OutputWatch>,
+    
+    /// Resource limits the process runs under
+    limits: Vec<(Resource, Limit)>,
+    
+    /// cgroup the process runs in, until it is closed
+    #[cfg(target_os = "linux")]
+    cgroup: Option<Cgroup>,
+    
+    /// Usage accounted to the cgroup when it was removed
+    final_cgroup_stats: Option<CgroupStats>,
+    
+    /// Whether the process was killed by `close`
+    killed: bool,
+    
+    /// Whether the process leads its own process group
+    #[cfg(unix)]
+    process_group: bool,
+    
+    /// Background sampler of the process's /proc metrics, if started
+    #[cfg(target_os = "linux")]
+    sampler: Option<Sampler>,
+    
+    /// Time of the last output or consumed stdin, with a watchdog
+    activity: Option<Arc<Activity>>,
+    
+    /// Watchdog thread detecting stalls, if enabled
+    watchdog: Option<Monitor>,
+    
+    /// Thread killing the process once its timeout expires, if set
+    deadline: Option<Deadline>,
+    
+    /// How `close` stops the process, if not by killing it right away
+    shutdown: Option<Shutdown>,
+    
+    /// Exit noticed by the reaper, if it watches the process
+    exit_watch: Option<Arc<ExitWatch>>,
+    
+    /// Exit status if the process has finished
+    exit_status: Option<ExitStatus>,
+    
+    /// Time the process was spawned
+    started: Instant,
+    
+    /// Resources used by the process, once it has been reaped
+    usage: Option<ResourceUsage>,
 }
 
 impl Process {
@@ -58,60 +817,105 @@ impl Process {
     
     /// Start a new process with the given program path and arguments
     pub fn new_with_args(program: &str, args: &[&str]) -> Result<Self> {
-        if program.is_empty() {
-            return Err(ProcessError::InvalidState);
-        }
-        
-        // Create the command
-        let mut command = Command::new(program);
-        
-        // Configure the command
-        command
-            .args(args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null()) // We don't care about stdout by default
-            .stderr(Stdio::piped());
-        
-        // On Windows, hide the console window
-        #[cfg(windows)]
-        {
-            const CREATE_NO_WINDOW: u32 = 0x08000000;
-            command.creation_flags(CREATE_NO_WINDOW);
+        ProcessBuilder::new(program).args(args).spawn()
+    }
+    
+    /// Wrap a child started by a backend, connecting its streams as `builder` configures
+    ///
+    /// Output and stdin are handled like for a real process. Settings that
+    /// need an operating system process, such as limits, priorities,
+    /// cgroups, sandboxing, process groups and the watchdog, have no effect, and PTY
+    /// mode is not supported.
+    pub fn from_spawned(builder: &ProcessBuilder, spawned: Spawned) -> Result<Self> {
+        let Spawned { mut child, stdin, stdout, stderr } = spawned;
+        if builder.pty.is_some() {
+            let _ = child.kill();
+            return Err(ProcessError::Unsupported("pseudo-terminal mode with a backend"));
         }
         
-        // Spawn the process
-        let mut child = command.spawn()?;
-        
-        // Take ownership of the I/O handles
-        let stdin = child.stdin.take().map(|stdin| Arc::new(Mutex::new(stdin)));
-        let stderr = child.stderr.take();
-        
-        // Create a buffer for stderr output
+        let stdout_buffer = Arc::new(Mutex::new(Vec::new()));
         let stderr_buffer = Arc::new(Mutex::new(Vec::new()));
+        let hooks = OutputHooks {
+            events: (builder.event_capacity > 0).then(|| Arc::new(EventLog::new(Instant::now(), builder.event_capacity))),
+            watch: Arc::new(OutputWatch::default()),
+            activity: None,
+        };
         
-        // If we have a stderr handle, spawn a thread to read from it
-        if let Some(mut stderr) = stderr {
-            let buffer = Arc::clone(&stderr_buffer);
-            thread::spawn(move || {
-                let mut buf = [0u8; 1024];
-                loop {
-                    match stderr.read(&mut buf) {
-                        Ok(0) => break, // EOF
-                        Ok(n) => {
-                            let mut buffer = buffer.lock().unwrap();
-                            buffer.extend_from_slice(&buf[..n]);
-                        }
-                        Err(_) => break,
-                    }
+        // Both streams are always drained, so the child never blocks on a full pipe
+        let mut readers = Vec::new();
+        for (stream, sink, id, buffer) in [
+            (stdout, &builder.stdout, StreamId::Stdout, &stdout_buffer),
+            (stderr, &builder.stderr, StreamId::Stderr, &stderr_buffer),
+        ] {
+            match prepare_drained_output(sink, id, buffer, &hooks)? {
+                Some(target) => {
+                    let drained = Arc::new(Drained::default());
+                    spawn_reader_thread(stream, target, Arc::clone(&drained));
+                    readers.push(drained);
                 }
-            });
+                None => {
+                    let mut stream = stream;
+                    thread::spawn(move || io::copy(&mut stream, &mut io::sink()));
+                }
+            }
         }
         
+        let stdin = match &builder.stdin {
+            StdinSource::Piped => Some(stdin),
+            StdinSource::Bytes(bytes) => {
+                let bytes = Arc::clone(bytes);
+                let mut stdin = stdin;
+                thread::spawn(move || {
+                    let _ = stdin.write_all(&bytes);
+                });
+                None
+            }
+            StdinSource::File(path) => {
+                let mut file = File::open(path)?;
+                let mut stdin = stdin;
+                thread::spawn(move || {
+                    let _ = io::copy(&mut file, &mut stdin);
+                });
+                None
+            }
+            StdinSource::Null => None,
+        };
+        
+        info!(
+            "Spawned {} as process {} of a backend",
+            command_line(&builder.program, &builder.args),
+            child.id()
+        );
+        
         Ok(Process {
-            process: Some(child),
-            stdin,
+            process: None,
+            backend_child: Some(child),
+            stdin: stdin.map(|stdin| Arc::new(Mutex::new(stdin))),
+            stdin_piped: matches!(builder.stdin, StdinSource::Piped),
+            #[cfg(unix)]
+            pty_master: None,
+            stdout_buffer,
             stderr_buffer,
-            exit_code: None,
+            readers,
+            events: hooks.events,
+            watch: hooks.watch,
+            limits: Vec::new(),
+            #[cfg(target_os = "linux")]
+            cgroup: None,
+            final_cgroup_stats: None,
+            #[cfg(unix)]
+            process_group: false,
+            #[cfg(target_os = "linux")]
+            sampler: None,
+            activity: None,
+            watchdog: None,
+            deadline: None,
+            shutdown: builder.shutdown.clone(),
+            killed: false,
+            exit_watch: None,
+            exit_status: None,
+            started: Instant::now(),
+            usage: None,
         })
     }
     
@@ -119,80 +923,414 @@ impl Process {
     pub fn write_stdin(&mut self, data: &[u8]) -> Result<usize> {
         if let Some(stdin) = &self.stdin {
             let mut stdin = stdin.lock().unwrap();
-            stdin.write(data).map_err(ProcessError::Io)
+            let written = stdin.write(data).inspect_err(|err| {
+                warn!("Writing to stdin of process {} failed: {}", self.id().unwrap_or(0), err);
+            })?;
+            if let Some(events) = &self.events {
+                events.record(StreamId::Stdin, &data[..written]);
+            }
+            if let Some(activity) = &self.activity {
+                activity.touch();
+            }
+            Ok(written)
+        } else if !self.stdin_piped {
+            Err(ProcessError::StdinNotPiped)
         } else {
             Err(ProcessError::InvalidState)
         }
     }
     
-    /// Read data from the stderr buffer
-    pub fn read_stderr(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let mut stderr_buffer = self.stderr_buffer.lock().unwrap();
+    /// Close the process's stdin, signalling EOF without terminating the process
+    ///
+    /// In PTY mode this sends the terminal's end-of-file character instead.
+    pub fn close_stdin(&mut self) {
+        #[cfg(unix)]
+        if self.pty_master.is_some() {
+            if let Some(stdin) = &self.stdin {
+                let _ = stdin.lock().unwrap().write_all(b"\x04");
+            }
+        }
+        self.stdin = None;
+    }
+    
+    /// Change the window size of the pseudo-terminal in PTY mode
+    pub fn resize_pty(&mut self, size: PtySize) -> Result<()> {
+        #[cfg(unix)]
+        if let Some(master) = &self.pty_master {
+            return Ok(pty::resize(master, size)?);
+        }
+        
+        let _ = size;
+        Err(ProcessError::InvalidState)
+    }
+    
+    /// Operating system identifier of the process, `None` once it has been closed
+    pub fn id(&self) -> Option<u32> {
+        if let Some(child) = &self.backend_child {
+            return Some(child.id());
+        }
+        self.process.as_ref().map(|process| process.id())
+    }
+    
+    /// Change the scheduling settings of the running process and all its threads
+    ///
+    /// Settings left as `None` are not changed. Raising the priority above
+    /// the current one usually requires privileges.
+    pub fn set_priority(&mut self, priority: &Priority) -> Result<()> {
+        if !self.is_running() {
+            return Err(ProcessError::ProcessFinished);
+        }
         
-        let bytes_to_read = std::cmp::min(buf.len(), stderr_buffer.len());
-        if bytes_to_read == 0 {
-            return Ok(0);
+        match &self.process {
+            Some(process) => set_priority(process.id(), priority),
+            None => Err(ProcessError::InvalidState),
+        }
+    }
+    
+    /// Directory of the process's cgroup, until the process is closed
+    pub fn cgroup_path(&self) -> Option<&Path> {
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = &self.cgroup {
+            return Some(cgroup.path());
         }
         
-        buf[..bytes_to_read].copy_from_slice(&stderr_buffer[..bytes_to_read]);
-        stderr_buffer.drain(..bytes_to_read);
+        None
+    }
+    
+    /// Get the memory and CPU usage accounted to the process's cgroup
+    ///
+    /// Covers the process and all its descendants. After `close` the usage
+    /// at the time the cgroup was removed is returned.
+    pub fn cgroup_stats(&self) -> Result<CgroupStats> {
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = &self.cgroup {
+            return Ok(cgroup.stats()?);
+        }
         
-        Ok(bytes_to_read)
+        self.final_cgroup_stats.clone().ok_or(ProcessError::InvalidState)
+    }
+    
+    /// Read data from the stderr buffer
+    pub fn read_stderr(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(drain_into(&self.stderr_buffer, buf))
+    }
+    
+    /// Read data from the stdout buffer
+    ///
+    /// Always returns 0 unless the process was spawned with stdout capture.
+    pub fn read_stdout(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(drain_into(&self.stdout_buffer, buf))
+    }
+    
+    /// Wait until `pattern` appears in the captured stdout or stderr
+    ///
+    /// Output up to the end of the match is consumed, so successive calls
+    /// find successive occurrences; `read_stdout` and `read_stderr` are not
+    /// affected. Only streams drained by the library can be matched, i.e.
+    /// not discarded ones or ones written directly to a file. Fails with
+    /// `Timeout`, or `OutputClosed` once all streams reached EOF without a match.
+    pub fn wait_for_output(&self, pattern: &Pattern, timeout: Duration) -> Result<OutputMatch> {
+        self.watch.wait_for(pattern, timeout)
+    }
+    
+    /// Get the recorded output events, oldest first
+    ///
+    /// Empty unless the process was spawned with `record_events`.
+    pub fn events(&self) -> Vec<OutputEvent> {
+        self.events.as_ref().map(|events| events.snapshot()).unwrap_or_default()
+    }
+    
+    /// Write the recorded output events as JSON Lines, one object per event
+    pub fn write_events_jsonl<W: Write>(&self, mut writer: W) -> Result<()> {
+        for event in self.events() {
+            writeln!(writer, "{}", event.to_json())?;
+        }
+        writer.flush()?;
+        Ok(())
     }
     
     /// Check if the process is still running
     pub fn is_running(&mut self) -> bool {
-        if self.exit_code.is_some() {
+        if self.exit_status.is_some() {
             return false;
         }
         
-        if let Some(process) = &mut self.process {
-            match process.try_wait() {
-                Ok(Some(status)) => {
-                    self.exit_code = status.code();
-                    false
-                }
-                Ok(None) => true,
-                Err(_) => false,
-            }
-        } else {
-            false
+        match self.reap(false) {
+            Ok(Some(_)) => false,
+            Ok(None) => true,
+            Err(_) => false,
         }
     }
     
+    /// Whether the reaper notices the exit of the process, so `reaper::wait_any` need not poll it
+    pub(crate) fn is_watched(&self) -> bool {
+        self.exit_watch.is_some()
+    }
+    
+    /// Exit of the process as noticed by the reaper, `None` if it does not watch the process
+    pub(crate) fn exit_watch(&self) -> Option<Arc<ExitWatch>> {
+        self.exit_watch.clone()
+    }
+    
     /// Wait for the process to exit and return the exit code
     pub fn wait(&mut self) -> Result<i32> {
-        // If we already have an exit code, return it
-        if let Some(exit_code) = self.exit_code {
-            return Ok(exit_code);
+        // If we already have an exit status, return its code
+        if let Some(status) = self.exit_status {
+            return Ok(status.code().unwrap_or(-1));
         }
         
         // If we have a process, wait for it
-        if let Some(process) = &mut self.process {
-            let status = process.wait()?;
-            let exit_code = status.code().unwrap_or(-1);
-            self.exit_code = Some(exit_code);
-            Ok(exit_code)
-        } else {
-            Err(ProcessError::InvalidState)
+        match self.reap(true)? {
+            Some(status) => Ok(status.code().unwrap_or(-1)),
+            None => Err(ProcessError::InvalidState),
+        }
+    }
+    
+    /// Collect the exit status and resource usage once the process has exited
+    ///
+    /// Blocks until then if `block` is set. Fails with `InvalidState` without a process.
+    fn reap(&mut self, block: bool) -> Result<Option<ExitStatus>> {
+        if let Some(child) = &mut self.backend_child {
+            if let Some(status) = if block { Some(child.wait()?) } else { child.try_wait()? } {
+                info!("Process {} of a backend ended with {}", child.id(), status);
+                self.exit_status = Some(status);
+                self.usage = Some(ResourceUsage::wall_only(self.started.elapsed()));
+            }
+            return Ok(self.exit_status);
+        }
+        
+        let Some(process) = &mut self.process else {
+            return Err(ProcessError::InvalidState);
+        };
+        
+        // Stop the watchdog and deadline while the exited process still holds its ID, so they cannot signal another
+        #[cfg(unix)]
+        if self.watchdog.is_some() || self.deadline.is_some() {
+            if !crate::watchdog::wait_exited(process.id(), block)? {
+                return Ok(None);
+            }
+            if let Some(monitor) = &mut self.watchdog {
+                monitor.finish();
+            }
+            if let Some(deadline) = &mut self.deadline {
+                deadline.finish();
+            }
+        }
+        
+        // The reaper knows when the process exited, even if it is reaped much later
+        let elapsed = || {
+            let exited_at = self.exit_watch.as_ref().and_then(|watch| watch.exited_at());
+            exited_at.unwrap_or_else(Instant::now).saturating_duration_since(self.started)
+        };
+        
+        #[cfg(unix)]
+        let reaped = crate::usage::wait4(process.id(), block)?
+            .map(|(status, rusage)| (status, ResourceUsage::from_rusage(&rusage, elapsed())));
+        
+        #[cfg(not(unix))]
+        let reaped = if block { Some(process.wait()?) } else { process.try_wait()? }
+            .map(|status| (status, ResourceUsage::wall_only(elapsed())));
+        
+        if let Some((status, usage)) = reaped {
+            info!("Process {} ended with {}", process.id(), status);
+            self.exit_status = Some(status);
+            self.usage = Some(usage);
+            if let Some(monitor) = &mut self.watchdog {
+                monitor.finish();
+            }
+            if let Some(deadline) = &mut self.deadline {
+                deadline.finish();
+            }
+        }
+        Ok(self.exit_status)
+    }
+    
+    /// Sample the CPU, memory and I/O usage of the process every `interval` (Linux only)
+    ///
+    /// Readings of `/proc` are taken on a background thread until the
+    /// process exits; `metrics` returns the latest one and the average over
+    /// the last `window` samples. In process-group mode the whole group is
+    /// sampled too. Starting again replaces the previous sampler.
+    pub fn start_sampler(&mut self, interval: Duration, window: usize) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            if !self.is_running() {
+                return Err(ProcessError::ProcessFinished);
+            }
+            let pid = self.process.as_ref().ok_or(ProcessError::InvalidState)?.id();
+            self.sampler = Some(Sampler::start(pid, self.process_group, interval, window));
+            Ok(())
+        }
+        
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (interval, window);
+            Err(ProcessError::Unsupported("metrics sampling"))
+        }
+    }
+    
+    /// Whether the watchdog currently considers the process stalled
+    pub fn is_stalled(&self) -> bool {
+        self.watchdog.as_ref().is_some_and(Monitor::is_stalled)
+    }
+    
+    /// The most recent stall detected by the watchdog, if any
+    pub fn last_stall(&self) -> Option<Stall> {
+        self.watchdog.as_ref().and_then(|monitor| monitor.last_stall().0)
+    }
+    
+    /// Get the latest and average sampled metrics, `None` before the first sample
+    ///
+    /// Samples remain available after the process exits.
+    pub fn metrics(&self) -> Option<ProcessMetrics> {
+        #[cfg(target_os = "linux")]
+        if let Some(sampler) = &self.sampler {
+            return sampler.metrics();
+        }
+        
+        None
+    }
+    
+    /// Get the resources used by the process, or `None` while it is still running
+    ///
+    /// Covers the process itself and those of its descendants it waited for.
+    pub fn resource_usage(&mut self) -> Option<ResourceUsage> {
+        if self.is_running() {
+            return None;
+        }
+        
+        self.usage
+    }
+    
+    /// Describe how the process ended, or `None` while it is still running
+    ///
+    /// Distinguishes a normal exit, a signal, and a termination caused by
+    /// one of the builder's resource limits.
+    pub fn exit_info(&mut self) -> Option<ExitInfo> {
+        if self.is_running() {
+            return None;
+        }
+        
+        let (stall, acted) = self.watchdog.as_ref().map(Monitor::last_stall).unwrap_or_default();
+        let expired = self.deadline.as_ref().is_some_and(Deadline::expired);
+        self.exit_status.map(|status| {
+            ExitInfo::new(status, &self.limits, self.killed, self.usage.as_ref())
+                .with_stall(stall, acted)
+                .with_timeout(expired)
+        })
+    }
+    
+    /// Wait for the process to exit and collect all remaining captured output
+    ///
+    /// Closes stdin first and waits until the output streams reach EOF.
+    pub fn wait_with_output(&mut self) -> Result<ProcessOutput> {
+        self.stdin = None;
+        let exit_code = self.wait()?;
+        
+        for reader in self.readers.drain(..) {
+            reader.wait();
+        }
+        
+        Ok(ProcessOutput {
+            exit_code,
+            stdout: std::mem::take(&mut *self.stdout_buffer.lock().unwrap()),
+            stderr: std::mem::take(&mut *self.stderr_buffer.lock().unwrap()),
+        })
+    }
+    
+    /// Ask the process to finish as configured and wait up to the grace period for it
+    fn stop_gracefully(&mut self, shutdown: &Shutdown) {
+        if !shutdown.stdin.is_empty() && self.stdin_piped {
+            let _ = self.write_stdin(&shutdown.stdin);
+        }
+        self.stdin = None;
+        
+        // Not signalled once reaped, when its ID may have been reused
+        #[cfg(unix)]
+        if let (Some(signal), Some(process)) = (shutdown.signal, &self.process) {
+            if self.exit_status.is_none() {
+                let pid = process.id() as libc::pid_t;
+                info!("Sending signal {} to process {} to shut it down", signal, pid);
+                unsafe { libc::kill(if self.process_group { -pid } else { pid }, signal) };
+            }
+        }
+        
+        let deadline = Instant::now() + shutdown.grace;
+        while self.is_running() && Instant::now() < deadline {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
         }
     }
     
     /// Close stdin, terminate the process, and clean up resources
+    ///
+    /// With a `Shutdown` set on the builder, a running process is asked to
+    /// finish and given its grace period before it is killed.
     pub fn close(&mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            if self.is_running() {
+                self.stop_gracefully(&shutdown);
+            }
+        }
+        
         // Drop stdin to close it
         self.stdin = None;
         
+        // In process-group mode the whole group is terminated, as long as the
+        // leader is not reaped and its ID cannot have been reused
+        #[cfg(unix)]
+        if self.process_group && self.exit_status.is_none() {
+            if let Some(process) = &self.process {
+                info!("Killing process group {}", process.id());
+                unsafe { libc::kill(-(process.id() as libc::pid_t), libc::SIGKILL) };
+                self.killed = true;
+            }
+        }
+        
         // If the process is still running, try to terminate it
         if self.is_running() {
-            if let Some(mut process) = self.process.take() {
+            if let Some(process) = &mut self.process {
                 // Try to kill the process first
+                info!("Killing process {}", process.id());
                 let _ = process.kill();
-                let status = process.wait()?;
-                self.exit_code = status.code();
+                self.killed = true;
+            }
+            if let Some(child) = &mut self.backend_child {
+                info!("Killing process {} of a backend", child.id());
+                let _ = child.kill();
+                self.killed = true;
             }
+            self.reap(true)?;
+            self.process = None;
+            self.backend_child = None;
+        }
+        
+        // Keep the final usage, then remove the cgroup with anything left in it
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = self.cgroup.take() {
+            self.final_cgroup_stats = cgroup.stats().ok();
+            cgroup.remove();
+        }
+        
+        #[cfg(target_os = "linux")]
+        if let Some(sampler) = &mut self.sampler {
+            sampler.stop();
         }
         
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+/// Move as many bytes as fit from the front of `buffer` into `buf`
+fn drain_into(buffer: &Mutex<Vec<u8>>, buf: &mut [u8]) -> usize {
+    let mut buffer = buffer.lock().unwrap();
+    
+    let bytes_to_read = std::cmp::min(buf.len(), buffer.len());
+    if bytes_to_read == 0 {
+        return 0;
+    }
+    
+    buf[..bytes_to_read].copy_from_slice(&buffer[..bytes_to_read]);
+    buffer.drain(..bytes_to_read);
+    
+    bytes_to_read
+}
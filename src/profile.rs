@@ -0,0 +1,402 @@
+use crate::output::{FileSink, OutputSink};
+use crate::process::{Process, ProcessBuilder, ProcessError, Result, Shutdown, StdinSource};
+use crate::watchdog::{Watchdog, WatchdogAction};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Keys allowed at the top level of a profile
+const KEYS: &[&str] = &[
+    "program",
+    "args",
+    "vars",
+    "env",
+    "env_clear",
+    "cwd",
+    "process_group",
+    "stdin",
+    "stdout",
+    "stderr",
+    "timeout_ms",
+    "stall_timeout_ms",
+    "stall_action",
+    "shutdown",
+];
+
+/// Keys allowed in the `shutdown` table
+const SHUTDOWN_KEYS: &[&str] = &["stdin", "signal", "grace_ms"];
+
+/// Where a stream of a profile's process is connected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamMode {
+    /// A pipe to the host: `write_stdin` for stdin (`"pipe"`)
+    Pipe,
+
+    /// The null device (`"null"`)
+    Null,
+
+    /// Captured for `read_stdout` / `read_stderr` (`"capture"`)
+    Capture,
+
+    /// A file, truncated for output (`"file:PATH"`)
+    File(String),
+
+    /// A file appended to (`"append:PATH"`)
+    Append(String),
+}
+
+/// A command line and how to run it, kept in a TOML or JSON file
+///
+/// Strings may refer to variables as `${name}`, filled in from the values
+/// given to `builder` or else the profile's own `vars`; `$$` stands for a
+/// literal `$`. A TOML profile for an ffmpeg recording might read:
+///
+/// ```toml
+/// program = "${ffmpeg}"
+/// args = ["-f", "rawvideo", "-s", "${width}x${height}", "-i", "-", "${output}"]
+/// cwd = "${project}/Recordings"
+/// stderr = "file:${project}/Logs/ffmpeg.log"
+/// stall_timeout_ms = 10000
+/// stall_action = "kill"
+///
+/// [vars]
+/// ffmpeg = "ffmpeg"
+///
+/// [env]
+/// AV_LOG_FORCE_NOCOLOR = "1"
+///
+/// [shutdown]
+/// stdin = "q"
+/// grace_ms = 5000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// Program to run
+    pub program: String,
+
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+
+    /// Default values of variables
+    pub vars: BTreeMap<String, String>,
+
+    /// Environment variables set for the process
+    pub env: BTreeMap<String, String>,
+
+    /// Whether the inherited environment is cleared first
+    pub env_clear: bool,
+
+    /// Working directory of the process, if not the current one
+    pub cwd: Option<String>,
+
+    /// Whether the process leads a new process group
+    pub process_group: bool,
+
+    /// Where stdin is read from, `Pipe` by default
+    pub stdin: StreamMode,
+
+    /// Where stdout goes, `Null` by default
+    pub stdout: StreamMode,
+
+    /// Where stderr goes, `Capture` by default
+    pub stderr: StreamMode,
+
+    /// Time after which the process is killed, if any
+    pub timeout: Option<Duration>,
+
+    /// Stall detection for the process, if any
+    pub stall: Option<(Duration, WatchdogAction)>,
+
+    /// How closing the process stops it, if not by killing it right away
+    pub shutdown: Option<Shutdown>,
+}
+
+impl Profile {
+    /// Load a profile from a file, parsed as JSON for a `.json` extension and as TOML otherwise
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        if json {
+            Profile::from_json(&text)
+        } else {
+            Profile::from_toml(&text)
+        }
+    }
+
+    /// Parse and validate a profile in TOML
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table: toml::Table = text.parse().map_err(|err: toml::de::Error| invalid(err.to_string().trim_end()))?;
+        let value = serde_json::to_value(table).map_err(|err| invalid(&err.to_string()))?;
+        Profile::from_value(&value)
+    }
+
+    /// Parse and validate a profile in JSON
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text).map_err(|err| invalid(&err.to_string()))?;
+        Profile::from_value(&value)
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        let table = value.as_object().ok_or_else(|| invalid("a profile must be a table"))?;
+        check_keys(table, KEYS, "")?;
+
+        let program = string(table, "program")?.ok_or_else(|| invalid("program is missing"))?;
+        if program.is_empty() {
+            return Err(invalid("program is empty"));
+        }
+        let args = match table.get("args") {
+            None => Vec::new(),
+            Some(Value::Array(args)) => args
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string).ok_or_else(|| invalid("args must be strings")))
+                .collect::<Result<_>>()?,
+            Some(_) => return Err(invalid("args must be a list")),
+        };
+
+        let stall = match number(table, "stall_timeout_ms")? {
+            Some(timeout) => {
+                let action = match table.get("stall_action") {
+                    None => WatchdogAction::Notify,
+                    Some(Value::String(action)) if action == "notify" => WatchdogAction::Notify,
+                    Some(Value::String(action)) if action == "kill" => WatchdogAction::Kill,
+                    Some(Value::Number(signal)) => WatchdogAction::Signal(signal_number(signal)?),
+                    Some(_) => return Err(invalid("stall_action must be \"notify\", \"kill\" or a signal number")),
+                };
+                Some((Duration::from_millis(timeout), action))
+            }
+            None if table.contains_key("stall_action") => {
+                return Err(invalid("stall_action needs stall_timeout_ms"));
+            }
+            None => None,
+        };
+
+        let shutdown = match table.get("shutdown") {
+            None => None,
+            Some(Value::Object(shutdown)) => {
+                check_keys(shutdown, SHUTDOWN_KEYS, "shutdown.")?;
+                let grace = number(shutdown, "grace_ms")?.unwrap_or(0);
+                let mut policy = Shutdown::new(Duration::from_millis(grace));
+                if let Some(stdin) = string(shutdown, "stdin")? {
+                    policy = policy.stdin(stdin);
+                }
+                match shutdown.get("signal") {
+                    None => {}
+                    Some(Value::Number(signal)) => policy = policy.signal(signal_number(signal)?),
+                    Some(_) => return Err(invalid("shutdown.signal must be a number")),
+                }
+                Some(policy)
+            }
+            Some(_) => return Err(invalid("shutdown must be a table")),
+        };
+
+        let profile = Profile {
+            program,
+            args,
+            vars: string_table(table, "vars")?,
+            env: string_table(table, "env")?,
+            env_clear: boolean(table, "env_clear")?,
+            cwd: string(table, "cwd")?,
+            process_group: boolean(table, "process_group")?,
+            stdin: stream_mode(table, "stdin", StreamMode::Pipe)?,
+            stdout: stream_mode(table, "stdout", StreamMode::Null)?,
+            stderr: stream_mode(table, "stderr", StreamMode::Capture)?,
+            timeout: number(table, "timeout_ms")?.map(Duration::from_millis),
+            stall,
+            shutdown,
+        };
+
+        if matches!(profile.stdin, StreamMode::Capture | StreamMode::Append(_)) {
+            return Err(invalid("stdin must be \"pipe\", \"null\" or \"file:PATH\""));
+        }
+        if matches!(profile.stdout, StreamMode::Pipe) || matches!(profile.stderr, StreamMode::Pipe) {
+            return Err(invalid("output must be \"capture\", \"null\", \"file:PATH\" or \"append:PATH\""));
+        }
+
+        // Templates are checked now, so a malformed one fails when loading rather than spawning
+        for template in profile.templates() {
+            expand(template, &|_| Some(""))?;
+        }
+        Ok(profile)
+    }
+
+    /// All strings of the profile that may refer to variables
+    fn templates(&self) -> impl Iterator<Item = &str> {
+        let paths = [&self.stdin, &self.stdout, &self.stderr].into_iter().filter_map(|mode| match mode {
+            StreamMode::File(path) | StreamMode::Append(path) => Some(path.as_str()),
+            _ => None,
+        });
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .chain(self.env.values().map(String::as_str))
+            .chain(self.cwd.as_deref())
+            .chain(paths)
+    }
+
+    /// Configure a builder from the profile, filling in `vars` over the profile's defaults
+    ///
+    /// Fails with `InvalidProfile` if a referenced variable has no value.
+    pub fn builder(&self, vars: &HashMap<String, String>) -> Result<ProcessBuilder> {
+        let lookup = |name: &str| vars.get(name).or_else(|| self.vars.get(name)).map(String::as_str);
+        let fill = |template: &str| expand(template, &lookup);
+
+        let mut builder = ProcessBuilder::new(&fill(&self.program)?);
+        for arg in &self.args {
+            builder.arg(&fill(arg)?);
+        }
+        if self.env_clear {
+            builder.env_clear();
+        }
+        for (key, value) in &self.env {
+            builder.env(key, &fill(value)?);
+        }
+        if let Some(cwd) = &self.cwd {
+            builder.current_dir(fill(cwd)?);
+        }
+        builder.process_group(self.process_group);
+
+        builder.stdin(match &self.stdin {
+            StreamMode::Null => StdinSource::Null,
+            StreamMode::File(path) => StdinSource::File(PathBuf::from(fill(path)?)),
+            _ => StdinSource::Piped,
+        });
+        builder.stdout(output_sink(&self.stdout, &fill)?);
+        builder.stderr(output_sink(&self.stderr, &fill)?);
+
+        if let Some(timeout) = self.timeout {
+            builder.timeout(timeout);
+        }
+        if let Some((timeout, action)) = self.stall {
+            builder.watchdog(Watchdog::new(timeout).action(action));
+        }
+        if let Some(shutdown) = &self.shutdown {
+            builder.shutdown(shutdown.clone());
+        }
+        Ok(builder)
+    }
+
+    /// Spawn the process described by the profile, see `builder`
+    pub fn spawn(&self, vars: &HashMap<String, String>) -> Result<Process> {
+        self.builder(vars)?.spawn()
+    }
+}
+
+fn invalid(message: &str) -> ProcessError {
+    ProcessError::InvalidProfile(message.to_string())
+}
+
+fn check_keys(table: &Map<String, Value>, allowed: &[&str], prefix: &str) -> Result<()> {
+    match table.keys().find(|key| !allowed.contains(&key.as_str())) {
+        Some(key) => Err(invalid(&format!("unknown key {}{}", prefix, key))),
+        None => Ok(()),
+    }
+}
+
+fn string(table: &Map<String, Value>, key: &str) -> Result<Option<String>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(invalid(&format!("{} must be a string", key))),
+    }
+}
+
+fn number(table: &Map<String, Value>, key: &str) -> Result<Option<u64>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| invalid(&format!("{} must be a non-negative integer", key))),
+    }
+}
+
+fn boolean(table: &Map<String, Value>, key: &str) -> Result<bool> {
+    match table.get(key) {
+        None => Ok(false),
+        Some(Value::Bool(value)) => Ok(*value),
+        Some(_) => Err(invalid(&format!("{} must be true or false", key))),
+    }
+}
+
+fn string_table(table: &Map<String, Value>, key: &str) -> Result<BTreeMap<String, String>> {
+    let entries = match table.get(key) {
+        None => return Ok(BTreeMap::new()),
+        Some(Value::Object(entries)) => entries,
+        Some(_) => return Err(invalid(&format!("{} must be a table", key))),
+    };
+    entries
+        .iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => Ok((name.clone(), value.clone())),
+            _ => Err(invalid(&format!("{}.{} must be a string", key, name))),
+        })
+        .collect()
+}
+
+fn signal_number(value: &serde_json::Number) -> Result<i32> {
+    value
+        .as_i64()
+        .and_then(|signal| i32::try_from(signal).ok())
+        .filter(|signal| *signal > 0)
+        .ok_or_else(|| invalid("signals must be positive numbers"))
+}
+
+fn stream_mode(table: &Map<String, Value>, key: &str, default: StreamMode) -> Result<StreamMode> {
+    let Some(mode) = string(table, key)? else {
+        return Ok(default);
+    };
+    match mode.split_once(':') {
+        None if mode == "pipe" => Ok(StreamMode::Pipe),
+        None if mode == "null" => Ok(StreamMode::Null),
+        None if mode == "capture" => Ok(StreamMode::Capture),
+        Some(("file", path)) if !path.is_empty() => Ok(StreamMode::File(path.to_string())),
+        Some(("append", path)) if !path.is_empty() => Ok(StreamMode::Append(path.to_string())),
+        _ => Err(invalid(&format!("unknown {} mode {}", key, mode))),
+    }
+}
+
+fn output_sink(mode: &StreamMode, fill: &dyn Fn(&str) -> Result<String>) -> Result<OutputSink> {
+    Ok(match mode {
+        StreamMode::Capture => OutputSink::Capture,
+        StreamMode::File(path) => OutputSink::File(FileSink::new(fill(path)?).append(false)),
+        StreamMode::Append(path) => OutputSink::File(FileSink::new(fill(path)?)),
+        _ => OutputSink::Null,
+    })
+}
+
+/// Replace `${name}` with the value of the variable and `$$` with `$`
+fn expand<'a>(template: &str, lookup: &dyn Fn(&str) -> Option<&'a str>) -> Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let end = match rest.strip_prefix('{') {
+            Some(after) => after.find('}'),
+            None => None,
+        };
+        let Some(end) = end else {
+            return Err(invalid(&format!("malformed variable in {:?}, use ${{name}} or $$", template)));
+        };
+        let name = &rest[1..end + 1];
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid(&format!("bad variable name {:?} in {:?}", name, template)));
+        }
+        let value = lookup(name).ok_or_else(|| invalid(&format!("variable {} is not set", name)))?;
+        expanded.push_str(value);
+        rest = &rest[end + 2..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
@@ -0,0 +1,133 @@
+/// Window size of a pseudo-terminal, in character cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtySize {
+    /// Number of columns
+    pub cols: u16,
+
+    /// Number of rows
+    pub rows: u16,
+}
+
+impl Default for PtySize {
+    fn default() -> Self {
+        PtySize { cols: 80, rows: 24 }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) use self::unix::*;
+
+#[cfg(unix)]
+mod unix {
+    use super::PtySize;
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use std::ptr;
+
+    /// Both ends of a freshly opened pseudo-terminal
+    pub(crate) struct Pty {
+        /// The end kept by the library
+        pub(crate) master: File,
+
+        /// The end handed to the child as its terminal
+        pub(crate) slave: OwnedFd,
+    }
+
+    fn winsize(size: PtySize) -> libc::winsize {
+        libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+
+    /// Open a pseudo-terminal with the given window size
+    pub(crate) fn open(size: PtySize) -> io::Result<Pty> {
+        let mut master = -1;
+        let mut slave = -1;
+        let mut winsize = winsize(size);
+
+        // Apple's openpty takes mutable pointers, which also coerce to the const ones elsewhere
+        let result =
+            unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null_mut(), ptr::addr_of_mut!(winsize)) };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let pty = unsafe {
+            Pty {
+                master: File::from_raw_fd(master),
+                slave: OwnedFd::from_raw_fd(slave),
+            }
+        };
+
+        // Neither end may leak into other children
+        for fd in [pty.master.as_raw_fd(), pty.slave.as_raw_fd()] {
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(pty)
+    }
+
+    /// Change the window size of a pseudo-terminal, delivering SIGWINCH to its session
+    pub(crate) fn resize(master: &File, size: PtySize) -> io::Result<()> {
+        let winsize = winsize(size);
+        if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Writer to the master end that waits while the terminal's input buffer is full
+    ///
+    /// Needed since the master is non-blocking while its output is drained by the reactor.
+    pub(crate) struct PtyWriter(pub(crate) File);
+
+    impl Write for PtyWriter {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            loop {
+                match self.0.write(data) {
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        let mut fd = libc::pollfd {
+                            fd: self.0.as_raw_fd(),
+                            events: libc::POLLOUT,
+                            revents: 0,
+                        };
+                        if unsafe { libc::poll(&mut fd, 1, -1) } == -1 {
+                            let err = io::Error::last_os_error();
+                            if err.kind() != io::ErrorKind::Interrupted {
+                                return Err(err);
+                            }
+                        }
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    /// Make the child a session leader with the pseudo-terminal on stdin as controlling terminal
+    pub(crate) fn set_controlling_terminal(command: &mut Command) {
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::output::{spawn_reader_thread, Drained, StreamTarget};
+use crate::reaper::ExitWatch;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+/// Bytes read from one stream per readiness event, so a busy stream cannot starve the others
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Number of readiness events handled per `epoll_wait`
+const MAX_EVENTS: usize = 64;
+
+/// An output stream drained by the reactor
+struct Stream {
+    /// Non-blocking read end of the stream
+    file: File,
+    target: StreamTarget,
+    drained: Arc<Drained>,
+}
+
+/// A child watched for its exit
+struct Child {
+    /// Becomes readable once the child exits
+    pidfd: OwnedFd,
+    watch: Arc<ExitWatch>,
+}
+
+/// Something the reactor waits on
+enum Source {
+    Stream(Stream),
+    Child(Child),
+}
+
+impl Source {
+    fn fd(&self) -> RawFd {
+        match self {
+            Source::Stream(stream) => stream.file.as_raw_fd(),
+            Source::Child(child) => child.pidfd.as_raw_fd(),
+        }
+    }
+}
+
+/// A single thread draining the output streams of all processes and noticing their exits with epoll
+pub(crate) struct Reactor {
+    epoll: OwnedFd,
+
+    /// Registered sources by their epoll token
+    sources: Mutex<HashMap<u64, Source>>,
+    next_token: AtomicU64,
+}
+
+impl Reactor {
+    /// The reactor shared by all processes, started on first use
+    ///
+    /// `None` if epoll is not available, in which case streams get a thread each.
+    pub(crate) fn get() -> Option<&'static Reactor> {
+        static REACTOR: OnceLock<Option<&'static Reactor>> = OnceLock::new();
+
+        *REACTOR.get_or_init(|| {
+            let reactor: &'static Reactor = Box::leak(Box::new(Reactor::new().ok()?));
+            thread::Builder::new()
+                .name("betahub-reactor".to_string())
+                .spawn(move || reactor.run())
+                .ok()?;
+            Some(reactor)
+        })
+    }
+
+    fn new() -> io::Result<Self> {
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Reactor {
+            epoll: unsafe { OwnedFd::from_raw_fd(epoll) },
+            sources: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(0),
+        })
+    }
+
+    /// Drain `fd` into `target` until EOF, setting `drained` then
+    ///
+    /// The descriptor is switched to non-blocking mode, which also affects
+    /// duplicates of it. Descriptors epoll cannot watch get a thread of their own.
+    pub(crate) fn register(&self, fd: OwnedFd, target: StreamTarget, drained: Arc<Drained>) {
+        let file = File::from(fd);
+        if set_nonblocking(&file).is_err() {
+            spawn_reader_thread(file, target, drained);
+            return;
+        }
+
+        if let Err(source) = self.add(Source::Stream(Stream { file, target, drained })) {
+            if let Source::Stream(stream) = *source {
+                spawn_reader_thread(stream.file, stream.target, stream.drained);
+            }
+        }
+    }
+
+    /// Start watching the child `pid` for its exit, `None` without pidfd support
+    ///
+    /// The child is not reaped, so `pid` cannot have been reused until its owner does.
+    pub(crate) fn watch_exit(&self, pid: u32) -> Option<Arc<ExitWatch>> {
+        let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if pidfd == -1 {
+            return None;
+        }
+
+        let watch = Arc::new(ExitWatch::default());
+        let child = Child {
+            pidfd: unsafe { OwnedFd::from_raw_fd(pidfd as RawFd) },
+            watch: Arc::clone(&watch),
+        };
+        self.add(Source::Child(child)).ok()?;
+        Some(watch)
+    }
+
+    /// Add a source to the epoll set, handing it back if that fails
+    fn add(&self, source: Source) -> Result<(), Box<Source>> {
+        // The lock is taken before adding, so the reactor thread cannot look the source up too early
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let mut sources = self.sources.lock().unwrap();
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: token,
+        };
+        if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_ADD, source.fd(), &mut event) } == -1 {
+            return Err(Box::new(source));
+        }
+        sources.insert(token, source);
+        Ok(())
+    }
+
+    fn run(&self) {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        let mut buf = vec![0u8; READ_CHUNK];
+
+        loop {
+            let ready = unsafe {
+                libc::epoll_wait(self.epoll.as_raw_fd(), events.as_mut_ptr(), MAX_EVENTS as libc::c_int, -1)
+            };
+            if ready == -1 {
+                // Only EINTR is expected; anything else would repeat forever
+                if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                    return;
+                }
+                continue;
+            }
+
+            for event in &events[..ready as usize] {
+                self.service(event.u64, &mut buf);
+            }
+        }
+    }
+
+    /// Read what one stream has available or record a child's exit, and unregister finished sources
+    fn service(&self, token: u64, buf: &mut [u8]) {
+        // Taken out while serviced, since sinks may log to a host callback that spawns processes
+        let Some(mut source) = self.sources.lock().unwrap().remove(&token) else {
+            return;
+        };
+        let finished = match &mut source {
+            Source::Child(_) => true,
+            Source::Stream(stream) => match stream.file.read(buf) {
+                Ok(0) => true,
+                Ok(n) => {
+                    stream.target.write(&buf[..n]);
+                    false
+                }
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => false,
+                Err(err) => {
+                    stream.target.read_failed(&err);
+                    true
+                }
+            },
+        };
+        if !finished {
+            self.sources.lock().unwrap().insert(token, source);
+            return;
+        }
+
+        unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), libc::EPOLL_CTL_DEL, source.fd(), std::ptr::null_mut()) };
+        match source {
+            Source::Stream(mut stream) => {
+                stream.target.finish();
+                stream.drained.set();
+            }
+            Source::Child(child) => child.watch.set(),
+        }
+    }
+}
+
+/// Switch a descriptor to non-blocking mode
+fn set_nonblocking(file: &File) -> io::Result<()> {
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
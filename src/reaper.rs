@@ -0,0 +1,111 @@
+use crate::process::{Process, ProcessError, Result};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often processes the reaper does not watch are checked by `wait_any`
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Counts child exits noticed by the reaper, so waiters can block until the next one
+struct Exits {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+static EXITS: Exits = Exits {
+    generation: Mutex::new(0),
+    changed: Condvar::new(),
+};
+
+/// Exit of one child, recorded by the reaper as soon as the process ends
+///
+/// The child is not reaped by the reaper, so its ID stays reserved until
+/// the owning `Process` collects the exit status.
+#[derive(Default)]
+pub(crate) struct ExitWatch {
+    exited_at: Mutex<Option<Instant>>,
+}
+
+impl ExitWatch {
+    /// Record that the child has exited and wake up waiters
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set(&self) {
+        self.exited_at.lock().unwrap().get_or_insert_with(Instant::now);
+        *EXITS.generation.lock().unwrap() += 1;
+        EXITS.changed.notify_all();
+    }
+
+    /// Time the child was noticed to exit, `None` while it is running
+    pub(crate) fn exited_at(&self) -> Option<Instant> {
+        *self.exited_at.lock().unwrap()
+    }
+
+    /// Wait up to `timeout` for the child to exit, returning whether it has
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        // Checked with the generation locked, so an exit in between is not missed
+        let generation = EXITS.generation.lock().unwrap();
+        let _ = EXITS
+            .changed
+            .wait_timeout_while(generation, timeout, |_| self.exited_at().is_none())
+            .unwrap();
+        self.exited_at().is_some()
+    }
+}
+
+/// Start watching a child for its exit, `None` if exits cannot be watched
+pub(crate) fn watch(pid: u32) -> Option<Arc<ExitWatch>> {
+    #[cfg(target_os = "linux")]
+    {
+        crate::reactor::Reactor::get()?.watch_exit(pid)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Wait until any of `processes` has exited and return its index
+///
+/// Processes that already exited or were closed count as well, the first
+/// one in the slice winning. The exit is collected, so `wait` and
+/// `exit_info` of the returned process return immediately. Exits are
+/// noticed by the reaper where available (pidfds on Linux); other
+/// processes are checked periodically. Fails with `Timeout`, or
+/// `InvalidState` without processes.
+pub fn wait_any(processes: &mut [&mut Process], timeout: Option<Duration>) -> Result<usize> {
+    if processes.is_empty() {
+        return Err(ProcessError::InvalidState);
+    }
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        // Taken before checking, so an exit in between is not missed
+        let generation = EXITS.generation.lock().unwrap();
+        let seen = *generation;
+        drop(generation);
+
+        for (index, process) in processes.iter_mut().enumerate() {
+            if !process.is_running() {
+                return Ok(index);
+            }
+        }
+
+        let mut wait = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::MAX,
+        };
+        if wait.is_zero() {
+            return Err(ProcessError::Timeout);
+        }
+        if processes.iter().any(|process| !process.is_watched()) {
+            wait = wait.min(POLL_INTERVAL);
+        }
+
+        let generation = EXITS.generation.lock().unwrap();
+        let _ = EXITS
+            .changed
+            .wait_timeout_while(generation, wait, |generation| *generation == seen)
+            .unwrap();
+    }
+}
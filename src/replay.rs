@@ -0,0 +1,360 @@
+use crate::output::OutputSink;
+use crate::process::{Process, ProcessBuilder, ProcessError, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Prefix of every file the replay buffer creates in its segment directory
+const FILE_PREFIX: &str = "replay_";
+
+/// Name of the segment list maintained by ffmpeg's segment muxer
+const SEGMENT_LIST: &str = "replay_segments.csv";
+
+/// Name of the concat list handed to ffmpeg when saving a clip
+const CONCAT_LIST: &str = "replay_concat.txt";
+
+/// How long `stop` waits for the recorder to finish before killing it
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configuration of a replay buffer recording
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    /// Path to the ffmpeg executable
+    pub ffmpeg: String,
+
+    /// Input and encoder arguments; the segment output is appended to them
+    pub args: Vec<String>,
+
+    /// Directory holding the rolling segments
+    pub segment_dir: PathBuf,
+
+    /// Seconds of recording kept on disk
+    pub buffer_seconds: u32,
+
+    /// Length of a single segment in seconds
+    pub segment_seconds: u32,
+
+    /// Container format of the segments, passed to `-segment_format`
+    pub segment_format: String,
+
+    /// File extension of the segments
+    pub segment_extension: String,
+}
+
+impl ReplayConfig {
+    /// Create a configuration keeping the last 30 seconds in 2 second MPEG-TS segments
+    pub fn new(ffmpeg: &str, args: &[&str], segment_dir: impl Into<PathBuf>) -> Self {
+        ReplayConfig {
+            ffmpeg: ffmpeg.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            segment_dir: segment_dir.into(),
+            buffer_seconds: 30,
+            segment_seconds: 2,
+            segment_format: "mpegts".to_string(),
+            segment_extension: "ts".to_string(),
+        }
+    }
+}
+
+/// A completed segment as listed by ffmpeg
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// File name of the segment, relative to the segment directory
+    pub file: String,
+
+    /// Start time of the segment in seconds
+    pub start: f64,
+
+    /// End time of the segment in seconds
+    pub end: f64,
+}
+
+impl Segment {
+    /// Length of the segment in seconds
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// Parse a segment list written with `-segment_list_type csv`
+pub fn parse_segment_list(contents: &str) -> Vec<Segment> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            // Split from the right since quoted file names may contain commas
+            let mut fields = line.rsplitn(3, ',');
+            let end = fields.next()?.trim().parse().ok()?;
+            let start = fields.next()?.trim().parse().ok()?;
+            let file = fields.next()?.trim().trim_matches('"').to_string();
+            Some(Segment { file, start, end })
+        })
+        .collect()
+}
+
+/// Select the newest segments covering at least `seconds`, oldest first
+pub fn select_segments(segments: &[Segment], seconds: f64) -> &[Segment] {
+    let mut first = segments.len();
+    let mut total = 0.0;
+    while first > 0 && total < seconds {
+        first -= 1;
+        total += segments[first].duration();
+    }
+    &segments[first..]
+}
+
+/// Records continuously into short segments and keeps only the newest ones,
+/// so that the last few seconds can be saved as a clip on demand
+pub struct ReplayBuffer {
+    /// The recording configuration
+    config: ReplayConfig,
+
+    /// The ffmpeg process writing the segments
+    recorder: Process,
+
+    /// Serializes pruning and clip saving so segments are not removed while in use
+    segments_lock: Arc<Mutex<()>>,
+
+    /// Set when the pruner thread should exit
+    stop_signal: Arc<(Mutex<bool>, Condvar)>,
+
+    /// Thread removing segments that fell out of the buffer
+    pruner: Option<JoinHandle<()>>,
+}
+
+impl ReplayBuffer {
+    /// Start recording with the given configuration
+    pub fn start(config: ReplayConfig) -> Result<Self> {
+        if config.buffer_seconds == 0 || config.segment_seconds == 0 {
+            return Err(ProcessError::InvalidState);
+        }
+
+        fs::create_dir_all(&config.segment_dir)?;
+        remove_replay_files(&config.segment_dir)?;
+
+        let segment_list = path_str(&config.segment_dir.join(SEGMENT_LIST))?;
+        let segment_pattern = path_str(&config.segment_dir.join(format!(
+            "{}%06d.{}",
+            FILE_PREFIX, config.segment_extension
+        )))?;
+        let segment_time = config.segment_seconds.to_string();
+
+        let mut args = vec!["-hide_banner", "-nostats", "-y"];
+        args.extend(config.args.iter().map(String::as_str));
+        args.extend([
+            "-f",
+            "segment",
+            "-segment_time",
+            &segment_time,
+            "-segment_format",
+            &config.segment_format,
+            "-reset_timestamps",
+            "1",
+            "-segment_list",
+            &segment_list,
+            "-segment_list_type",
+            "csv",
+            &segment_pattern,
+        ]);
+
+        // Nothing reads the recorder's stderr, so capturing it would grow without
+        // bound over a long recording
+        let recorder = ProcessBuilder::new(&config.ffmpeg)
+            .args(&args)
+            .stderr(OutputSink::Null)
+            .spawn()?;
+
+        let segments_lock = Arc::new(Mutex::new(()));
+        let stop_signal = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let pruner = {
+            let segments_lock = Arc::clone(&segments_lock);
+            let stop_signal = Arc::clone(&stop_signal);
+            let segment_dir = config.segment_dir.clone();
+            let keep_seconds = f64::from(config.buffer_seconds);
+            let interval = Duration::from_secs(u64::from(config.segment_seconds));
+
+            thread::spawn(move || loop {
+                {
+                    let (stopped, cvar) = &*stop_signal;
+                    let stopped = stopped.lock().unwrap();
+                    let (stopped, _) = cvar
+                        .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                        .unwrap();
+                    if *stopped {
+                        break;
+                    }
+                }
+
+                let _guard = segments_lock.lock().unwrap();
+                let _ = prune_segments(&segment_dir, keep_seconds);
+            })
+        };
+
+        Ok(ReplayBuffer {
+            config,
+            recorder,
+            segments_lock,
+            stop_signal,
+            pruner: Some(pruner),
+        })
+    }
+
+    /// Write data to the recorder's stdin, for configurations reading frames from `pipe:0`
+    pub fn write_stdin(&mut self, data: &[u8]) -> Result<usize> {
+        self.recorder.write_stdin(data)
+    }
+
+    /// Check if the recorder is still running
+    pub fn is_running(&mut self) -> bool {
+        self.recorder.is_running()
+    }
+
+    /// Concatenate the newest segments covering `seconds` into a single clip at `output`
+    ///
+    /// Only completed segments are included, so the clip ends at most one
+    /// segment length before the call.
+    pub fn save_last(&self, seconds: u32, output: &Path) -> Result<()> {
+        let seconds = f64::from(seconds.min(self.config.buffer_seconds));
+        let segment_dir = &self.config.segment_dir;
+
+        let _guard = self.segments_lock.lock().unwrap();
+
+        let segments = read_segment_list(segment_dir)?;
+        let selected: Vec<PathBuf> = select_segments(&segments, seconds)
+            .iter()
+            .map(|segment| segment_dir.join(&segment.file))
+            .filter(|path| path.exists())
+            .collect();
+
+        if selected.is_empty() {
+            return Err(ProcessError::NothingRecorded);
+        }
+
+        let mut concat = String::new();
+        for path in &selected {
+            // The concat demuxer uses shell-like quoting
+            let quoted = path_str(path)?.replace('\'', "'\\''");
+            concat.push_str(&format!("file '{}'\n", quoted));
+        }
+
+        let concat_list = segment_dir.join(CONCAT_LIST);
+        fs::write(&concat_list, concat)?;
+
+        let result = run_to_completion(
+            &self.config.ffmpeg,
+            &[
+                "-hide_banner",
+                "-nostats",
+                "-y",
+                "-f",
+                "concat",
+                "-safe",
+                "0",
+                "-i",
+                &path_str(&concat_list)?,
+                "-c",
+                "copy",
+                &path_str(output)?,
+            ],
+        );
+
+        let _ = fs::remove_file(&concat_list);
+        result
+    }
+
+    /// Stop recording and remove the segments from disk
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        let Some(pruner) = self.pruner.take() else {
+            return Ok(());
+        };
+
+        {
+            let (stopped, cvar) = &*self.stop_signal;
+            *stopped.lock().unwrap() = true;
+            cvar.notify_all();
+        }
+        let _ = pruner.join();
+
+        // Closing stdin lets ffmpeg finish when it reads frames from it
+        self.recorder.close_stdin();
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.recorder.is_running() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        self.recorder.close()?;
+
+        remove_replay_files(&self.config.segment_dir)
+    }
+}
+
+impl Drop for ReplayBuffer {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+/// Run a command to completion, turning a non-zero exit code into an error
+fn run_to_completion(program: &str, args: &[&str]) -> Result<()> {
+    let mut process = Process::new_with_args(program, args)?;
+
+    // Waits for stderr to be drained, so the error message is complete
+    let output = process.wait_with_output();
+    let _ = process.close();
+    let output = output?;
+    if output.exit_code == 0 {
+        return Ok(());
+    }
+
+    Err(ProcessError::CommandFailed {
+        code: output.exit_code,
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+fn read_segment_list(segment_dir: &Path) -> Result<Vec<Segment>> {
+    match fs::read_to_string(segment_dir.join(SEGMENT_LIST)) {
+        Ok(contents) => Ok(parse_segment_list(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove completed segments that are no longer needed to cover `keep_seconds`
+fn prune_segments(segment_dir: &Path, keep_seconds: f64) -> Result<()> {
+    let segments = read_segment_list(segment_dir)?;
+    let keep = select_segments(&segments, keep_seconds).len();
+
+    for segment in &segments[..segments.len() - keep] {
+        match fs::remove_file(segment_dir.join(&segment.file)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove segments and lists left behind by a previous recording
+fn remove_replay_files(segment_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(segment_dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(FILE_PREFIX) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn path_str(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or(ProcessError::InvalidState)
+}
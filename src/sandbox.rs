@@ -0,0 +1,619 @@
+use std::path::PathBuf;
+
+/// Host paths bound read-only by `SandboxConfig::new`, where they exist
+pub const DEFAULT_READ_ONLY: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc"];
+
+/// Isolation of a spawned process in its own Linux namespaces (Linux only)
+///
+/// The process gets new user, mount, PID and network namespaces. Its file
+/// system is an empty read-only root holding the bound host paths, a fresh
+/// `/proc`, private `/tmp` and `/dev/shm`, and the basic devices in `/dev`.
+/// Paths appear at the same location as on the host.
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    /// Host paths bound read-only; those that do not exist are skipped
+    pub read_only: Vec<PathBuf>,
+
+    /// Host paths bound writable; each must exist
+    pub writable: Vec<PathBuf>,
+
+    /// Whether the process keeps the host's network instead of only a loopback interface
+    pub share_network: bool,
+}
+
+impl SandboxConfig {
+    /// Isolate the process with the `DEFAULT_READ_ONLY` paths and no network
+    pub fn new() -> Self {
+        SandboxConfig {
+            read_only: DEFAULT_READ_ONLY.iter().map(PathBuf::from).collect(),
+            ..Self::empty()
+        }
+    }
+
+    /// Isolate the process without any host paths, which must all be added
+    pub fn empty() -> Self {
+        SandboxConfig {
+            read_only: Vec::new(),
+            writable: Vec::new(),
+            share_network: false,
+        }
+    }
+
+    /// Bind an absolute host path read-only
+    pub fn read_only(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_only.push(path.into());
+        self
+    }
+
+    /// Bind an absolute host path writable, e.g. the directory a tool writes its results to
+    pub fn writable(mut self, path: impl Into<PathBuf>) -> Self {
+        self.writable.push(path.into());
+        self
+    }
+
+    /// Keep the host's network
+    pub fn share_network(mut self, share: bool) -> Self {
+        self.share_network = share;
+        self
+    }
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::linux::*;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::SandboxConfig;
+    use std::collections::BTreeSet;
+    use std::ffi::{CStr, CString};
+    use std::fs;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::ptr;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// ID the process runs under inside the sandbox instead of root, so it holds no capabilities there
+    const NOBODY: u32 = 65534;
+
+    /// Devices bound from the host into `/dev`
+    const DEVICES: [&str; 5] = ["/dev/null", "/dev/zero", "/dev/full", "/dev/random", "/dev/urandom"];
+
+    /// Signals passed on to the program by the processes between it and the caller
+    const FORWARDED_SIGNALS: [libc::c_int; 7] = [
+        libc::SIGHUP,
+        libc::SIGINT,
+        libc::SIGQUIT,
+        libc::SIGTERM,
+        libc::SIGUSR1,
+        libc::SIGUSR2,
+        libc::SIGWINCH,
+    ];
+
+    /// Process forwarded signals go to, set in each forwarding process
+    static FORWARD_TO: AtomicI32 = AtomicI32::new(0);
+
+    /// One step of building the file system, with paths below the new root
+    enum Step {
+        Dir(CString),
+        File(CString),
+        Symlink {
+            target: CString,
+            path: CString,
+        },
+        Bind {
+            source: CString,
+            path: CString,
+            /// Flags to remount with for a read-only bind, keeping those of the source
+            read_only: Option<libc::c_ulong>,
+        },
+        Mount {
+            fstype: &'static CStr,
+            path: CString,
+            flags: libc::c_ulong,
+        },
+    }
+
+    /// Everything the child needs to enter the sandbox, prepared before forking
+    ///
+    /// The child forks twice: the first fork becomes init of the new PID
+    /// namespace, the second runs the program. Both parents pass signals on
+    /// and exit like the program, so the spawned process stands in for it.
+    pub(crate) struct Sandbox {
+        /// Flags for `unshare`
+        namespaces: libc::c_int,
+
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+
+        /// Empty host directory the new root is mounted on
+        root: CString,
+
+        steps: Vec<Step>,
+
+        /// Working directory inside the sandbox
+        current_dir: CString,
+    }
+
+    impl Sandbox {
+        /// Plan the sandbox for `program`, which is bound read-only if given as an absolute path outside the bound paths
+        pub(crate) fn prepare(config: &SandboxConfig, program: &str, current_dir: Option<&Path>) -> io::Result<Self> {
+            let uid = unsafe { libc::getuid() };
+            let gid = unsafe { libc::getgid() };
+            let inside = |id: u32| if id == 0 { NOBODY } else { id };
+
+            let root = std::env::temp_dir().join(format!("betahub-sandbox-{}", uid));
+            create_root_dir(&root)?;
+
+            let mut layout = Layout {
+                root,
+                steps: Vec::new(),
+                created: BTreeSet::new(),
+                bound: Vec::new(),
+            };
+
+            layout.mount(Path::new("/proc"), c"proc", libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC)?;
+            layout.mount(Path::new("/tmp"), c"tmpfs", libc::MS_NOSUID | libc::MS_NODEV)?;
+            layout.mount(Path::new("/dev/shm"), c"tmpfs", libc::MS_NOSUID | libc::MS_NODEV)?;
+            for device in DEVICES {
+                layout.bind(Path::new(device), false)?;
+            }
+            for (name, target) in [
+                ("fd", "/proc/self/fd"),
+                ("stdin", "/proc/self/fd/0"),
+                ("stdout", "/proc/self/fd/1"),
+                ("stderr", "/proc/self/fd/2"),
+            ] {
+                layout.symlink(target, &Path::new("/dev").join(name))?;
+            }
+
+            // Parents first, so nested paths are bound on top of them
+            let mut binds: Vec<(&Path, bool)> = config
+                .read_only
+                .iter()
+                .map(|path| (path.as_path(), true))
+                .chain(config.writable.iter().map(|path| (path.as_path(), false)))
+                .collect();
+            binds.sort_by_key(|(path, _)| path.components().count());
+
+            for (path, read_only) in binds {
+                if !path.is_absolute() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("sandbox path {} is not absolute", path.display()),
+                    ));
+                }
+                match layout.bind(path, read_only) {
+                    Err(err) if read_only && err.kind() == io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+            }
+
+            // Such as /bin/sh, which binding again would clash with the link recreated for /bin
+            let program = Path::new(program);
+            if program.is_absolute() && !layout.covers(program) {
+                match layout.bind(program, true) {
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+            }
+
+            Ok(Sandbox {
+                namespaces: libc::CLONE_NEWUSER
+                    | libc::CLONE_NEWNS
+                    | libc::CLONE_NEWPID
+                    | if config.share_network { 0 } else { libc::CLONE_NEWNET },
+                uid_map: format!("{} {} 1", inside(uid), uid).into_bytes(),
+                gid_map: format!("{} {} 1", inside(gid), gid).into_bytes(),
+                root: c_path(&layout.root)?,
+                steps: layout.steps,
+                current_dir: c_path(current_dir.unwrap_or(Path::new("/")))?,
+            })
+        }
+
+        /// Enter the sandbox between fork and exec
+        ///
+        /// Done after any other setup, which the helper processes share.
+        pub(crate) fn attach(self, command: &mut Command) {
+            unsafe {
+                command.pre_exec(move || self.enter());
+            }
+        }
+
+        unsafe fn enter(&self) -> io::Result<()> {
+            check(libc::unshare(self.namespaces))?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // A new PID namespace only applies to children, the first of which is its init
+            let (alive_read, alive_write) = pipe()?;
+            let (status_read, status_write) = pipe()?;
+            let init = check(libc::fork())?;
+            if init != 0 {
+                libc::close(alive_read);
+                libc::close(status_write);
+                let status = relay(init, &mut [alive_write, status_read]);
+
+                // Init reports how the program ended unless it failed before starting it
+                let mut reported: libc::c_int = 0;
+                let size = std::mem::size_of::<libc::c_int>();
+                if libc::read(status_read, ptr::addr_of_mut!(reported).cast(), size) == size as isize {
+                    exit_like(reported);
+                }
+                exit_like(status);
+            }
+
+            // Init; the namespace and everything in it dies with it
+            libc::close(alive_write);
+            libc::close(status_read);
+            check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+            if parent_gone(alive_read) {
+                libc::_exit(1);
+            }
+
+            if self.namespaces & libc::CLONE_NEWNET != 0 {
+                loopback_up()?;
+            }
+            self.build_root()?;
+
+            let program = check(libc::fork())?;
+            if program != 0 {
+                libc::close(alive_read);
+                let status = relay(program, &mut [status_write]);
+                libc::write(status_write, ptr::addr_of!(status).cast(), std::mem::size_of::<libc::c_int>());
+                libc::_exit(0);
+            }
+
+            libc::close(alive_read);
+            libc::close(status_write);
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            Ok(())
+        }
+
+        /// Mount the new root, switch to it and make it read-only
+        unsafe fn build_root(&self) -> io::Result<()> {
+            // Keep the mounts from propagating back to the host
+            check(libc::mount(
+                ptr::null(),
+                c"/".as_ptr(),
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.root.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                c"mode=0755".as_ptr().cast(),
+            ))?;
+
+            for step in &self.steps {
+                step.run()?;
+            }
+
+            // The old root ends up on top of the new one, from where it is detached
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(libc::syscall(libc::SYS_pivot_root, c".".as_ptr(), c".".as_ptr()) as libc::c_int)?;
+            check(libc::umount2(c".".as_ptr(), libc::MNT_DETACH))?;
+            check(libc::mount(
+                ptr::null(),
+                c"/".as_ptr(),
+                ptr::null(),
+                libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+                ptr::null(),
+            ))?;
+            check(libc::chdir(self.current_dir.as_ptr()))?;
+            Ok(())
+        }
+    }
+
+    impl Step {
+        unsafe fn run(&self) -> io::Result<()> {
+            match self {
+                // Nested paths may already exist inside a bound directory
+                Step::Dir(path) => {
+                    if !exists(path) {
+                        check(libc::mkdir(path.as_ptr(), 0o755))?;
+                    }
+                }
+                Step::File(path) => {
+                    if !exists(path) {
+                        let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC, 0o644))?;
+                        libc::close(fd);
+                    }
+                }
+                Step::Symlink { target, path } => {
+                    check(libc::symlink(target.as_ptr(), path.as_ptr()))?;
+                }
+                Step::Bind { source, path, read_only } => {
+                    check(libc::mount(source.as_ptr(), path.as_ptr(), ptr::null(), libc::MS_BIND, ptr::null()))?;
+                    if let Some(flags) = read_only {
+                        check(libc::mount(
+                            ptr::null(),
+                            path.as_ptr(),
+                            ptr::null(),
+                            libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | flags,
+                            ptr::null(),
+                        ))?;
+                    }
+                }
+                Step::Mount { fstype, path, flags } => {
+                    check(libc::mount(fstype.as_ptr(), path.as_ptr(), fstype.as_ptr(), *flags, ptr::null()))?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Steps building the file system below `root`, planned on the host
+    struct Layout {
+        root: PathBuf,
+        steps: Vec<Step>,
+
+        /// Paths already planned to exist
+        created: BTreeSet<PathBuf>,
+
+        /// Resolved host directories bound so far
+        bound: Vec<PathBuf>,
+    }
+
+    impl Layout {
+        fn target(&self, path: &Path) -> io::Result<CString> {
+            c_path(&self.root.join(path.strip_prefix("/").unwrap_or(path)))
+        }
+
+        /// Create `path` and its parents as directories
+        fn dir(&mut self, path: &Path) -> io::Result<()> {
+            if path.parent().is_none() || self.created.contains(path) {
+                return Ok(());
+            }
+            if let Some(parent) = path.parent() {
+                self.dir(parent)?;
+            }
+            self.steps.push(Step::Dir(self.target(path)?));
+            self.created.insert(path.to_path_buf());
+            Ok(())
+        }
+
+        fn mount(&mut self, path: &Path, fstype: &'static CStr, flags: libc::c_ulong) -> io::Result<()> {
+            self.dir(path)?;
+            self.steps.push(Step::Mount {
+                fstype,
+                path: self.target(path)?,
+                flags,
+            });
+            Ok(())
+        }
+
+        fn symlink(&mut self, target: impl AsRef<Path>, path: &Path) -> io::Result<()> {
+            if let Some(parent) = path.parent() {
+                self.dir(parent)?;
+            }
+            self.steps.push(Step::Symlink {
+                target: c_path(target.as_ref())?,
+                path: self.target(path)?,
+            });
+            self.created.insert(path.to_path_buf());
+            Ok(())
+        }
+
+        /// Bind a host path at the same location, recreating it if it is a symlink
+        fn bind(&mut self, source: &Path, read_only: bool) -> io::Result<()> {
+            let metadata = fs::symlink_metadata(source)?;
+
+            // Links such as /bin to usr/bin are recreated rather than followed
+            if metadata.file_type().is_symlink() {
+                return self.symlink(fs::read_link(source)?, source);
+            }
+
+            if metadata.is_dir() {
+                self.dir(source)?;
+                self.bound.push(fs::canonicalize(source)?);
+            } else if !self.created.contains(source) {
+                if let Some(parent) = source.parent() {
+                    self.dir(parent)?;
+                }
+                self.steps.push(Step::File(self.target(source)?));
+                self.created.insert(source.to_path_buf());
+            }
+
+            let read_only = if read_only { Some(locked_flags(source)?) } else { None };
+            self.steps.push(Step::Bind {
+                source: c_path(source)?,
+                path: self.target(source)?,
+                read_only,
+            });
+            Ok(())
+        }
+
+        /// Whether `path` resolves on the host into a directory bound so far
+        fn covers(&self, path: &Path) -> bool {
+            fs::canonicalize(path).is_ok_and(|path| self.bound.iter().any(|dir| path.starts_with(dir)))
+        }
+    }
+
+    /// Create the directory new roots are mounted on, refusing one that someone else controls
+    fn create_root_dir(root: &Path) -> io::Result<()> {
+        match fs::create_dir(root) {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            result => result?,
+        }
+
+        let metadata = fs::symlink_metadata(root)?;
+        if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is not a directory of the current user", root.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Mount flags of the file system containing `path`, which a read-only remount in a user namespace must keep
+    fn locked_flags(path: &Path) -> io::Result<libc::c_ulong> {
+        let path = c_path(path)?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+
+        let mut flags = 0;
+        for (st, ms) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        Ok(flags)
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+
+    fn check<T: Default + PartialOrd>(result: T) -> io::Result<T> {
+        if result < T::default() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(result)
+    }
+
+    unsafe fn exists(path: &CStr) -> bool {
+        let mut stat: libc::stat = std::mem::zeroed();
+        libc::lstat(path.as_ptr(), &mut stat) == 0
+    }
+
+    unsafe fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+        let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    unsafe fn pipe() -> io::Result<(libc::c_int, libc::c_int)> {
+        let mut fds = [0; 2];
+        check(libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC))?;
+        Ok((fds[0], fds[1]))
+    }
+
+    /// Whether the process holding the write end of `alive` has exited
+    unsafe fn parent_gone(alive: libc::c_int) -> bool {
+        let mut poll = libc::pollfd {
+            fd: alive,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        libc::poll(&mut poll, 1, 0) == 1 && poll.revents & libc::POLLHUP != 0
+    }
+
+    /// Bring up the loopback interface of a new network namespace
+    unsafe fn loopback_up() -> io::Result<()> {
+        let socket = check(libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0))?;
+        let mut request: libc::ifreq = std::mem::zeroed();
+        request.ifr_name[..2].copy_from_slice(&[b'l' as libc::c_char, b'o' as libc::c_char]);
+
+        let mut result = libc::ioctl(socket, libc::SIOCGIFFLAGS, &mut request);
+        if result == 0 {
+            request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            result = libc::ioctl(socket, libc::SIOCSIFFLAGS, &request);
+        }
+        let err = io::Error::last_os_error();
+        libc::close(socket);
+        if result == -1 {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    extern "C" fn forward(signal: libc::c_int) {
+        unsafe { libc::kill(FORWARD_TO.load(Ordering::Relaxed), signal) };
+    }
+
+    /// Close everything but `keep`, pass signals on to `child` and reap processes until it exits
+    ///
+    /// Returns the wait status of `child`.
+    unsafe fn relay(child: libc::pid_t, keep: &mut [libc::c_int]) -> libc::c_int {
+        keep.sort_unstable();
+        let mut first = 0;
+        for &fd in keep.iter() {
+            close_range(first, fd as u32);
+            first = fd as u32 + 1;
+        }
+        close_range(first, u32::MAX);
+
+        FORWARD_TO.store(child, Ordering::Relaxed);
+        for signal in FORWARDED_SIGNALS {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = forward as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, ptr::null_mut());
+        }
+
+        loop {
+            let mut status = 0;
+            let pid = libc::waitpid(-1, &mut status, 0);
+            if pid == child {
+                return status;
+            }
+            if pid == -1 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                libc::_exit(1);
+            }
+        }
+    }
+
+    /// Close the descriptors from `first` up to but excluding `end`
+    unsafe fn close_range(first: u32, end: u32) {
+        if first >= end {
+            return;
+        }
+        if libc::syscall(libc::SYS_close_range, first, end - 1, 0) == -1 {
+            // Linux before 5.9
+            let max = libc::sysconf(libc::_SC_OPEN_MAX).clamp(0, end as libc::c_long) as u32;
+            for fd in first..max {
+                libc::close(fd as libc::c_int);
+            }
+        }
+    }
+
+    /// Exit with the wait status of another process
+    unsafe fn exit_like(status: libc::c_int) -> ! {
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+
+            // The program dumped its own core if it was going to
+            let no_core = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = libc::SIG_DFL;
+            libc::sigaction(signal, &action, ptr::null_mut());
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, signal);
+            libc::sigprocmask(libc::SIG_UNBLOCK, &set, ptr::null_mut());
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::exit::ExitInfo;
+use crate::process::{Process, ProcessBuilder, ProcessError, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the supervisor checks whether it is stopping, and whether an unwatched process exited
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Number of events kept for `Supervisor::events`
+const EVENT_HISTORY: usize = 256;
+
+/// When a supervised process is restarted after it exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never restart; supervision ends with the first exit
+    Never,
+
+    /// Restart unless the process exited with code 0
+    OnFailure,
+
+    /// Restart after every exit
+    Always,
+}
+
+/// Something that happened to a supervised process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    /// A new instance of the process was spawned after `restarts` restarts
+    Started { restarts: usize, pid: u32 },
+
+    /// The current instance of the process ended
+    Exited { restarts: usize, exit: ExitInfo },
+
+    /// Restart number `attempt` will be made after `delay`
+    Restarting { attempt: usize, delay: Duration },
+
+    /// Restart number `attempt` failed to spawn the process
+    SpawnFailed { attempt: usize, error: String },
+
+    /// The restart policy does not restart the process after its last exit
+    Finished { restarts: usize },
+
+    /// Supervision ended because the process was restarted too often
+    GaveUp { restarts: usize },
+}
+
+/// Callback invoked on the supervisor thread for each event
+pub type SupervisorCallback = Arc<dyn Fn(&SupervisorEvent) + Send + Sync>;
+
+/// Configuration of a supervised process
+#[derive(Clone)]
+pub struct SupervisorConfig {
+    /// Configuration every instance of the process is spawned from
+    pub builder: ProcessBuilder,
+
+    /// When the process is restarted
+    pub policy: RestartPolicy,
+
+    /// Delay before the first restart, doubled for each consecutive restart
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the delay; a process that ran this long resets the delay
+    pub max_backoff: Duration,
+
+    /// At most this many restarts within the window before giving up
+    pub max_restarts: Option<(usize, Duration)>,
+
+    /// Called for each event
+    pub callback: Option<SupervisorCallback>,
+}
+
+impl SupervisorConfig {
+    /// Restart the process on failure, waiting 100ms up to 30 seconds in between, without a limit
+    pub fn new(builder: ProcessBuilder) -> Self {
+        SupervisorConfig {
+            builder,
+            policy: RestartPolicy::OnFailure,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: None,
+            callback: None,
+        }
+    }
+
+    /// Set when the process is restarted
+    pub fn policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Wait `initial` before the first restart, doubling up to `max` for consecutive ones
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Give up once the process would be restarted more than `count` times within `window`
+    pub fn max_restarts(mut self, count: usize, window: Duration) -> Self {
+        self.max_restarts = Some((count, window));
+        self
+    }
+
+    /// Call `callback` for each event
+    pub fn on_event(mut self, callback: impl Fn(&SupervisorEvent) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl fmt::Debug for SupervisorConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SupervisorConfig")
+            .field("builder", &self.builder)
+            .field("policy", &self.policy)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("max_restarts", &self.max_restarts)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// State shared with the supervisor thread
+#[derive(Default)]
+struct State {
+    /// Set when the supervisor thread should exit
+    stopping: bool,
+
+    /// Set once no further restarts will be made
+    finished: bool,
+
+    /// Number of restarts made so far, including failed ones
+    restarts: usize,
+
+    /// The most recent events, oldest first
+    events: VecDeque<SupervisorEvent>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+
+    /// The current instance of the process, kept after it exits until replaced
+    process: Mutex<Process>,
+}
+
+/// Keeps a process running by restarting it according to a restart policy
+///
+/// Output and stdin refer to the current instance of the process; output
+/// that an exited instance left unread is dropped when it is replaced.
+pub struct Supervisor {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    /// Spawn the process and start supervising it
+    ///
+    /// Fails if the first instance cannot be spawned.
+    pub fn start(config: SupervisorConfig) -> Result<Self> {
+        let process = config.builder.spawn()?;
+        let pid = process.id().unwrap_or(0);
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            process: Mutex::new(process),
+        });
+
+        // The supervisor thread reports the first instance, like every later one
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || supervise(&shared, &config, pid))
+        };
+
+        Ok(Supervisor {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// ID of the current instance of the process
+    pub fn id(&self) -> Option<u32> {
+        self.process().id()
+    }
+
+    /// Check if the current instance of the process is running
+    pub fn is_running(&self) -> bool {
+        self.process().is_running()
+    }
+
+    /// Check if supervision ended, i.e. the process will not be restarted anymore
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().finished
+    }
+
+    /// Number of restarts made so far, including ones that failed to spawn
+    pub fn restarts(&self) -> usize {
+        self.shared.state.lock().unwrap().restarts
+    }
+
+    /// Get the most recent events, oldest first
+    pub fn events(&self) -> Vec<SupervisorEvent> {
+        self.shared.state.lock().unwrap().events.iter().cloned().collect()
+    }
+
+    /// Write data to the stdin of the current instance
+    pub fn write_stdin(&self, data: &[u8]) -> Result<usize> {
+        self.process().write_stdin(data)
+    }
+
+    /// Read data from the stdout buffer of the current instance
+    pub fn read_stdout(&self, buf: &mut [u8]) -> Result<usize> {
+        self.process().read_stdout(buf)
+    }
+
+    /// Read data from the stderr buffer of the current instance
+    pub fn read_stderr(&self, buf: &mut [u8]) -> Result<usize> {
+        self.process().read_stderr(buf)
+    }
+
+    /// Stop supervising and terminate the current instance
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    fn process(&self) -> MutexGuard<'_, Process> {
+        self.shared.process.lock().unwrap()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.stopping = true;
+            state.finished = true;
+        }
+        self.shared.changed.notify_all();
+        thread.join().map_err(|_| ProcessError::InvalidState)?;
+
+        self.process().close()
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+/// Record an event and pass it to the callback
+fn emit(shared: &Shared, config: &SupervisorConfig, event: SupervisorEvent) {
+    {
+        let mut state = shared.state.lock().unwrap();
+        if state.events.len() == EVENT_HISTORY {
+            state.events.pop_front();
+        }
+        state.events.push_back(event.clone());
+    }
+
+    if let Some(callback) = &config.callback {
+        callback(&event);
+    }
+}
+
+/// Sleep for `timeout`, returning `false` early if the supervisor is stopping
+fn sleep(shared: &Shared, timeout: Duration) -> bool {
+    let state = shared.state.lock().unwrap();
+    let (state, _) = shared
+        .changed
+        .wait_timeout_while(state, timeout, |state| !state.stopping)
+        .unwrap();
+    !state.stopping
+}
+
+/// Wait for the current instance to exit, `None` if the supervisor is stopping
+///
+/// An exit the reaper watches for wakes the supervisor right away, and the
+/// poll interval only bounds how late a stop is noticed. Other processes
+/// are polled for their exit as well.
+fn wait_exit(shared: &Shared) -> Option<ExitInfo> {
+    loop {
+        let watch = {
+            let mut process = shared.process.lock().unwrap();
+            if let Some(exit) = process.exit_info() {
+                return Some(exit);
+            }
+            process.exit_watch()
+        };
+
+        match watch {
+            Some(watch) => {
+                if !watch.wait(POLL_INTERVAL) && shared.state.lock().unwrap().stopping {
+                    return None;
+                }
+            }
+            None => {
+                if !sleep(shared, POLL_INTERVAL) {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Body of the supervisor thread, starting with the first instance spawned as `pid`
+fn supervise(shared: &Shared, config: &SupervisorConfig, pid: u32) {
+    let mut restart_times = VecDeque::new();
+    let mut delay = config.initial_backoff;
+    let mut started = Instant::now();
+    emit(shared, config, SupervisorEvent::Started { restarts: 0, pid });
+
+    loop {
+        let Some(exit) = wait_exit(shared) else {
+            return;
+        };
+
+        // Release the cgroup and other resources of the exited instance right away
+        let _ = shared.process.lock().unwrap().close();
+
+        let mut restarts = shared.state.lock().unwrap().restarts;
+        let mut success = exit.code == Some(0);
+        emit(shared, config, SupervisorEvent::Exited { restarts, exit });
+
+        // Restart the process until it spawns or supervision ends
+        loop {
+            let restart = match config.policy {
+                RestartPolicy::Never => false,
+                RestartPolicy::OnFailure => !success,
+                RestartPolicy::Always => true,
+            };
+            if !restart {
+                shared.state.lock().unwrap().finished = true;
+                emit(shared, config, SupervisorEvent::Finished { restarts });
+                return;
+            }
+
+            // Only restarts within the window count towards the limit
+            let now = Instant::now();
+            if let Some((count, window)) = config.max_restarts {
+                while restart_times
+                    .front()
+                    .is_some_and(|&time| now.duration_since(time) > window)
+                {
+                    restart_times.pop_front();
+                }
+                if restart_times.len() >= count {
+                    shared.state.lock().unwrap().finished = true;
+                    emit(shared, config, SupervisorEvent::GaveUp { restarts });
+                    return;
+                }
+            }
+
+            // A process that stayed up long enough is considered healthy again
+            if started.elapsed() >= config.max_backoff {
+                delay = config.initial_backoff;
+            }
+
+            let attempt = restarts + 1;
+            emit(shared, config, SupervisorEvent::Restarting { attempt, delay });
+            if !sleep(shared, delay) {
+                return;
+            }
+
+            restart_times.push_back(Instant::now());
+            shared.state.lock().unwrap().restarts = attempt;
+            delay = (delay * 2).min(config.max_backoff);
+            started = Instant::now();
+
+            match config.builder.spawn() {
+                Ok(process) => {
+                    let pid = process.id().unwrap_or(0);
+                    *shared.process.lock().unwrap() = process;
+                    emit(shared, config, SupervisorEvent::Started { restarts: attempt, pid });
+                    break;
+                }
+                Err(err) => {
+                    emit(
+                        shared,
+                        config,
+                        SupervisorEvent::SpawnFailed {
+                            attempt,
+                            error: err.to_string(),
+                        },
+                    );
+                    restarts = attempt;
+                    success = false;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+/// Resources used by a finished process
+///
+/// Everything but the wall-clock time is only measured on Unix and zero elsewhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Time from spawn until the process was reaped
+    pub wall_time: Duration,
+
+    /// CPU time spent in user mode
+    pub user_time: Duration,
+
+    /// CPU time spent in the kernel
+    pub system_time: Duration,
+
+    /// Peak resident set size in bytes
+    pub max_rss: u64,
+
+    /// Page faults served without I/O
+    pub minor_faults: u64,
+
+    /// Page faults that required I/O
+    pub major_faults: u64,
+
+    /// Context switches because the process waited for a resource
+    pub voluntary_switches: u64,
+
+    /// Context switches because the process was preempted
+    pub involuntary_switches: u64,
+}
+
+impl ResourceUsage {
+    /// Usage with only the wall-clock time known
+    pub(crate) fn wall_only(wall_time: Duration) -> Self {
+        ResourceUsage {
+            wall_time,
+            ..ResourceUsage::default()
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn from_rusage(rusage: &libc::rusage, wall_time: Duration) -> Self {
+        let timeval = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        };
+
+        // macOS reports the peak RSS in bytes, other systems in kilobytes
+        let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+
+        ResourceUsage {
+            wall_time,
+            user_time: timeval(rusage.ru_utime),
+            system_time: timeval(rusage.ru_stime),
+            max_rss: rusage.ru_maxrss as u64 * rss_unit,
+            minor_faults: rusage.ru_minflt as u64,
+            major_faults: rusage.ru_majflt as u64,
+            voluntary_switches: rusage.ru_nvcsw as u64,
+            involuntary_switches: rusage.ru_nivcsw as u64,
+        }
+    }
+}
+
+/// Reap a child with `wait4`, collecting its resource usage
+///
+/// Returns `None` if `block` is not set and the child is still running.
+#[cfg(unix)]
+pub(crate) fn wait4(pid: u32, block: bool) -> std::io::Result<Option<(std::process::ExitStatus, libc::rusage)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let flags = if block { 0 } else { libc::WNOHANG };
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    loop {
+        match unsafe { libc::wait4(pid as libc::pid_t, &mut status, flags, &mut rusage) } {
+            -1 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(None),
+            _ => return Ok(Some((std::process::ExitStatus::from_raw(status), rusage))),
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use log::{info, warn};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// What the watchdog does when a process stalls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// Only flag the process as stalled and call the callback
+    Notify,
+
+    /// Send a signal to the process (Unix only)
+    Signal(i32),
+
+    /// Kill the process
+    Kill,
+}
+
+/// A period in which a process neither produced output nor consumed stdin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stall {
+    /// Time since spawn at which the stall was detected
+    pub at: Duration,
+
+    /// How long the process had been inactive when the stall was detected
+    pub idle: Duration,
+}
+
+/// Callback invoked on the watchdog thread when a stall is detected
+pub type StallCallback = Arc<dyn Fn(&Stall) + Send + Sync>;
+
+/// Detection of processes that stopped producing output and consuming stdin
+///
+/// Output counts as activity when the library drains it, i.e. for captured
+/// streams and file sinks, and stdin as consumed when `write_stdin` returns.
+#[derive(Clone)]
+pub struct Watchdog {
+    /// Inactivity after which the process is considered stalled
+    pub timeout: Duration,
+
+    /// What to do once the process stalls
+    pub action: WatchdogAction,
+
+    /// Called once per stall, before the action is taken
+    pub callback: Option<StallCallback>,
+}
+
+impl Watchdog {
+    /// Flag the process as stalled after `timeout` of inactivity, without other action
+    pub fn new(timeout: Duration) -> Self {
+        Watchdog {
+            timeout,
+            action: WatchdogAction::Notify,
+            callback: None,
+        }
+    }
+
+    /// Set what to do once the process stalls
+    pub fn action(mut self, action: WatchdogAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Call `callback` on each stall
+    pub fn on_stall(mut self, callback: impl Fn(&Stall) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("timeout", &self.timeout)
+            .field("action", &self.action)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// Time of the last output or stdin write of a process
+pub(crate) struct Activity {
+    /// Time the process was spawned
+    start: Instant,
+
+    /// Time of the last activity
+    last: Mutex<Instant>,
+}
+
+impl Activity {
+    pub(crate) fn new(start: Instant) -> Self {
+        Activity {
+            start,
+            last: Mutex::new(start),
+        }
+    }
+
+    /// Record activity now
+    pub(crate) fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    fn idle(&self) -> Duration {
+        self.last.lock().unwrap().elapsed()
+    }
+}
+
+/// State shared with the watchdog thread
+#[derive(Default)]
+struct State {
+    /// Set once the process is about to be reaped, after which it may not be signalled
+    finished: bool,
+
+    /// Whether the process is stalled right now
+    stalled: bool,
+
+    /// The most recent stall
+    last_stall: Option<Stall>,
+
+    /// Signal or kill the watchdog carried out, if any
+    acted: Option<WatchdogAction>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// How the watchdog terminates a process
+pub(crate) struct Target {
+    /// Process ID, for logging
+    id: u32,
+
+    #[cfg(unix)]
+    pid: libc::pid_t,
+
+    /// Handle opened at spawn, which keeps the process ID from being reused
+    #[cfg(windows)]
+    handle: winapi::um::winnt::HANDLE,
+}
+
+// The handle is only used to terminate the process
+#[cfg(windows)]
+unsafe impl Send for Target {}
+
+impl Target {
+    #[cfg(unix)]
+    pub(crate) fn new(pid: u32) -> std::io::Result<Self> {
+        Ok(Target {
+            id: pid,
+            pid: pid as libc::pid_t,
+        })
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn new(pid: u32) -> std::io::Result<Self> {
+        use winapi::um::processthreadsapi::OpenProcess;
+        use winapi::um::winnt::PROCESS_TERMINATE;
+
+        let handle = unsafe { OpenProcess(PROCESS_TERMINATE, 0, pid) };
+        if handle.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Target { id: pid, handle })
+    }
+
+    /// Carry out the action, `None` for `Notify`
+    ///
+    /// Called under the state lock, so the outcome is logged by the caller
+    /// once the lock is released, as logging may reach a host callback.
+    fn act(&self, action: WatchdogAction) -> Option<std::io::Result<()>> {
+        #[cfg(unix)]
+        {
+            let signal = match action {
+                WatchdogAction::Notify => return None,
+                WatchdogAction::Signal(signal) => signal,
+                WatchdogAction::Kill => libc::SIGKILL,
+            };
+            if unsafe { libc::kill(self.pid, signal) } == -1 {
+                return Some(Err(std::io::Error::last_os_error()));
+            }
+            Some(Ok(()))
+        }
+
+        // Signals are rejected at spawn
+        #[cfg(windows)]
+        {
+            if action != WatchdogAction::Kill {
+                return None;
+            }
+            if unsafe { winapi::um::processthreadsapi::TerminateProcess(self.handle, 1) } == 0 {
+                return Some(Err(std::io::Error::last_os_error()));
+            }
+            Some(Ok(()))
+        }
+    }
+
+    /// Log the outcome of `act` for a stalled process
+    fn log_stall_action(&self, action: WatchdogAction, outcome: &std::io::Result<()>) {
+        match (outcome, action) {
+            (Ok(()), WatchdogAction::Signal(signal)) => info!("Sent signal {} to stalled process {}", signal, self.id),
+            (Ok(()), _) => info!("Killed stalled process {}", self.id),
+            (Err(err), _) => warn!("Signalling process {} failed: {}", self.id, err),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Target {
+    fn drop(&mut self) {
+        unsafe { winapi::um::handleapi::CloseHandle(self.handle) };
+    }
+}
+
+/// Background thread watching one process for stalls
+pub(crate) struct Monitor {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    pub(crate) fn start(watchdog: Watchdog, activity: Arc<Activity>, target: Target) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                // Checked often enough to notice a stall ending
+                let poll = (watchdog.timeout / 4).max(Duration::from_millis(10));
+                let mut state = shared.state.lock().unwrap();
+
+                while !state.finished {
+                    let idle = activity.idle();
+
+                    // Activity ends a stall; sleep until the next one could start
+                    if idle < watchdog.timeout {
+                        state.stalled = false;
+                        state = shared.changed.wait_timeout(state, watchdog.timeout - idle).unwrap().0;
+                        continue;
+                    }
+
+                    if !state.stalled {
+                        let stall = Stall {
+                            at: activity.start.elapsed(),
+                            idle,
+                        };
+                        state.stalled = true;
+                        state.last_stall = Some(stall);
+                        warn!("Process {} stalled without activity for {:?}", target.id, idle);
+
+                        if let Some(callback) = &watchdog.callback {
+                            drop(state);
+                            callback(&stall);
+                            state = shared.state.lock().unwrap();
+                            if state.finished {
+                                break;
+                            }
+                        }
+
+                        // Signalled under the lock, so it cannot race with reaping
+                        if let Some(outcome) = target.act(watchdog.action) {
+                            if outcome.is_ok() {
+                                state.acted = Some(watchdog.action);
+                            }
+                            drop(state);
+                            target.log_stall_action(watchdog.action, &outcome);
+                            state = shared.state.lock().unwrap();
+                            if state.finished {
+                                break;
+                            }
+                        }
+                    }
+
+                    state = shared.changed.wait_timeout(state, poll).unwrap().0;
+                }
+            })
+        };
+
+        Monitor {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Whether the process is stalled right now
+    pub(crate) fn is_stalled(&self) -> bool {
+        self.shared.state.lock().unwrap().stalled
+    }
+
+    /// The most recent stall and the signal or kill carried out because of it
+    pub(crate) fn last_stall(&self) -> (Option<Stall>, Option<WatchdogAction>) {
+        let state = self.shared.state.lock().unwrap();
+        (state.last_stall, state.acted)
+    }
+
+    /// Stop watching; must be called before the process is reaped
+    pub(crate) fn finish(&mut self) {
+        self.shared.state.lock().unwrap().finished = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Background thread killing one process once it has run for too long
+pub(crate) struct Deadline {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Deadline {
+    pub(crate) fn start(at: Instant, target: Target) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                let mut state = shared.state.lock().unwrap();
+                while !state.finished {
+                    let left = at.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        // Killed under the lock, so it cannot race with reaping
+                        let outcome = target.act(WatchdogAction::Kill);
+                        if matches!(outcome, Some(Ok(()))) {
+                            state.acted = Some(WatchdogAction::Kill);
+                        }
+                        drop(state);
+                        match outcome {
+                            Some(Ok(())) => warn!("Killed process {} after it timed out", target.id),
+                            Some(Err(err)) => warn!("Process {} timed out, but killing it failed: {}", target.id, err),
+                            None => warn!("Process {} timed out", target.id),
+                        }
+                        break;
+                    }
+                    state = shared.changed.wait_timeout(state, left).unwrap().0;
+                }
+            })
+        };
+
+        Deadline {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Whether the process was killed for running too long
+    pub(crate) fn expired(&self) -> bool {
+        self.shared.state.lock().unwrap().acted.is_some()
+    }
+
+    /// Stop waiting; must be called before the process is reaped
+    pub(crate) fn finish(&mut self) {
+        self.shared.state.lock().unwrap().finished = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Wait until the process exits without reaping it, so its ID stays reserved
+///
+/// Returns whether the process has exited; blocks until it does if `block` is set.
+#[cfg(unix)]
+pub(crate) fn wait_exited(pid: u32, block: bool) -> std::io::Result<bool> {
+    let flags = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == -1 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+
+        // Left zeroed by WNOHANG while the process is still running
+        return Ok(info.si_signo != 0);
+    }
+}
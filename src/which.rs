@@ -0,0 +1,220 @@
+use crate::process::{ProcessError, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+use std::ffi::OsString;
+
+/// Resolves program names to executable paths before spawning
+///
+/// Directories are searched in order: the bundled-tools directory, then
+/// either the caller-supplied search list or the `PATH` environment variable.
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    /// Directories searched instead of `PATH`
+    search_paths: Option<Vec<PathBuf>>,
+
+    /// Directory searched first; relative paths are resolved against the library's directory
+    bundled_dir: Option<PathBuf>,
+}
+
+impl Resolver {
+    /// Create a resolver searching `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Search the given directories instead of `PATH`
+    pub fn search_paths<I, P>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.search_paths = Some(paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Search a bundled-tools directory before anything else
+    ///
+    /// A relative directory is taken relative to the directory containing
+    /// this library, e.g. `Tools` next to the plugin.
+    pub fn bundled_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.bundled_dir = Some(dir.into());
+        self
+    }
+
+    /// Resolve `program` to the path of an executable file
+    ///
+    /// Programs containing a path separator are only checked, not searched for.
+    pub fn resolve(&self, program: &str) -> Result<PathBuf> {
+        if program.is_empty() {
+            return Err(ProcessError::InvalidState);
+        }
+
+        let program_path = Path::new(program);
+        if program_path.components().count() > 1 || program_path.is_absolute() {
+            return check_candidates(program_path).unwrap_or_else(|| {
+                Err(ProcessError::NotFound {
+                    program: program.to_string(),
+                    searched: program_path
+                        .parent()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or_default(),
+                })
+            });
+        }
+
+        let dirs = self.search_dirs();
+        let mut not_executable = None;
+
+        for dir in &dirs {
+            match check_candidates(&dir.join(program)) {
+                Some(Ok(path)) => return Ok(path),
+                Some(Err(e)) => {
+                    not_executable.get_or_insert(e);
+                }
+                None => {}
+            }
+        }
+
+        Err(not_executable.unwrap_or_else(|| ProcessError::NotFound {
+            program: program.to_string(),
+            searched: dirs
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }))
+    }
+
+    /// Directories to search, in order
+    fn search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+
+        if let Some(bundled_dir) = &self.bundled_dir {
+            if bundled_dir.is_absolute() {
+                dirs.push(bundled_dir.clone());
+            } else if let Some(library_dir) = library_dir() {
+                dirs.push(library_dir.join(bundled_dir));
+            }
+        }
+
+        match &self.search_paths {
+            Some(paths) => dirs.extend(paths.iter().cloned()),
+            None => {
+                if let Some(path) = env::var_os("PATH") {
+                    dirs.extend(env::split_paths(&path).filter(|dir| !dir.as_os_str().is_empty()));
+                }
+            }
+        }
+
+        dirs
+    }
+}
+
+/// Resolve `program` by searching `PATH`
+pub fn which(program: &str) -> Result<PathBuf> {
+    Resolver::new().resolve(program)
+}
+
+/// Check a candidate path, trying the `PATHEXT` extensions on Windows
+///
+/// Returns `None` if no file exists, or the result of the executable check otherwise.
+fn check_candidates(path: &Path) -> Option<Result<PathBuf>> {
+    let candidates = candidate_paths(path);
+
+    let mut not_executable = None;
+    for candidate in candidates {
+        let Ok(metadata) = fs::metadata(&candidate) else {
+            continue;
+        };
+
+        if metadata.is_file() && is_executable(&metadata) {
+            return Some(Ok(candidate));
+        }
+        not_executable.get_or_insert(candidate);
+    }
+
+    not_executable.map(|path| Err(ProcessError::NotExecutable(path)))
+}
+
+#[cfg(not(windows))]
+fn candidate_paths(path: &Path) -> Vec<PathBuf> {
+    vec![path.to_path_buf()]
+}
+
+#[cfg(windows)]
+fn candidate_paths(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![path.to_path_buf()];
+
+    if path.extension().is_none() {
+        let pathext = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        candidates.extend(pathext.split(';').filter(|ext| !ext.is_empty()).map(|ext| {
+            let mut name = OsString::from(path.as_os_str());
+            name.push(ext);
+            PathBuf::from(name)
+        }));
+    }
+
+    candidates
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    true
+}
+
+/// Directory containing the loaded library (or the executable it is linked into)
+pub fn library_dir() -> Option<PathBuf> {
+    library_path()?.parent().map(Path::to_path_buf)
+}
+
+#[cfg(unix)]
+fn library_path() -> Option<PathBuf> {
+    use std::ffi::CStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let address = library_path as *const libc::c_void;
+
+    if unsafe { libc::dladdr(address, &mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+
+    let fname = unsafe { CStr::from_ptr(info.dli_fname) };
+    let path = Path::new(std::ffi::OsStr::from_bytes(fname.to_bytes()));
+    fs::canonicalize(path).ok()
+}
+
+#[cfg(windows)]
+fn library_path() -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use winapi::shared::minwindef::HMODULE;
+    use winapi::um::libloaderapi::{
+        GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+        GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+    };
+
+    let mut module: HMODULE = std::ptr::null_mut();
+    let address = library_path as *const u16;
+    let flags = GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT;
+
+    if unsafe { GetModuleHandleExW(flags, address, &mut module) } == 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u16; 32768];
+    let len = unsafe { GetModuleFileNameW(module, buf.as_mut_ptr(), buf.len() as u32) } as usize;
+    if len == 0 || len >= buf.len() {
+        return None;
+    }
+
+    Some(PathBuf::from(OsString::from_wide(&buf[..len])))
+}
@@ -0,0 +1,100 @@
+use betahub_process_wrapper::backend::{FakeBackend, FakeScript};
+use betahub_process_wrapper::expect::Pattern;
+use betahub_process_wrapper::exit::TerminationReason;
+use betahub_process_wrapper::output::{FileSink, OutputSink};
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError};
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_fake_output_and_exit_code() {
+    let fake = Arc::new(FakeBackend::new());
+    fake.set_script(
+        "ffmpeg",
+        FakeScript::new()
+            .stderr("frame=    1\n")
+            .sleep(Duration::from_millis(50))
+            .stderr("frame=    2\n")
+            .stdout("done\n")
+            .exit_code(1),
+    );
+    
+    let mut proc = ProcessBuilder::new("/usr/bin/ffmpeg")
+        .args(&["-i", "input.mkv"])
+        .capture_stdout(true)
+        .backend(fake.clone())
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 1);
+    assert_eq!(output.stderr, b"frame=    1\nframe=    2\n");
+    assert_eq!(output.stdout, b"done\n");
+    assert_eq!(proc.exit_info().unwrap().code, Some(1));
+    
+    let invocations = fake.invocations();
+    assert_eq!(invocations.len(), 1);
+    assert_eq!(invocations[0].program, "/usr/bin/ffmpeg");
+    assert_eq!(invocations[0].args, vec!["-i", "input.mkv"]);
+}
+
+#[test]
+fn test_fake_captures_stdin_until_closed() {
+    let fake = Arc::new(FakeBackend::new());
+    fake.set_script("recorder", FakeScript::new().stderr("recording\n").until_stdin_closed());
+    
+    let mut proc = ProcessBuilder::new("recorder").backend(fake.clone()).spawn().unwrap();
+    proc.wait_for_output(&Pattern::literal("recording"), Duration::from_secs(5)).unwrap();
+    proc.write_stdin(b"q").unwrap();
+    assert!(proc.is_running());
+    assert_eq!(fake.invocations()[0].stdin, b"q");
+    
+    proc.close_stdin();
+    assert_eq!(proc.wait().unwrap(), 0);
+}
+
+#[test]
+fn test_fake_killed_by_close() {
+    let fake = Arc::new(FakeBackend::new());
+    fake.set_script("stuck", FakeScript::new().sleep(Duration::from_secs(30)));
+    
+    let mut proc = ProcessBuilder::new("stuck").backend(fake).spawn().unwrap();
+    assert!(proc.id().is_some());
+    
+    let started = Instant::now();
+    proc.close().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(proc.id().is_none());
+    
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.code, None);
+    assert_ne!(info.reason, TerminationReason::Exited);
+}
+
+#[test]
+fn test_fake_output_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ffmpeg.log");
+    let fake = Arc::new(FakeBackend::new());
+    fake.set_script("ffmpeg", FakeScript::new().stderr("to the log\n"));
+    
+    let mut proc = ProcessBuilder::new("ffmpeg")
+        .stderr(OutputSink::File(FileSink::new(&path)))
+        .backend(fake)
+        .spawn()
+        .unwrap();
+    
+    assert!(proc.wait_with_output().unwrap().stderr.is_empty());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "to the log\n");
+}
+
+#[test]
+fn test_unscripted_programs_fail() {
+    let fake = Arc::new(FakeBackend::new());
+    fake.set_script("ffmpeg", FakeScript::new());
+    
+    let result = ProcessBuilder::new("echo").arg("real").backend(fake.clone()).spawn();
+    assert!(matches!(result, Err(ProcessError::Unscripted(program)) if program == "echo"));
+    assert!(fake.invocations().is_empty());
+}
@@ -0,0 +1,147 @@
+#![cfg(target_os = "linux")]
+
+use betahub_process_wrapper::cgroup::CgroupConfig;
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError};
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_cgroup, process_builder_free, process_builder_new, process_builder_spawn,
+    process_cgroup_stats, process_close, process_wait, ProcessCgroupStats,
+};
+use std::ffi::CString;
+use std::fs;
+use std::path::PathBuf;
+
+const BUSY_LOOP: &str = "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done";
+
+/// A writable cgroup v2 directory to create test cgroups in, if the system has one
+fn cgroup_parent() -> Option<PathBuf> {
+    ["/sys/fs/cgroup/unified", "/sys/fs/cgroup"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|parent| {
+            let probe = parent.join(format!("pw_probe_{}", std::process::id()));
+            parent.join("cgroup.procs").exists() && fs::create_dir(&probe).is_ok() && fs::remove_dir(&probe).is_ok()
+        })
+}
+
+#[test]
+fn test_cgroup_placement_and_accounting() {
+    let Some(parent) = cgroup_parent() else {
+        return;
+    };
+    
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", &format!("cat /proc/self/cgroup; {}", BUSY_LOOP)])
+        .capture_stdout(true)
+        .cgroup(CgroupConfig::new(&parent))
+        .spawn()
+        .unwrap();
+    
+    let path = proc.cgroup_path().unwrap().to_path_buf();
+    assert!(path.starts_with(&parent));
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 0);
+    let name = path.file_name().unwrap().to_str().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains(name));
+    
+    let stats = proc.cgroup_stats().unwrap();
+    assert!(stats.cpu_usage > std::time::Duration::ZERO);
+    
+    // The final usage stays readable once the cgroup is removed
+    proc.close().unwrap();
+    assert!(!path.exists());
+    assert!(proc.cgroup_path().is_none());
+    assert_eq!(proc.cgroup_stats().unwrap().cpu_usage, stats.cpu_usage);
+}
+
+#[test]
+fn test_cgroup_close_kills_descendants() {
+    let Some(parent) = cgroup_parent() else {
+        return;
+    };
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "sleep 30 & sleep 30"])
+        .cgroup(CgroupConfig::new(&parent))
+        .spawn()
+        .unwrap();
+    let path = proc.cgroup_path().unwrap().to_path_buf();
+    
+    // Killing the whole cgroup needs Linux 5.14
+    if !path.join("cgroup.kill").exists() {
+        proc.close().unwrap();
+        return;
+    }
+    
+    proc.close().unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_cgroup_memory_limit() {
+    let Some(parent) = cgroup_parent() else {
+        return;
+    };
+    let controllers = fs::read_to_string(parent.join("cgroup.controllers")).unwrap_or_default();
+    if !controllers.split_whitespace().any(|controller| controller == "memory") {
+        return;
+    }
+    
+    let mut proc = ProcessBuilder::new("true")
+        .cgroup(CgroupConfig::new(&parent).memory_max(64 * 1024 * 1024))
+        .spawn()
+        .unwrap();
+    
+    let memory_max = fs::read_to_string(proc.cgroup_path().unwrap().join("memory.max")).unwrap();
+    assert_eq!(memory_max.trim(), (64 * 1024 * 1024).to_string());
+    
+    proc.wait().unwrap();
+    assert!(proc.cgroup_stats().unwrap().memory_current.is_some());
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_cgroup_missing_parent() {
+    let result = ProcessBuilder::new("true")
+        .cgroup(CgroupConfig::new("/nonexistent/cgroup/parent"))
+        .spawn();
+    assert!(matches!(result, Err(ProcessError::Io(_))));
+}
+
+#[test]
+fn test_cgroup_stats_without_cgroup() {
+    let mut proc = ProcessBuilder::new("true").spawn().unwrap();
+    proc.wait().unwrap();
+    
+    assert!(proc.cgroup_path().is_none());
+    assert!(matches!(proc.cgroup_stats(), Err(ProcessError::InvalidState)));
+}
+
+#[test]
+fn test_ffi_cgroup() {
+    let Some(parent) = cgroup_parent() else {
+        return;
+    };
+    
+    let program = CString::new("sh").unwrap();
+    let flag = CString::new("-c").unwrap();
+    let script = CString::new(BUSY_LOOP).unwrap();
+    let parent = CString::new(parent.to_str().unwrap()).unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, flag.as_ptr());
+        process_builder_arg(builder, script.as_ptr());
+        assert_eq!(process_builder_cgroup(builder, parent.as_ptr(), 0, 0, 0), 0);
+        let proc = process_builder_spawn(builder);
+        process_builder_free(builder);
+        assert!(!proc.is_null());
+        
+        assert_eq!(process_wait(proc), 0);
+        let mut stats = ProcessCgroupStats::default();
+        assert_eq!(process_cgroup_stats(proc, &mut stats), 0);
+        assert!(stats.cpu_usage_us > 0);
+        
+        process_close(proc);
+        assert_eq!(process_cgroup_stats(proc, &mut stats), 0);
+    }
+}
@@ -0,0 +1,160 @@
+use betahub_process_wrapper::output::backup_path;
+use betahub_process_wrapper::process::ProcessBuilder;
+use serde_json::Value;
+use std::fs;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+const CLI: &str = env!("CARGO_BIN_EXE_betahub-pw");
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
+/// Run the CLI, returning its output and the JSON report
+fn run(args: &[&str]) -> (Output, Value) {
+    let dir = TempDir::new().unwrap();
+    let report = dir.path().join("report.json");
+    
+    let output = Command::new(CLI)
+        .arg("--report")
+        .arg(&report)
+        .args(args)
+        .output()
+        .unwrap();
+    let report = serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+    (output, report)
+}
+
+#[test]
+fn test_builder_env_and_current_dir() {
+    let dir = TempDir::new().unwrap();
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "echo \"$PW_SET|$PW_REMOVED|$PW_CLEARED\"; pwd"])
+        .env("PW_SET", "set")
+        .env("PW_REMOVED", "inherited")
+        .env_remove("PW_REMOVED")
+        .current_dir(dir.path())
+        .capture_stdout(true)
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let cwd = fs::canonicalize(dir.path()).unwrap();
+    assert_eq!(stdout, format!("set||\n{}\n", cwd.display()));
+    
+    let mut builder = ProcessBuilder::new("/usr/bin/env");
+    builder.env("PW_KEPT", "kept").env_clear();
+    let output = builder.capture_stdout(true).spawn().unwrap().wait_with_output().unwrap();
+    assert_eq!(output.stdout, b"PW_KEPT=kept\n");
+}
+
+#[test]
+fn test_streams_output_and_reports_exit() {
+    let (output, report) = run(&["--env", "PW_VALUE=42", "sh", "-c", "echo out $PW_VALUE; echo err >&2; exit 3"]);
+    
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, b"out 42\n");
+    assert_eq!(output.stderr, b"err\n");
+    assert_eq!(report["program"], "sh");
+    assert_eq!(report["timed_out"], false);
+    assert_eq!(report["exit"]["code"], 3);
+    assert_eq!(report["exit"]["reason"], "exited");
+    assert!(report["usage"]["max_rss"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_feeds_file_in_chunks() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("frames.bin");
+    let data: Vec<u8> = (0..250_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&input, &data).unwrap();
+    let copy = dir.path().join("copy.bin");
+    
+    let (output, report) = run(&[
+        "--feed",
+        input.to_str().unwrap(),
+        "--chunk-size",
+        "100000",
+        "--chunk-interval",
+        "5",
+        "--stdout",
+        &format!("file:{}", copy.display()),
+        TESTCHILD,
+        "echo",
+    ]);
+    
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(report["stdin_bytes"], 250_000);
+    assert_eq!(fs::read(copy).unwrap(), data);
+}
+
+#[test]
+fn test_stdin_data_tee_rotation_and_events() {
+    let dir = TempDir::new().unwrap();
+    let log = dir.path().join("out.log");
+    let events = dir.path().join("events.jsonl");
+    
+    let (output, report) = run(&[
+        "--stdin",
+        "data:hello",
+        "--stdout",
+        &format!("file:{}", log.display()),
+        "--rotate",
+        "5:1",
+        "--tee",
+        "--events",
+        events.to_str().unwrap(),
+        TESTCHILD,
+        "echo",
+        "sleep",
+        "50",
+        "out",
+        "two",
+    ]);
+    
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(report["exit"]["code"], 0);
+    
+    // Teed output is still streamed, while the file rotated before the second write
+    assert_eq!(output.stdout, b"hellotwo\n");
+    assert_eq!(fs::read(backup_path(&log, 1)).unwrap(), b"hello");
+    assert_eq!(fs::read(&log).unwrap(), b"two\n");
+    
+    let events = fs::read_to_string(events).unwrap();
+    let events: Vec<Value> = events.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert!(events.iter().any(|event| event["stream"] == "stdout"));
+}
+
+#[test]
+fn test_missing_feed_is_not_a_spawn_failure() {
+    let output = Command::new(CLI).args(["--feed", "/nonexistent/frames.raw", "true"]).output().unwrap();
+    
+    assert_eq!(output.status.code(), Some(74));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--feed /nonexistent/frames.raw"));
+}
+
+#[test]
+fn test_timeout_kills_process() {
+    let (output, report) = run(&["--timeout", "100", TESTCHILD, "hang"]);
+    
+    assert_eq!(output.status.code(), Some(124));
+    assert_eq!(report["timed_out"], true);
+    #[cfg(unix)]
+    assert_eq!(report["exit"]["signal"], libc::SIGKILL);
+}
+
+#[test]
+fn test_usage_errors() {
+    for args in [
+        &["--bogus", "true"][..],
+        &["--timeout"],
+        &["--limit", "bogus=1", "true"],
+        &["--io-priority", "high", "true"],
+        &["--cpus", "0-x", "true"],
+        &["--memory-max", "1000", "true"],
+        &[],
+    ] {
+        let output = Command::new(CLI).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("usage: betahub-pw"));
+    }
+}
@@ -0,0 +1,14 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Poll `condition` for up to five seconds
+pub fn eventually(mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
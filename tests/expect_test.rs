@@ -0,0 +1,117 @@
+use betahub_process_wrapper::expect::Pattern;
+use betahub_process_wrapper::output::StreamId;
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError};
+use betahub_process_wrapper::{
+    process_builder_capture_stdout, process_builder_free, process_builder_new, process_builder_spawn,
+    process_close, process_wait_for_output, process_write_stdin,
+};
+use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_wait_for_prompt() {
+    // A tiny interactive tool: prompt, read a name, greet
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "printf 'name> '; read name; echo \"hello $name\"; printf 'name> '; read name"])
+        .capture_stdout(true)
+        .spawn()
+        .unwrap();
+    
+    let prompt = Pattern::literal("name> ");
+    let found = proc.wait_for_output(&prompt, Duration::from_secs(5)).unwrap();
+    assert_eq!(found.stream, StreamId::Stdout);
+    assert_eq!(found.text, "name> ");
+    
+    proc.write_stdin(b"world\n").unwrap();
+    
+    let greeting = Pattern::regex(r"hello (\w+)").unwrap();
+    let found = proc.wait_for_output(&greeting, Duration::from_secs(5)).unwrap();
+    assert_eq!(found.text, "hello world");
+    assert_eq!(found.captures, vec![Some("world".to_string())]);
+    
+    // The first prompt was consumed, so this waits for the second one
+    proc.wait_for_output(&prompt, Duration::from_secs(5)).unwrap();
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_wait_for_stderr() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "echo 'listening on port 4242' 1>&2; sleep 5"])
+        .spawn()
+        .unwrap();
+    
+    let pattern = Pattern::regex(r"port (\d+)(x)?").unwrap();
+    let found = proc.wait_for_output(&pattern, Duration::from_secs(5)).unwrap();
+    assert_eq!(found.stream, StreamId::Stderr);
+    assert_eq!(found.captures, vec![Some("4242".to_string()), None]);
+    
+    // Matching does not consume the buffer read by read_stderr
+    let mut buf = [0u8; 64];
+    let n = proc.read_stderr(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"listening on port 4242\n");
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_wait_for_output_timeout() {
+    let mut proc = ProcessBuilder::new("sleep").arg("5").spawn().unwrap();
+    
+    let start = Instant::now();
+    let err = proc
+        .wait_for_output(&Pattern::literal("never"), Duration::from_millis(100))
+        .unwrap_err();
+    assert!(matches!(err, ProcessError::Timeout));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_wait_for_output_closed() {
+    let proc = ProcessBuilder::new("echo").arg("done").capture_stdout(true).spawn().unwrap();
+    
+    let err = proc
+        .wait_for_output(&Pattern::literal("never"), Duration::from_secs(5))
+        .unwrap_err();
+    assert!(matches!(err, ProcessError::OutputClosed));
+}
+
+#[test]
+fn test_invalid_regex() {
+    assert!(matches!(Pattern::regex("(unclosed"), Err(ProcessError::InvalidPattern(_))));
+}
+
+#[test]
+fn test_ffi_wait_for_output() {
+    let program = CString::new("cat").unwrap();
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    unsafe { process_builder_capture_stdout(builder, 1) };
+    let proc = unsafe { process_builder_spawn(builder) };
+    unsafe { process_builder_free(builder) };
+    
+    let data = b"frame=120 fps=60\n";
+    unsafe { process_write_stdin(proc, data.as_ptr(), data.len()) };
+    
+    let pattern = CString::new(r"fps=(\d+)").unwrap();
+    let mut buf = [0 as libc::c_char; 16];
+    let len = unsafe { process_wait_for_output(proc, pattern.as_ptr(), 1, 5000, 1, buf.as_mut_ptr(), buf.len()) };
+    assert_eq!(len, 2);
+    assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap(), "60");
+    
+    // A group the pattern does not have is rejected without consuming output
+    let data = b"fps=30\n";
+    unsafe { process_write_stdin(proc, data.as_ptr(), data.len()) };
+    let len = unsafe { process_wait_for_output(proc, pattern.as_ptr(), 1, 5000, 2, buf.as_mut_ptr(), buf.len()) };
+    assert_eq!(len, -2);
+    let len = unsafe { process_wait_for_output(proc, pattern.as_ptr(), 1, 5000, 1, buf.as_mut_ptr(), buf.len()) };
+    assert_eq!(len, 2);
+    assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap(), "30");
+    
+    let literal = CString::new("never").unwrap();
+    let len = unsafe { process_wait_for_output(proc, literal.as_ptr(), 0, 50, 0, buf.as_mut_ptr(), buf.len()) };
+    assert_eq!(len, -1);
+    
+    unsafe { process_close(proc) };
+}
@@ -0,0 +1,50 @@
+//! The C exports of the fake backend, built with the `fake-backend` feature
+//!
+//! Kept out of the main test binary: the fake is installed as the default
+//! backend for the whole process, which would break tests running real
+//! processes in parallel.
+
+use betahub_process_wrapper::{
+    process_close, process_fake_backend_init, process_fake_backend_reset, process_fake_backend_script,
+    process_fake_backend_stdin, process_fake_backend_step, process_read_stderr, process_start, process_wait,
+    process_write_stdin, FAKE_STEP_SLEEP, FAKE_STEP_STDERR,
+};
+use std::ffi::CString;
+
+#[test]
+fn test_ffi_fake_backend() {
+    let program = CString::new("pw-fake-ffi-tool").unwrap();
+    let command = CString::new("pw-fake-ffi-tool --record").unwrap();
+    let progress = b"frame=1\n";
+    
+    unsafe {
+        assert_eq!(process_fake_backend_script(program.as_ptr(), 0, 0), -1);
+        process_fake_backend_init();
+        assert_eq!(process_fake_backend_script(program.as_ptr(), 3, 0), 0);
+        assert_eq!(
+            process_fake_backend_step(program.as_ptr(), FAKE_STEP_STDERR, progress.as_ptr(), progress.len(), 0),
+            0
+        );
+        assert_eq!(process_fake_backend_step(program.as_ptr(), FAKE_STEP_SLEEP, std::ptr::null(), 0, 200), 0);
+        assert_eq!(process_fake_backend_step(program.as_ptr(), 42, std::ptr::null(), 0, 0), -1);
+        
+        let proc = process_start(command.as_ptr());
+        assert!(!proc.is_null());
+        assert_eq!(process_write_stdin(proc, b"q".as_ptr(), 1), 1);
+        assert_eq!(process_wait(proc), 3);
+        
+        let mut buf = [0u8; 64];
+        let n = process_read_stderr(proc, buf.as_mut_ptr(), buf.len());
+        assert_eq!(&buf[..n as usize], progress);
+        assert_eq!(process_fake_backend_stdin(program.as_ptr(), buf.as_mut_ptr(), buf.len()), 1);
+        assert_eq!(buf[0], b'q');
+        
+        // Programs without a script are not run for real
+        let unscripted = CString::new("pw-unscripted-tool").unwrap();
+        assert!(process_start(unscripted.as_ptr()).is_null());
+        
+        process_close(proc);
+        process_fake_backend_reset();
+        assert_eq!(process_fake_backend_stdin(program.as_ptr(), buf.as_mut_ptr(), buf.len()), -1);
+    }
+}
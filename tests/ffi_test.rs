@@ -1,10 +1,31 @@
+use betahub_process_wrapper::process::Process;
 use betahub_process_wrapper::{
-    process_close, process_is_running, process_read_stderr, process_start, process_start_with_args,
-    process_wait, process_write_stdin,
+    process_builder_arg, process_builder_capture_stdout, process_builder_free, process_builder_new,
+    process_builder_spawn, process_builder_stdin_bytes, process_builder_stdin_null, process_close,
+    process_is_running, process_read_stderr, process_read_stdout, process_start,
+    process_start_with_args, process_wait, process_write_stdin, WRITE_STDIN_NOT_PIPED,
 };
 use std::ffi::CString;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
+/// Read captured stderr until some arrived and the process exited, for up to five seconds
+fn read_stderr_eventually(proc: *mut Process) -> String {
+    let mut output = Vec::new();
+    let mut buf = [0u8; 1024];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let result = unsafe { process_read_stderr(proc, buf.as_mut_ptr(), buf.len()) };
+        output.extend_from_slice(&buf[..result as usize]);
+        if !output.is_empty() && unsafe { process_is_running(proc) } == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    String::from_utf8(output).unwrap()
+}
 
 #[test]
 fn test_ffi_process_start() {
@@ -23,7 +44,7 @@ fn test_ffi_process_start_with_args() {
     let arg2 = CString::new("world").unwrap();
     
     // Create array of C string pointers
-    let args = vec![arg1.as_ptr(), arg2.as_ptr()];
+    let args = [arg1.as_ptr(), arg2.as_ptr()];
     
     let proc = unsafe { process_start_with_args(program.as_ptr(), args.as_ptr(), args.len()) };
     assert!(!proc.is_null());
@@ -44,7 +65,7 @@ fn test_ffi_null_command() {
 
 #[test]
 fn test_ffi_null_program() {
-    let args = vec![std::ptr::null()];
+    let args = [std::ptr::null()];
     let proc = unsafe { process_start_with_args(std::ptr::null(), args.as_ptr(), args.len()) };
     assert!(proc.is_null());
 }
@@ -76,26 +97,16 @@ fn test_ffi_write_stdin() {
 #[test]
 fn test_ffi_stderr_capture() {
     // Start a process that outputs to stderr
-    let cmd = CString::new("sh -c \"echo error message >&2\"").unwrap();
+    let cmd = CString::new(format!("{} err error-message", TESTCHILD)).unwrap();
     let proc = unsafe { process_start(cmd.as_ptr()) };
     assert!(!proc.is_null());
     
-    // Wait for stderr to be captured (increased delay)
-    thread::sleep(Duration::from_millis(300));
-    
-    // Read from stderr
-    let mut buf = [0u8; 1024];
-    let result = unsafe { process_read_stderr(proc, buf.as_mut_ptr(), buf.len()) };
-    assert!(result > 0);
-    
-    // Verify the stderr output
-    let stderr_output = std::str::from_utf8(&buf[..result as usize]).unwrap();
-    println!("Stderr output: {}", stderr_output);
-    assert!(stderr_output.contains("error") || stderr_output.contains("message"));
+    // Wait for stderr to be captured
+    assert_eq!(read_stderr_eventually(proc), "error-message\n");
     
     // Wait for the process
     let exit_code = unsafe { process_wait(proc) };
-    println!("Exit code: {}", exit_code);
+    assert_eq!(exit_code, 0);
     
     // Clean up
     unsafe { process_close(proc) };
@@ -104,27 +115,17 @@ fn test_ffi_stderr_capture() {
 #[test]
 fn test_ffi_stderr_capture_with_args() {
     // Start a process that outputs to stderr using process_start_with_args
-    let program = CString::new("sh").unwrap();
-    let arg = CString::new("-c").unwrap();
-    let cmd = CString::new("echo error message >&2").unwrap();
+    let program = CString::new(TESTCHILD).unwrap();
+    let arg = CString::new("err").unwrap();
+    let text = CString::new("error message").unwrap();
     
-    let args = vec![arg.as_ptr(), cmd.as_ptr()];
+    let args = [arg.as_ptr(), text.as_ptr()];
     
     let proc = unsafe { process_start_with_args(program.as_ptr(), args.as_ptr(), args.len()) };
     assert!(!proc.is_null());
     
     // Wait for stderr to be captured
-    thread::sleep(Duration::from_millis(300));
-    
-    // Read from stderr
-    let mut buf = [0u8; 1024];
-    let result = unsafe { process_read_stderr(proc, buf.as_mut_ptr(), buf.len()) };
-    assert!(result > 0);
-    
-    // Verify the stderr output
-    let stderr_output = std::str::from_utf8(&buf[..result as usize]).unwrap();
-    println!("Stderr output with args: {}", stderr_output);
-    assert!(stderr_output.contains("error") || stderr_output.contains("message"));
+    assert_eq!(read_stderr_eventually(proc), "error message\n");
     
     // Wait for the process
     let exit_code = unsafe { process_wait(proc) };
@@ -136,8 +137,8 @@ fn test_ffi_stderr_capture_with_args() {
 
 #[test]
 fn test_ffi_is_running() {
-    // Start a process that sleeps for a short time
-    let cmd = CString::new("sleep 0.1").unwrap();
+    // Start a process that runs until it reads a line
+    let cmd = CString::new(format!("{} read-line", TESTCHILD)).unwrap();
     let proc = unsafe { process_start(cmd.as_ptr()) };
     assert!(!proc.is_null());
     
@@ -145,17 +146,15 @@ fn test_ffi_is_running() {
     let is_running = unsafe { process_is_running(proc) };
     assert_eq!(is_running, 1);
     
-    // Wait for it to exit
-    thread::sleep(Duration::from_millis(200));
+    // Let it exit and wait for it
+    assert_eq!(unsafe { process_write_stdin(proc, b"\n".as_ptr(), 1) }, 1);
+    let exit_code = unsafe { process_wait(proc) };
+    assert_eq!(exit_code, 0);
     
     // Check if it's still running (it shouldn't be)
     let is_running = unsafe { process_is_running(proc) };
     assert_eq!(is_running, 0);
     
-    // Wait for it
-    let exit_code = unsafe { process_wait(proc) };
-    assert_eq!(exit_code, 0);
-    
     // Clean up
     unsafe { process_close(proc) };
 }
@@ -193,4 +192,79 @@ fn test_ffi_null_process() {
     
     // This should not crash
     unsafe { process_close(std::ptr::null_mut()) };
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_ffi_builder_stdin_bytes() {
+    let program = CString::new(TESTCHILD).unwrap();
+    let echo = CString::new("echo").unwrap();
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    assert!(!builder.is_null());
+    
+    let data = b"piped by the library";
+    unsafe {
+        assert_eq!(process_builder_arg(builder, echo.as_ptr()), 0);
+        assert_eq!(process_builder_stdin_bytes(builder, data.as_ptr(), data.len()), 0);
+        process_builder_capture_stdout(builder, 1);
+    }
+    
+    let proc = unsafe { process_builder_spawn(builder) };
+    assert!(!proc.is_null());
+    unsafe { process_builder_free(builder) };
+    
+    // Writing is rejected while the library feeds stdin
+    let result = unsafe { process_write_stdin(proc, b"x".as_ptr(), 1) };
+    assert_eq!(result, WRITE_STDIN_NOT_PIPED);
+    
+    let exit_code = unsafe { process_wait(proc) };
+    assert_eq!(exit_code, 0);
+    
+    // The output may still be drained after the exit
+    let mut output = Vec::new();
+    let mut buf = [0u8; 64];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while output.len() < data.len() && Instant::now() < deadline {
+        let result = unsafe { process_read_stdout(proc, buf.as_mut_ptr(), buf.len()) };
+        output.extend_from_slice(&buf[..result as usize]);
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(output, data);
+    
+    unsafe { process_close(proc) };
+}
+
+#[test]
+fn test_ffi_builder_stdin_null() {
+    let program = CString::new("sh").unwrap();
+    let arg1 = CString::new("-c").unwrap();
+    let arg2 = CString::new("read line; exit 4").unwrap();
+    
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    unsafe {
+        assert_eq!(process_builder_arg(builder, arg1.as_ptr()), 0);
+        assert_eq!(process_builder_arg(builder, arg2.as_ptr()), 0);
+        process_builder_stdin_null(builder);
+    }
+    
+    let proc = unsafe { process_builder_spawn(builder) };
+    assert!(!proc.is_null());
+    unsafe { process_builder_free(builder) };
+    
+    // Distinguished from a broken pipe
+    let data = b"input\n";
+    assert_eq!(unsafe { process_write_stdin(proc, data.as_ptr(), data.len()) }, WRITE_STDIN_NOT_PIPED);
+    
+    let exit_code = unsafe { process_wait(proc) };
+    assert_eq!(exit_code, 4);
+    assert_eq!(unsafe { process_write_stdin(proc, data.as_ptr(), data.len()) }, WRITE_STDIN_NOT_PIPED);
+    
+    unsafe { process_close(proc) };
+}
+
+#[test]
+fn test_ffi_builder_null() {
+    assert!(unsafe { process_builder_new(std::ptr::null()) }.is_null());
+    assert!(unsafe { process_builder_spawn(std::ptr::null_mut()) }.is_null());
+    assert_eq!(unsafe { process_builder_arg(std::ptr::null_mut(), std::ptr::null()) }, -1);
+    unsafe { process_builder_free(std::ptr::null_mut()) };
+}
@@ -4,9 +4,11 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tempfile::tempdir;
 
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
 #[test]
 fn test_process_version() {
     // Check if a command can run successfully
@@ -24,17 +26,19 @@ fn test_process_version() {
 
 #[test]
 fn test_process_error_output() {
-    // Run a command with an invalid option
-    let cmd = CString::new("ls -invalid-option").unwrap();
+    // Run a command failing like a tool given an invalid option
+    let cmd = CString::new(format!("{} err unrecognized-option exit 2", TESTCHILD)).unwrap();
     let proc = unsafe { process_start(cmd.as_ptr()) };
     assert!(!proc.is_null());
     
     // Wait for stderr to be captured
-    thread::sleep(Duration::from_millis(300));
-    
-    // Read from stderr
     let mut buf = [0u8; 1024];
-    let result = unsafe { process_read_stderr(proc, buf.as_mut_ptr(), buf.len()) };
+    let mut result = 0;
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while result == 0 && Instant::now() < deadline {
+        result = unsafe { process_read_stderr(proc, buf.as_mut_ptr(), buf.len()) };
+        thread::sleep(Duration::from_millis(5));
+    }
     assert!(result > 0);
     
     // Verify that the stderr output contains an error message
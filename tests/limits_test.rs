@@ -0,0 +1,160 @@
+#![cfg(unix)]
+
+use betahub_process_wrapper::exit::TerminationReason;
+use betahub_process_wrapper::limits::{Resource, UNLIMITED};
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_limit, process_builder_new, process_builder_spawn, process_close,
+    process_exit_info, process_wait, ProcessExitInfo, EXIT_REASON_LIMIT_EXCEEDED, LIMIT_CPU_TIME,
+};
+use std::ffi::CString;
+
+#[test]
+fn test_limit_applied_to_child() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "ulimit -n; ulimit -c"])
+        .capture_stdout(true)
+        .limit(Resource::OpenFiles, 64, 64)
+        .limit(Resource::CoreSize, 0, UNLIMITED)
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "64\n0\n");
+}
+
+#[test]
+fn test_limit_replaces_earlier_limit() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "ulimit -n"])
+        .capture_stdout(true)
+        .limit(Resource::OpenFiles, 64, 64)
+        .limit(Resource::OpenFiles, 32, 32)
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "32\n");
+}
+
+#[test]
+fn test_cpu_time_limit_reported() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "while :; do :; done"])
+        .limit(Resource::CpuTime, 1, 5)
+        .spawn()
+        .unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), -1);
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.code, None);
+    assert_eq!(info.signal, Some(libc::SIGXCPU));
+    assert_eq!(info.reason, TerminationReason::LimitExceeded(Resource::CpuTime));
+}
+
+#[test]
+fn test_file_size_limit_reported() {
+    let path = std::env::temp_dir().join(format!("pw_limit_{}.bin", std::process::id()));
+    let script = format!("exec head -c 100000 /dev/zero > '{}'", path.display());
+    
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", &script])
+        .limit(Resource::FileSize, 4096, 4096)
+        .spawn()
+        .unwrap();
+    
+    proc.wait().unwrap();
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.reason, TerminationReason::LimitExceeded(Resource::FileSize));
+    assert!(std::fs::metadata(&path).unwrap().len() <= 4096);
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_exit_info_without_limits() {
+    let mut proc = ProcessBuilder::new("sh").args(&["-c", "exit 3"]).spawn().unwrap();
+    proc.wait().unwrap();
+    
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.code, Some(3));
+    assert_eq!(info.signal, None);
+    assert_eq!(info.reason, TerminationReason::Exited);
+}
+
+#[test]
+fn test_hard_cpu_time_limit_reported() {
+    // Ignoring SIGXCPU lets the process run into the hard limit's SIGKILL
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "trap '' XCPU; while :; do :; done"])
+        .limit(Resource::CpuTime, 1, 2)
+        .spawn()
+        .unwrap();
+    
+    proc.wait().unwrap();
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.signal, Some(libc::SIGKILL));
+    assert_eq!(info.reason, TerminationReason::LimitExceeded(Resource::CpuTime));
+}
+
+#[test]
+fn test_outside_kill_not_attributed_to_limit() {
+    let mut proc = ProcessBuilder::new("sleep")
+        .arg("10")
+        .limit(Resource::CpuTime, 10, 10)
+        .spawn()
+        .unwrap();
+    
+    unsafe { libc::kill(proc.id().unwrap() as libc::pid_t, libc::SIGKILL) };
+    proc.wait().unwrap();
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.signal, Some(libc::SIGKILL));
+    assert_eq!(info.reason, TerminationReason::Signaled);
+}
+
+#[test]
+fn test_close_not_attributed_to_limit() {
+    let mut proc = ProcessBuilder::new("sleep")
+        .arg("10")
+        .limit(Resource::CpuTime, 10, 10)
+        .spawn()
+        .unwrap();
+    assert!(proc.exit_info().is_none());
+    
+    proc.close().unwrap();
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.signal, Some(libc::SIGKILL));
+    assert_eq!(info.reason, TerminationReason::Signaled);
+}
+
+#[test]
+fn test_ffi_limit_and_exit_info() {
+    let program = CString::new("sh").unwrap();
+    let arg = CString::new("-c").unwrap();
+    let script = CString::new("while :; do :; done").unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        assert_eq!(process_builder_limit(builder, 99, 1, 1), -1);
+        assert_eq!(process_builder_limit(builder, LIMIT_CPU_TIME, 1, 5), 0);
+        
+        process_builder_arg(builder, arg.as_ptr());
+        process_builder_arg(builder, script.as_ptr());
+        let proc = process_builder_spawn(builder);
+        process_builder_free(builder);
+        assert!(!proc.is_null());
+        
+        let mut info = ProcessExitInfo::default();
+        assert_eq!(process_exit_info(proc, &mut info), 0);
+        
+        assert_eq!(process_wait(proc), -1);
+        assert_eq!(process_exit_info(proc, &mut info), 1);
+        assert_eq!(info.reason, EXIT_REASON_LIMIT_EXCEEDED);
+        assert_eq!(info.limit, LIMIT_CPU_TIME);
+        assert_eq!(info.signal, libc::SIGXCPU);
+        assert_eq!(info.code, -1);
+        
+        process_close(proc);
+    }
+}
@@ -1,3 +1,28 @@
+mod common;
+
 mod process_test;
 mod ffi_test;
-mod integration_test; 
\ No newline at end of file
+mod integration_test;
+mod replay_test;
+mod probe_test;
+mod which_test;
+mod output_test;
+mod expect_test;
+mod pty_test;
+mod limits_test;
+mod priority_test;
+mod cgroup_test;
+mod usage_test;
+mod metrics_test;
+mod watchdog_test;
+mod supervisor_test;
+mod pool_test;
+mod pipeline_test;
+mod reactor_test;
+mod reaper_test;
+mod backend_test;
+mod testchild_test;
+mod logging_test;
+mod cli_test;
+mod profile_test;
+mod sandbox_test;
@@ -0,0 +1,148 @@
+#![cfg(target_os = "linux")]
+
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError};
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_builder_process_group,
+    process_builder_spawn, process_close, process_metrics, process_start_sampler, ProcessMetricsSample,
+};
+use std::ffi::CString;
+use std::thread;
+use std::time::Duration;
+
+const INTERVAL: Duration = Duration::from_millis(50);
+
+/// Processes in the process group `pgid` that have not exited, ignoring zombies
+fn live_members(pgid: u32) -> usize {
+    std::fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok())
+        .filter(|stat| {
+            let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 1..].split_whitespace().collect();
+            fields[0] != "Z" && fields[2] == pgid.to_string()
+        })
+        .count()
+}
+
+#[test]
+fn test_sampler_reports_busy_process() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "while :; do :; done"])
+        .spawn()
+        .unwrap();
+    assert!(proc.metrics().is_none());
+    
+    proc.start_sampler(INTERVAL, 4).unwrap();
+    thread::sleep(Duration::from_millis(500));
+    
+    let metrics = proc.metrics().unwrap();
+    assert!(metrics.latest.cpu_percent > 5.0, "{:?}", metrics);
+    assert!(metrics.average.cpu_percent > 5.0, "{:?}", metrics);
+    assert!(metrics.latest.rss_bytes > 0);
+    assert!(metrics.latest.vm_bytes >= metrics.latest.rss_bytes);
+    assert_eq!(metrics.latest.threads, 1);
+    assert_eq!(metrics.latest.processes, 1);
+    assert!(metrics.group_latest.is_none());
+    assert!(metrics.group_average.is_none());
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_sampler_idle_process() {
+    let mut proc = ProcessBuilder::new("sleep").arg("10").spawn().unwrap();
+    proc.start_sampler(INTERVAL, 4).unwrap();
+    thread::sleep(Duration::from_millis(300));
+    
+    let metrics = proc.metrics().unwrap();
+    assert!(metrics.average.cpu_percent < 20.0, "{:?}", metrics);
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_sampler_covers_process_group() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "sleep 30 & sleep 30 & wait"])
+        .process_group(true)
+        .spawn()
+        .unwrap();
+    let pgid = proc.id().unwrap();
+    
+    proc.start_sampler(INTERVAL, 4).unwrap();
+    thread::sleep(Duration::from_millis(400));
+    
+    let metrics = proc.metrics().unwrap();
+    assert_eq!(metrics.latest.processes, 1);
+    let group = metrics.group_latest.unwrap();
+    assert_eq!(group.processes, 3);
+    assert!(group.rss_bytes > metrics.latest.rss_bytes);
+    assert!(metrics.group_average.is_some());
+    
+    // Closing terminates the whole group
+    assert_eq!(live_members(pgid), 3);
+    proc.close().unwrap();
+    for _ in 0..100 {
+        if live_members(pgid) == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(live_members(pgid), 0);
+}
+
+#[test]
+fn test_sampler_keeps_samples_after_exit() {
+    let mut proc = ProcessBuilder::new("sleep").arg("0.3").spawn().unwrap();
+    proc.start_sampler(INTERVAL, 10).unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), 0);
+    thread::sleep(INTERVAL * 2);
+    assert!(proc.metrics().is_some());
+}
+
+#[test]
+fn test_sampler_on_finished_process() {
+    let mut proc = ProcessBuilder::new("true").spawn().unwrap();
+    proc.wait().unwrap();
+    
+    assert!(matches!(proc.start_sampler(INTERVAL, 4), Err(ProcessError::ProcessFinished)));
+    assert!(proc.metrics().is_none());
+}
+
+#[test]
+fn test_ffi_metrics() {
+    let program = CString::new("sleep").unwrap();
+    let seconds = CString::new("10").unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, seconds.as_ptr());
+        let proc = process_builder_spawn(builder);
+        assert!(!proc.is_null());
+        
+        let mut latest = ProcessMetricsSample::default();
+        let mut average = ProcessMetricsSample::default();
+        assert_eq!(process_metrics(proc, 0, &mut latest, &mut average), 0);
+        
+        assert_eq!(process_start_sampler(proc, 50, 4), 0);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(process_metrics(proc, 0, &mut latest, &mut average), 1);
+        assert_eq!(latest.processes, 1);
+        assert!(latest.rss_bytes > 0);
+        
+        // Not spawned in process-group mode
+        assert_eq!(process_metrics(proc, 1, &mut latest, &mut average), -1);
+        process_close(proc);
+        
+        // The same builder in process-group mode
+        process_builder_process_group(builder, 1);
+        let proc = process_builder_spawn(builder);
+        process_builder_free(builder);
+        assert_eq!(process_start_sampler(proc, 50, 4), 0);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(process_metrics(proc, 1, &mut latest, &mut average), 1);
+        assert_eq!(latest.processes, 1);
+        process_close(proc);
+    }
+}
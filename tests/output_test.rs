@@ -0,0 +1,241 @@
+use betahub_process_wrapper::output::{backup_path, FileSink, OutputSink, RotatingFile, StreamId};
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_builder_record_events,
+    process_builder_spawn, process_builder_stderr_file, process_close, process_wait,
+    process_write_event_log,
+};
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_stderr_to_file() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("stderr.log");
+    
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "echo to the log 1>&2"])
+        .stderr(OutputSink::File(FileSink::new(&log_path)))
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert!(output.stderr.is_empty());
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "to the log\n");
+}
+
+#[test]
+fn test_file_append_and_truncate() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("stdout.log");
+    fs::write(&log_path, "previous\n").unwrap();
+    
+    let run = |sink: FileSink| {
+        ProcessBuilder::new("echo")
+            .arg("next")
+            .stdout(OutputSink::File(sink))
+            .spawn()
+            .unwrap()
+            .wait_with_output()
+            .unwrap();
+    };
+    
+    run(FileSink::new(&log_path));
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "previous\nnext\n");
+    
+    run(FileSink::new(&log_path).append(false));
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "next\n");
+}
+
+#[test]
+fn test_file_tee() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("stderr.log");
+    
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "echo both places 1>&2"])
+        .stderr(OutputSink::File(FileSink::new(&log_path).tee(true)))
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.stderr, b"both places\n");
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "both places\n");
+}
+
+#[test]
+fn test_rotating_file() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("rotated.log");
+    
+    let mut file = RotatingFile::open(FileSink::new(&log_path).rotate(10, 2)).unwrap();
+    for line in ["first...\n", "second..\n", "third...\n", "fourth..\n"] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+    
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "fourth..\n");
+    assert_eq!(fs::read_to_string(backup_path(&log_path, 1)).unwrap(), "third...\n");
+    assert_eq!(fs::read_to_string(backup_path(&log_path, 2)).unwrap(), "second..\n");
+    assert!(!backup_path(&log_path, 3).exists());
+}
+
+#[test]
+fn test_rotation_without_backups() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("rotated.log");
+    
+    let mut file = RotatingFile::open(FileSink::new(&log_path).rotate(10, 0)).unwrap();
+    file.write_all(b"first...\n").unwrap();
+    file.write_all(b"second..\n").unwrap();
+    
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "second..\n");
+    assert!(!backup_path(&log_path, 1).exists());
+}
+
+#[test]
+fn test_process_output_rotation() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("stderr.log");
+    
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "for i in 1 2 3 4 5 6 7 8; do echo line $i 1>&2; sleep 0.01; done"])
+        .stderr(OutputSink::File(FileSink::new(&log_path).rotate(16, 3)))
+        .spawn()
+        .unwrap();
+    proc.wait_with_output().unwrap();
+    
+    assert!(fs::metadata(&log_path).unwrap().len() > 0);
+    assert!(backup_path(&log_path, 1).exists());
+    assert!(!backup_path(&log_path, 4).exists());
+    
+    // The newest output ends up in the live file
+    let newest = fs::read_to_string(&log_path).unwrap();
+    assert!(newest.ends_with("line 8\n"));
+}
+
+#[test]
+fn test_ffi_stderr_file() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("ffi.log");
+    
+    let program = CString::new("sh").unwrap();
+    let arg1 = CString::new("-c").unwrap();
+    let arg2 = CString::new("echo from ffi 1>&2").unwrap();
+    let path = CString::new(log_path.to_str().unwrap()).unwrap();
+    
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    unsafe {
+        process_builder_arg(builder, arg1.as_ptr());
+        process_builder_arg(builder, arg2.as_ptr());
+        assert_eq!(process_builder_stderr_file(builder, path.as_ptr(), 0, 0, 0, 0), 0);
+    }
+    
+    let proc = unsafe { process_builder_spawn(builder) };
+    assert!(!proc.is_null());
+    unsafe { process_builder_free(builder) };
+    
+    assert_eq!(unsafe { process_wait(proc) }, 0);
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "from ffi\n");
+    
+    unsafe { process_close(proc) };
+}
+
+#[test]
+fn test_event_log_interleaved() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "read line; echo out; sleep 0.05; echo err 1>&2; sleep 0.05; echo out2"])
+        .capture_stdout(true)
+        .record_events(16)
+        .spawn()
+        .unwrap();
+    
+    proc.write_stdin(b"go\n").unwrap();
+    proc.wait_with_output().unwrap();
+    
+    let events = proc.events();
+    let streams: Vec<_> = events.iter().map(|event| event.stream).collect();
+    assert_eq!(streams, vec![StreamId::Stdin, StreamId::Stdout, StreamId::Stderr, StreamId::Stdout]);
+    
+    // Stdin writes record only their length
+    assert_eq!(events[0].len, 3);
+    assert!(events[0].data.is_empty());
+    assert_eq!(events[2].data, b"err\n");
+    
+    assert!(events.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+}
+
+#[test]
+fn test_event_log_capacity() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "for i in 1 2 3 4 5; do echo $i; sleep 0.01; done"])
+        .capture_stdout(true)
+        .record_events(2)
+        .spawn()
+        .unwrap();
+    proc.wait_with_output().unwrap();
+    
+    let data: Vec<_> = proc.events().into_iter().map(|event| event.data).collect();
+    assert_eq!(data, vec![b"4\n".to_vec(), b"5\n".to_vec()]);
+}
+
+#[test]
+fn test_event_log_disabled() {
+    let mut proc = ProcessBuilder::new("echo").capture_stdout(true).spawn().unwrap();
+    proc.wait_with_output().unwrap();
+    assert!(proc.events().is_empty());
+}
+
+#[test]
+fn test_event_log_file_sink_recorded() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("stdout.log");
+    
+    let mut proc = ProcessBuilder::new("echo")
+        .arg("logged")
+        .stdout(OutputSink::File(FileSink::new(&log_path)))
+        .record_events(4)
+        .spawn()
+        .unwrap();
+    proc.wait_with_output().unwrap();
+    
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "logged\n");
+    assert_eq!(proc.events()[0].data, b"logged\n");
+}
+
+#[test]
+fn test_event_log_jsonl() {
+    let dir = tempdir().unwrap();
+    let jsonl_path = dir.path().join("events.jsonl");
+    
+    let program = CString::new("sh").unwrap();
+    let arg1 = CString::new("-c").unwrap();
+    let arg2 = CString::new("echo '\"quoted\"' 1>&2").unwrap();
+    let path = CString::new(jsonl_path.to_str().unwrap()).unwrap();
+    
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    unsafe {
+        process_builder_arg(builder, arg1.as_ptr());
+        process_builder_arg(builder, arg2.as_ptr());
+        process_builder_record_events(builder, 8);
+    }
+    let proc = unsafe { process_builder_spawn(builder) };
+    unsafe { process_builder_free(builder) };
+    
+    assert_eq!(unsafe { process_wait(proc) }, 0);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(unsafe { process_write_event_log(proc, path.as_ptr()) }, 0);
+    unsafe { process_close(proc) };
+    
+    let contents = fs::read_to_string(&jsonl_path).unwrap();
+    let lines: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["stream"], "stderr");
+    assert_eq!(lines[0]["data"], "\"quoted\"\n");
+    assert_eq!(lines[0]["len"], 9);
+    assert!(lines[0]["time"].as_f64().unwrap() >= 0.0);
+}
@@ -0,0 +1,182 @@
+use betahub_process_wrapper::pipeline::{pipefail, Pipeline, RunningPipeline};
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_capture_stdout, process_builder_free, process_builder_new,
+    process_pipeline_add, process_pipeline_close, process_pipeline_close_stdin, process_pipeline_free,
+    process_pipeline_new, process_pipeline_read_stdout, process_pipeline_spawn, process_pipeline_wait,
+    process_pipeline_write_stdin,
+};
+use std::ffi::CString;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn command(program: &str, args: &[&str]) -> ProcessBuilder {
+    let mut builder = ProcessBuilder::new(program);
+    builder.args(args);
+    builder
+}
+
+fn capturing(program: &str, args: &[&str]) -> ProcessBuilder {
+    let mut builder = command(program, args);
+    builder.capture_stdout(true);
+    builder
+}
+
+/// Collect output with `read` until it equals `expected`, for up to five seconds
+fn read_until(expected: &[u8], mut read: impl FnMut(&mut [u8]) -> usize) -> Vec<u8> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut output = Vec::new();
+    while output != expected && Instant::now() < deadline {
+        let mut buf = [0u8; 4096];
+        let bytes_read = read(&mut buf);
+        output.extend_from_slice(&buf[..bytes_read]);
+        if bytes_read == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    output
+}
+
+fn read_stdout(running: &mut RunningPipeline, expected: &[u8]) -> Vec<u8> {
+    read_until(expected, |buf| running.read_stdout(buf).unwrap())
+}
+
+#[test]
+fn test_pipefail() {
+    assert_eq!(pipefail(&[]), 0);
+    assert_eq!(pipefail(&[0, 0]), 0);
+    assert_eq!(pipefail(&[3, 0]), 3);
+    assert_eq!(pipefail(&[3, 0, 5, 0]), 5);
+    assert_eq!(pipefail(&[-1, 0]), -1);
+}
+
+#[test]
+fn test_pipeline_connects_stages() {
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("printf", &["hello world"]))
+        .stage(capturing("tr", &["a-z", "A-Z"]));
+    assert_eq!(pipeline.len(), 2);
+    
+    let mut running = pipeline.spawn().unwrap();
+    assert_eq!(running.wait().unwrap(), 0);
+    assert_eq!(read_stdout(&mut running, b"HELLO WORLD"), b"HELLO WORLD");
+    running.close().unwrap();
+}
+
+#[test]
+fn test_pipeline_stdin_and_eof() {
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("cat", &[]))
+        .stage(command("tr", &["a-z", "A-Z"]))
+        .stage(capturing("cat", &[]));
+    let mut running = pipeline.spawn().unwrap();
+    
+    running.write_stdin(b"abc\n").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert!(running.is_running());
+    
+    // EOF travels down the chain, flushing tr's buffered output, and every stage exits
+    running.close_stdin();
+    assert_eq!(running.wait_all().unwrap(), vec![0, 0, 0]);
+    assert!(!running.is_running());
+    assert_eq!(read_stdout(&mut running, b"ABC\n"), b"ABC\n");
+}
+
+#[test]
+fn test_pipeline_exit_status() {
+    let mut pipeline = Pipeline::new();
+    pipeline.stage(command("sh", &["-c", "exit 3"])).stage(command("cat", &[]));
+    let mut running = pipeline.spawn().unwrap();
+    assert_eq!(running.wait_all().unwrap(), vec![3, 0]);
+    assert_eq!(running.wait().unwrap(), 3);
+    
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("sh", &["-c", "exit 2"]))
+        .stage(command("sh", &["-c", "cat >/dev/null; exit 5"]));
+    assert_eq!(pipeline.spawn().unwrap().wait().unwrap(), 5);
+}
+
+#[test]
+fn test_pipeline_streams_large_output() {
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("head", &["-c", "1000000", "/dev/zero"]))
+        .stage(capturing("wc", &["-c"]));
+    let mut running = pipeline.spawn().unwrap();
+    
+    assert_eq!(running.wait().unwrap(), 0);
+    let output = read_until(b"1000000\n", |buf| running.read_stdout(buf).unwrap());
+    assert_eq!(String::from_utf8_lossy(&output).trim(), "1000000");
+}
+
+#[test]
+fn test_pipeline_stage_stderr() {
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("sh", &["-c", "echo first >&2"]))
+        .stage(command("sh", &["-c", "cat; echo second >&2"]));
+    let mut running = pipeline.spawn().unwrap();
+    running.wait().unwrap();
+    
+    assert_eq!(read_until(b"first\n", |buf| running.read_stderr(0, buf).unwrap()), b"first\n");
+    assert_eq!(read_until(b"second\n", |buf| running.read_stderr(1, buf).unwrap()), b"second\n");
+    assert!(running.read_stderr(2, &mut [0u8; 16]).is_err());
+    assert!(running.stage(1).unwrap().exit_info().is_some());
+}
+
+#[test]
+fn test_pipeline_spawn_errors() {
+    assert!(Pipeline::new().spawn().is_err());
+    
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .stage(command("sleep", &["10"]))
+        .stage(command("betahub_pw_missing_program", &[]));
+    let started = Instant::now();
+    assert!(pipeline.spawn().is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_ffi_pipeline() {
+    let cat = CString::new("cat").unwrap();
+    let tr = CString::new("tr").unwrap();
+    let from = CString::new("a-z").unwrap();
+    let to = CString::new("A-Z").unwrap();
+    
+    unsafe {
+        let pipeline = process_pipeline_new();
+        assert!(process_pipeline_spawn(pipeline).is_null());
+        
+        let first = process_builder_new(cat.as_ptr());
+        let second = process_builder_new(tr.as_ptr());
+        process_builder_arg(second, from.as_ptr());
+        process_builder_arg(second, to.as_ptr());
+        process_builder_capture_stdout(second, 1);
+        assert_eq!(process_pipeline_add(pipeline, first), 0);
+        assert_eq!(process_pipeline_add(pipeline, second), 0);
+        process_builder_free(first);
+        process_builder_free(second);
+        
+        let running = process_pipeline_spawn(pipeline);
+        process_pipeline_free(pipeline);
+        assert!(!running.is_null());
+        
+        let data = b"pipe\n";
+        assert_eq!(process_pipeline_write_stdin(running, data.as_ptr(), data.len()), 5);
+        process_pipeline_close_stdin(running);
+        
+        let mut codes = [-2; 2];
+        assert_eq!(process_pipeline_wait(running, codes.as_mut_ptr(), codes.len()), 0);
+        assert_eq!(codes, [0, 0]);
+        
+        let output = read_until(b"PIPE\n", |buf| {
+            process_pipeline_read_stdout(running, buf.as_mut_ptr(), buf.len()) as usize
+        });
+        assert_eq!(output, b"PIPE\n");
+        process_pipeline_close(running);
+    }
+}
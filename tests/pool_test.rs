@@ -0,0 +1,224 @@
+use betahub_process_wrapper::pool::{JobStatus, ProcessPool};
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_capture_stdout, process_builder_free, process_builder_new,
+    process_pool_free, process_pool_job_remove, process_pool_job_result, process_pool_job_status,
+    process_pool_job_stdout, process_pool_new, process_pool_progress, process_pool_submit, process_pool_wait_all,
+    ProcessJobResult, ProcessPoolProgress, POOL_JOB_COMPLETED,
+};
+use crate::common::eventually;
+use libc::c_void;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn shell(script: &str) -> ProcessBuilder {
+    let mut builder = ProcessBuilder::new("sh");
+    builder.args(&["-c", script]);
+    builder
+}
+
+#[test]
+fn test_pool_limits_concurrency() {
+    let max_running = Arc::new(AtomicU64::new(0));
+    let observed = Arc::clone(&max_running);
+    let pool = ProcessPool::with_progress(2, move |_, _, progress| {
+        observed.fetch_max(progress.running, Ordering::SeqCst);
+    });
+    
+    let started = Instant::now();
+    let jobs: Vec<_> = (0..4).map(|_| pool.submit(&shell("sleep 0.3")).unwrap()).collect();
+    assert!(pool.wait_all(Some(Duration::from_secs(10))));
+    
+    assert!(started.elapsed() >= Duration::from_millis(600));
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    for job in jobs {
+        assert_eq!(pool.status(job), Some(JobStatus::Completed));
+    }
+    
+    let progress = pool.progress();
+    assert_eq!(progress.submitted, 4);
+    assert_eq!(progress.completed, 4);
+    assert_eq!(progress.done(), 4);
+}
+
+#[test]
+fn test_pool_collects_results() {
+    let pool = ProcessPool::new(2);
+    let mut builder = shell("echo out; echo err >&2; exit 3");
+    builder.capture_stdout(true);
+    
+    let job = pool.submit(&builder).unwrap();
+    let result = pool.wait(job, None).unwrap();
+    
+    assert_eq!(result.status, JobStatus::Completed);
+    assert_eq!(result.exit.unwrap().code, Some(3));
+    assert_eq!(result.stdout, b"out\n");
+    assert_eq!(result.stderr, b"err\n");
+    assert!(result.duration > Duration::ZERO);
+    assert!(result.error.is_none());
+}
+
+#[test]
+fn test_pool_reports_spawn_failure() {
+    let pool = ProcessPool::new(1);
+    let job = pool.submit(&ProcessBuilder::new("betahub_pw_missing_program")).unwrap();
+    
+    let result = pool.wait(job, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(result.status, JobStatus::Failed);
+    assert!(result.exit.is_none());
+    assert!(result.error.is_some());
+    assert_eq!(pool.progress().failed, 1);
+}
+
+#[test]
+fn test_pool_cancels_jobs() {
+    let pool = ProcessPool::new(1);
+    let running = pool.submit(&shell("sleep 10")).unwrap();
+    let queued = pool.submit(&shell("sleep 10")).unwrap();
+    assert!(eventually(|| pool.status(running) == Some(JobStatus::Running)));
+    
+    // A queued job is cancelled right away and never runs
+    assert!(pool.cancel(queued));
+    assert_eq!(pool.status(queued), Some(JobStatus::Cancelled));
+    assert!(pool.result(queued).unwrap().exit.is_none());
+    
+    let started = Instant::now();
+    assert!(pool.cancel(running));
+    let result = pool.wait(running, Some(Duration::from_secs(5))).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(result.status, JobStatus::Cancelled);
+    #[cfg(unix)]
+    assert_eq!(result.exit.unwrap().signal, Some(libc::SIGKILL));
+    
+    assert!(!pool.cancel(running));
+    assert_eq!(pool.progress().cancelled, 2);
+}
+
+#[test]
+fn test_pool_reports_queued_cancel_from_worker() {
+    let caller = thread::current().id();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&reports);
+    let pool = ProcessPool::with_progress(1, move |job, status, progress| {
+        seen.lock().unwrap().push((job, status, progress.cancelled, thread::current().id()));
+    });
+    let running = pool.submit(&shell("sleep 10")).unwrap();
+    let queued = pool.submit(&shell("sleep 10")).unwrap();
+    assert!(eventually(|| pool.status(running) == Some(JobStatus::Running)));
+    
+    // Reported while the only worker is still busy with the running job
+    assert!(pool.cancel(queued));
+    assert!(eventually(|| {
+        reports.lock().unwrap().iter().any(|report| report.0 == queued)
+    }));
+    
+    let reports = reports.lock().unwrap();
+    let report = reports.iter().find(|report| report.0 == queued).unwrap();
+    assert_eq!(report.1, JobStatus::Cancelled);
+    assert_eq!(report.2, 1);
+    assert_ne!(report.3, caller);
+}
+
+#[test]
+fn test_pool_close_reports_cancellations() {
+    let caller = thread::current().id();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&reports);
+    let pool = ProcessPool::with_progress(1, move |job, status, _| {
+        seen.lock().unwrap().push((job, status, thread::current().id()));
+    });
+    let running = pool.submit(&shell("sleep 10")).unwrap();
+    let queued: Vec<_> = (0..2).map(|_| pool.submit(&shell("sleep 10")).unwrap()).collect();
+    assert!(eventually(|| pool.status(running) == Some(JobStatus::Running)));
+    
+    pool.close();
+    
+    let reports = reports.lock().unwrap();
+    for job in queued.iter().chain([&running]) {
+        assert!(reports.iter().any(|report| report.0 == *job && report.1 == JobStatus::Cancelled));
+    }
+    assert!(reports.iter().all(|report| report.2 != caller));
+}
+
+#[test]
+fn test_pool_wait_times_out() {
+    let pool = ProcessPool::new(1);
+    let job = pool.submit(&shell("sleep 10")).unwrap();
+    
+    assert!(pool.wait(job, Some(Duration::from_millis(100))).is_err());
+    assert!(!pool.wait_all(Some(Duration::from_millis(100))));
+    
+    // Closing cancels the running job
+    let started = Instant::now();
+    pool.close();
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_pool_remove() {
+    let pool = ProcessPool::new(1);
+    let job = pool.submit(&shell("sleep 0.2")).unwrap();
+    assert!(pool.remove(job).is_none());
+    
+    pool.wait(job, None).unwrap();
+    assert_eq!(pool.remove(job).unwrap().status, JobStatus::Completed);
+    assert!(pool.status(job).is_none());
+    assert!(pool.wait(job, None).is_err());
+}
+
+extern "C" fn count_update(user_data: *mut c_void, job: u64, _status: i32, progress: *const ProcessPoolProgress) {
+    assert!(job > 0);
+    assert_eq!(unsafe { (*progress).submitted }, 1);
+    let counter = unsafe { &*(user_data as *const AtomicUsize) };
+    counter.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn test_ffi_pool() {
+    let updates = AtomicUsize::new(0);
+    let program = CString::new("sh").unwrap();
+    let flag = CString::new("-c").unwrap();
+    let script = CString::new("printf hello").unwrap();
+    
+    unsafe {
+        let pool = process_pool_new(2, Some(count_update), &updates as *const AtomicUsize as *mut c_void);
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, flag.as_ptr());
+        process_builder_arg(builder, script.as_ptr());
+        process_builder_capture_stdout(builder, 1);
+        let job = process_pool_submit(pool, builder);
+        process_builder_free(builder);
+        assert!(job > 0);
+        
+        assert_eq!(process_pool_wait_all(pool, 5000), 0);
+        assert_eq!(process_pool_job_status(pool, job as u64), POOL_JOB_COMPLETED);
+        
+        let mut result = ProcessJobResult::default();
+        assert_eq!(process_pool_job_result(pool, job as u64, &mut result), 1);
+        assert_eq!(result.status, POOL_JOB_COMPLETED);
+        assert_eq!(result.ran, 1);
+        assert_eq!(result.exit.code, 0);
+        assert_eq!(result.stdout_len, 5);
+        
+        let mut buf = [0u8; 2];
+        assert_eq!(process_pool_job_stdout(pool, job as u64, buf.as_mut_ptr(), buf.len()), 5);
+        let mut buf = [0u8; 16];
+        assert_eq!(process_pool_job_stdout(pool, job as u64, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(&buf[..5], b"hello");
+        
+        let mut progress = ProcessPoolProgress::default();
+        assert_eq!(process_pool_progress(pool, &mut progress), 0);
+        assert_eq!(progress.completed, 1);
+        
+        assert_eq!(process_pool_job_remove(pool, job as u64), 0);
+        assert_eq!(process_pool_job_status(pool, job as u64), -1);
+        assert_eq!(process_pool_job_result(pool, job as u64, &mut result), -1);
+        process_pool_free(pool);
+    }
+    
+    // Running and completed
+    assert_eq!(updates.load(Ordering::SeqCst), 2);
+}
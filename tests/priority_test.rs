@@ -0,0 +1,143 @@
+#![cfg(target_os = "linux")]
+
+use betahub_process_wrapper::priority::{IoPriority, Priority, SchedPolicy};
+use betahub_process_wrapper::process::{Process, ProcessBuilder, ProcessError};
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_builder_priority, process_builder_spawn, process_close,
+    process_set_priority, IO_CLASS_IDLE, PRIORITY_UNCHANGED, SCHED_POLICY_BATCH,
+};
+use std::ffi::CString;
+use std::ptr;
+
+/// Fields of /proc/<pid>/stat following the command name, starting with the state (field 3)
+fn stat_fields(pid: u32) -> Vec<String> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+    let after_comm = &stat[stat.rfind(')').unwrap() + 1..];
+    after_comm.split_whitespace().map(str::to_string).collect()
+}
+
+fn nice_of(pid: u32) -> i32 {
+    stat_fields(pid)[19 - 3].parse().unwrap()
+}
+
+fn policy_of(pid: u32) -> i32 {
+    stat_fields(pid)[41 - 3].parse().unwrap()
+}
+
+fn ioprio_of(pid: u32) -> i64 {
+    unsafe { libc::syscall(libc::SYS_ioprio_get, 1, pid as libc::pid_t) }
+}
+
+fn cpus_of(pid: u32) -> String {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+    let line = status.lines().find(|line| line.starts_with("Cpus_allowed_list:")).unwrap();
+    line.split_whitespace().nth(1).unwrap().to_string()
+}
+
+fn own_nice() -> i32 {
+    nice_of(std::process::id())
+}
+
+fn spawn_sleep(builder: &mut ProcessBuilder) -> Process {
+    builder.arg("10").spawn().unwrap()
+}
+
+#[test]
+fn test_priority_applied_at_spawn() {
+    let mut proc = spawn_sleep(
+        ProcessBuilder::new("sleep")
+            .nice(19)
+            .sched_policy(SchedPolicy::Idle)
+            .io_priority(IoPriority::Idle)
+            .cpu_affinity(&[0]),
+    );
+    let pid = proc.id().unwrap();
+    
+    assert_eq!(nice_of(pid), 19);
+    assert_eq!(policy_of(pid), libc::SCHED_IDLE);
+    assert_eq!(ioprio_of(pid) >> 13, 3);
+    assert_eq!(cpus_of(pid), "0");
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_set_priority_on_running_process() {
+    let mut proc = spawn_sleep(&mut ProcessBuilder::new("sleep"));
+    let pid = proc.id().unwrap();
+    
+    let niceness = (own_nice() + 5).min(19);
+    proc.set_priority(&Priority {
+        nice: Some(niceness),
+        policy: Some(SchedPolicy::Batch),
+        io: Some(IoPriority::BestEffort(7)),
+        cpus: None,
+    })
+    .unwrap();
+    
+    assert_eq!(nice_of(pid), niceness);
+    assert_eq!(policy_of(pid), libc::SCHED_BATCH);
+    assert_eq!(ioprio_of(pid), (2 << 13) | 7);
+    
+    // Unset fields are left alone
+    proc.set_priority(&Priority {
+        io: Some(IoPriority::Idle),
+        ..Priority::default()
+    })
+    .unwrap();
+    assert_eq!(nice_of(pid), niceness);
+    assert_eq!(policy_of(pid), libc::SCHED_BATCH);
+    assert_eq!(ioprio_of(pid) >> 13, 3);
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_set_priority_on_finished_process() {
+    let mut proc = ProcessBuilder::new("true").spawn().unwrap();
+    proc.wait().unwrap();
+    
+    let result = proc.set_priority(&Priority {
+        nice: Some(10),
+        ..Priority::default()
+    });
+    assert!(matches!(result, Err(ProcessError::ProcessFinished)));
+}
+
+#[test]
+fn test_ffi_priority() {
+    let program = CString::new("sleep").unwrap();
+    let seconds = CString::new("10").unwrap();
+    let cpus = [0u32];
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, seconds.as_ptr());
+        
+        // Unknown policy and out-of-range I/O level are rejected
+        assert_eq!(process_builder_priority(builder, PRIORITY_UNCHANGED, 42, PRIORITY_UNCHANGED, 0, ptr::null(), 0), -1);
+        assert_eq!(process_builder_priority(builder, PRIORITY_UNCHANGED, PRIORITY_UNCHANGED, 2, 8, ptr::null(), 0), -1);
+        
+        assert_eq!(
+            process_builder_priority(builder, 19, PRIORITY_UNCHANGED, PRIORITY_UNCHANGED, 0, cpus.as_ptr(), cpus.len()),
+            0
+        );
+        let proc = process_builder_spawn(builder);
+        process_builder_free(builder);
+        assert!(!proc.is_null());
+        
+        let pid = (*proc).id().unwrap();
+        assert_eq!(nice_of(pid), 19);
+        assert_eq!(cpus_of(pid), "0");
+        
+        assert_eq!(
+            process_set_priority(proc, PRIORITY_UNCHANGED, SCHED_POLICY_BATCH, IO_CLASS_IDLE, 0, ptr::null(), 0),
+            0
+        );
+        assert_eq!(nice_of(pid), 19);
+        assert_eq!(policy_of(pid), libc::SCHED_BATCH);
+        assert_eq!(ioprio_of(pid) >> 13, 3);
+        
+        process_close(proc);
+    }
+}
@@ -0,0 +1,106 @@
+use betahub_process_wrapper::probe::{self, parse_listing, Capabilities};
+use betahub_process_wrapper::{process_probe_pick_encoder, process_probe_supports, PROBE_ENCODER};
+use std::ffi::CString;
+use tempfile::tempdir;
+
+const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+";
+
+const MUXERS: &str = "File formats:
+ D.. = Demuxing supported
+ .E. = Muxing supported
+ ..d = Is a device
+ ---
+  E  matroska        Matroska
+  E  mp4             MP4 (MPEG-4 Part 14)
+  E  mpegts          MPEG-TS (MPEG-2 Transport Stream)
+";
+
+const PIX_FMTS: &str = "Pixel formats:
+I.... = Supported Input  format for conversion
+.O... = Supported Output format for conversion
+FLAGS NAME            NB_COMPONENTS BITS_PER_PIXEL BIT_DEPTHS
+-----
+IO... yuv420p                3             12      8-8-8
+IO... rgb24                  3             24      8-8-8
+";
+
+#[test]
+fn test_parse_listings() {
+    let capabilities = Capabilities::from_listings(ENCODERS, MUXERS, PIX_FMTS);
+    
+    assert!(capabilities.has_encoder("libx264"));
+    assert!(capabilities.has_encoder("aac"));
+    assert!(!capabilities.has_encoder("Video"));
+    assert!(capabilities.has_muxer("mp4"));
+    assert!(!capabilities.has_muxer("Muxing"));
+    assert!(capabilities.has_pix_fmt("rgb24"));
+    assert!(!capabilities.has_pix_fmt("NAME"));
+}
+
+#[test]
+fn test_parse_listing_multiple_names() {
+    let names = parse_listing(" --\n D  mov,mp4,m4a      QuickTime / MOV\n");
+    assert_eq!(names.into_iter().collect::<Vec<_>>(), vec!["m4a", "mov", "mp4"]);
+}
+
+#[test]
+fn test_pick_encoder() {
+    let capabilities = Capabilities::from_listings(ENCODERS, MUXERS, PIX_FMTS);
+    
+    assert_eq!(capabilities.pick_encoder(&["h264_qsv", "h264_nvenc", "libx264"]), Some("h264_nvenc"));
+    assert_eq!(capabilities.pick_encoder(&["hevc_qsv"]), None);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_probe_fake_ffmpeg() {
+    use std::os::unix::fs::PermissionsExt;
+    
+    // A stand-in ffmpeg printing canned listings
+    let dir = tempdir().unwrap();
+    let script = dir.path().join("ffmpeg");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\ncase \"$2\" in\n-encoders) cat <<'EOF'\n{}EOF\n;;\n-muxers) cat <<'EOF'\n{}EOF\n;;\n-pix_fmts) cat <<'EOF'\n{}EOF\n;;\n*) exit 1;;\nesac\n",
+            ENCODERS, MUXERS, PIX_FMTS
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    
+    let script = script.to_str().unwrap();
+    let capabilities = probe::probe(script).unwrap();
+    assert_eq!(*capabilities, Capabilities::from_listings(ENCODERS, MUXERS, PIX_FMTS));
+    
+    // The second probe is served from the cache
+    let cached = probe::probe(script).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&capabilities, &cached));
+    
+    let ffmpeg = CString::new(script).unwrap();
+    let name = CString::new("libx264").unwrap();
+    assert_eq!(unsafe { process_probe_supports(ffmpeg.as_ptr(), PROBE_ENCODER, name.as_ptr()) }, 1);
+    
+    let candidates = [CString::new("h264_qsv").unwrap(), CString::new("libx264").unwrap()];
+    let candidate_ptrs = [candidates[0].as_ptr(), candidates[1].as_ptr()];
+    let picked = unsafe { process_probe_pick_encoder(ffmpeg.as_ptr(), candidate_ptrs.as_ptr(), candidate_ptrs.len()) };
+    assert_eq!(picked, 1);
+}
+
+#[test]
+fn test_probe_missing_ffmpeg() {
+    assert!(probe::probe("nonexistentcommand").is_err());
+    
+    let ffmpeg = CString::new("nonexistentcommand").unwrap();
+    let name = CString::new("libx264").unwrap();
+    assert_eq!(unsafe { process_probe_supports(ffmpeg.as_ptr(), PROBE_ENCODER, name.as_ptr()) }, -1);
+}
@@ -0,0 +1,248 @@
+use betahub_process_wrapper::exit::TerminationReason;
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError, Shutdown};
+use betahub_process_wrapper::profile::{Profile, StreamMode};
+use betahub_process_wrapper::watchdog::WatchdogAction;
+use betahub_process_wrapper::{process_close, process_start_profile, process_wait};
+use libc::c_char;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::ptr;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
+const RECORDING: &str = r#"
+program = "${ffmpeg}"
+args = ["-s", "${width}x${height}", "-i", "-", "${output}", "$$1"]
+cwd = "${project}/Recordings"
+stdout = "capture"
+stderr = "append:${project}/ffmpeg.log"
+timeout_ms = 60000
+stall_timeout_ms = 10000
+stall_action = "kill"
+
+[vars]
+ffmpeg = "ffmpeg"
+width = "1920"
+
+[env]
+FFREPORT = "file=${project}/report.log"
+
+[shutdown]
+stdin = "q"
+grace_ms = 5000
+"#;
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+fn invalid(text: &str) -> String {
+    match Profile::from_toml(text) {
+        Err(ProcessError::InvalidProfile(message)) => message,
+        other => panic!("expected an invalid profile, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_toml_profile() {
+    let profile = Profile::from_toml(RECORDING).unwrap();
+    
+    assert_eq!(profile.program, "${ffmpeg}");
+    assert_eq!(profile.stdin, StreamMode::Pipe);
+    assert_eq!(profile.stdout, StreamMode::Capture);
+    assert_eq!(profile.stderr, StreamMode::Append("${project}/ffmpeg.log".to_string()));
+    assert_eq!(profile.timeout, Some(Duration::from_secs(60)));
+    assert_eq!(profile.stall, Some((Duration::from_secs(10), WatchdogAction::Kill)));
+    assert_eq!(profile.shutdown, Some(Shutdown::new(Duration::from_secs(5)).stdin("q")));
+    
+    let builder = profile
+        .builder(&vars(&[("project", "/game"), ("height", "1080"), ("output", "out.mp4"), ("width", "1280")]))
+        .unwrap();
+    assert_eq!(builder.get_program(), "ffmpeg");
+    assert_eq!(builder.get_args(), ["-s", "1280x1080", "-i", "-", "out.mp4", "$1"]);
+    assert_eq!(builder.get_current_dir(), Some("/game/Recordings".as_ref()));
+    assert_eq!(
+        builder.get_envs(),
+        [("FFREPORT".to_string(), Some("file=/game/report.log".to_string()))]
+    );
+}
+
+#[test]
+fn test_json_profile_matches_toml() {
+    let json = r#"{
+        "program": "${ffmpeg}",
+        "args": ["-s", "${width}x${height}", "-i", "-", "${output}", "$$1"],
+        "cwd": "${project}/Recordings",
+        "stdout": "capture",
+        "stderr": "append:${project}/ffmpeg.log",
+        "timeout_ms": 60000,
+        "stall_timeout_ms": 10000,
+        "stall_action": "kill",
+        "vars": { "ffmpeg": "ffmpeg", "width": "1920" },
+        "env": { "FFREPORT": "file=${project}/report.log" },
+        "shutdown": { "stdin": "q", "grace_ms": 5000 }
+    }"#;
+    
+    assert_eq!(Profile::from_json(json).unwrap(), Profile::from_toml(RECORDING).unwrap());
+}
+
+#[test]
+fn test_invalid_profiles() {
+    assert!(invalid("args = []").contains("program"));
+    assert!(invalid("program = \"x\"\nargz = []").contains("argz"));
+    assert!(invalid("program = \"x\"\nargs = [1]").contains("args"));
+    assert!(invalid("program = \"x\"\nstdout = \"tee\"").contains("stdout"));
+    assert!(invalid("program = \"x\"\nstdin = \"capture\"").contains("stdin"));
+    assert!(invalid("program = \"x\"\ntimeout_ms = -1").contains("timeout_ms"));
+    assert!(invalid("program = \"x\"\nstall_action = \"kill\"").contains("stall_timeout_ms"));
+    assert!(invalid("program = \"x\"\n[shutdown]\nsignl = 2").contains("shutdown.signl"));
+    assert!(invalid("program = \"x\"\nargs = [\"${unclosed\"]").contains("unclosed"));
+    assert!(invalid("program = \"x\"\nargs = [\"$HOME\"]").contains("$HOME"));
+    assert!(invalid("program = \"x\"\nargs = [\"${bad-name}\"]").contains("bad-name"));
+    assert!(!invalid("program = ").is_empty());
+    
+    assert!(matches!(Profile::from_json("[]"), Err(ProcessError::InvalidProfile(_))));
+}
+
+#[test]
+fn test_missing_variable() {
+    let profile = Profile::from_toml(RECORDING).unwrap();
+    
+    match profile.builder(&vars(&[("project", "/game")])) {
+        Err(ProcessError::InvalidProfile(message)) => assert!(message.contains("height")),
+        other => panic!("expected a missing variable, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_spawn_from_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("child.toml");
+    fs::write(
+        &path,
+        format!(
+            "program = {:?}\nargs = [\"out\", \"${{greeting}}\", \"exit\", \"3\"]\nstdout = \"capture\"\n",
+            TESTCHILD
+        ),
+    )
+    .unwrap();
+    
+    let profile = Profile::load(&path).unwrap();
+    let mut proc = profile.spawn(&vars(&[("greeting", "hello")])).unwrap();
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 3);
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[test]
+fn test_file_output_truncates_and_append_appends() {
+    let dir = TempDir::new().unwrap();
+    let truncated = dir.path().join("truncated.log");
+    let appended = dir.path().join("appended.log");
+    fs::write(&truncated, "OLD CONTENT\n").unwrap();
+    fs::write(&appended, "OLD CONTENT\n").unwrap();
+    
+    let profile = Profile::from_toml(&format!(
+        "program = {:?}\nargs = [\"out\", \"new\", \"err\", \"new\"]\nstdout = \"file:${{out}}\"\nstderr = \"append:${{err}}\"\n",
+        TESTCHILD
+    ))
+    .unwrap();
+    let mut proc = profile
+        .spawn(&vars(&[
+            ("out", truncated.to_str().unwrap()),
+            ("err", appended.to_str().unwrap()),
+        ]))
+        .unwrap();
+    assert_eq!(proc.wait().unwrap(), 0);
+    proc.close().unwrap();
+    
+    assert_eq!(fs::read_to_string(truncated).unwrap(), "new\n");
+    assert_eq!(fs::read_to_string(appended).unwrap(), "OLD CONTENT\nnew\n");
+}
+
+#[test]
+fn test_timeout_kills_process() {
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .arg("hang")
+        .timeout(Duration::from_millis(100))
+        .spawn()
+        .unwrap();
+    
+    let started = Instant::now();
+    assert_ne!(proc.wait().unwrap(), 0);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(proc.exit_info().unwrap().reason, TerminationReason::TimedOut);
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_timeout_not_reached() {
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["exit", "4"])
+        .timeout(Duration::from_secs(60))
+        .spawn()
+        .unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), 4);
+    assert_eq!(proc.exit_info().unwrap().reason, TerminationReason::Exited);
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_shutdown_lets_process_finish() {
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["read-line", "exit", "7"])
+        .shutdown(Shutdown::new(Duration::from_secs(5)).stdin("q\n"))
+        .spawn()
+        .unwrap();
+    
+    proc.close().unwrap();
+    assert_eq!(proc.exit_info().unwrap().code, Some(7));
+}
+
+#[test]
+fn test_shutdown_kills_after_grace() {
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["ignore-term", "out", "ready", "hang"])
+        .capture_stdout(true)
+        .shutdown(Shutdown::new(Duration::from_millis(200)).signal(libc::SIGTERM))
+        .spawn()
+        .unwrap();
+    proc.wait_for_output(&betahub_process_wrapper::expect::Pattern::literal("ready"), Duration::from_secs(5))
+        .unwrap();
+    
+    let started = Instant::now();
+    proc.close().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    let exit = proc.exit_info().unwrap();
+    assert_eq!(exit.signal, Some(libc::SIGKILL));
+}
+
+#[test]
+fn test_ffi_start_profile() {
+    let json = CString::new(format!(
+        r#"{{ "program": {:?}, "args": ["exit", "${{code}}"], "stdin": "null" }}"#,
+        TESTCHILD
+    ))
+    .unwrap();
+    let vars = [CString::new("code=5").unwrap()];
+    let var_ptrs: Vec<*const c_char> = vars.iter().map(|var| var.as_ptr()).collect();
+    
+    unsafe {
+        let proc = process_start_profile(json.as_ptr(), var_ptrs.as_ptr(), var_ptrs.len());
+        assert!(!proc.is_null());
+        assert_eq!(process_wait(proc), 5);
+        process_close(proc);
+        
+        // Missing variable, missing file, malformed variable
+        assert!(process_start_profile(json.as_ptr(), ptr::null(), 0).is_null());
+        let missing = CString::new("/nonexistent/profile.toml").unwrap();
+        assert!(process_start_profile(missing.as_ptr(), ptr::null(), 0).is_null());
+        let bad = [CString::new("code").unwrap()];
+        let bad_ptrs: Vec<*const c_char> = bad.iter().map(|var| var.as_ptr()).collect();
+        assert!(process_start_profile(json.as_ptr(), bad_ptrs.as_ptr(), 1).is_null());
+    }
+}
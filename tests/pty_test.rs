@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+use betahub_process_wrapper::expect::Pattern;
+use betahub_process_wrapper::process::{ProcessBuilder, ProcessError};
+use betahub_process_wrapper::pty::PtySize;
+use betahub_process_wrapper::{
+    process_builder_free, process_builder_new, process_builder_pty, process_builder_spawn, process_close,
+    process_resize_pty, process_start,
+};
+use std::ffi::CString;
+use std::time::Duration;
+
+#[test]
+fn test_pty_is_terminal() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "test -t 0 && test -t 1 && test -t 2 && echo on a tty 1>&2"])
+        .pty(PtySize::default())
+        .spawn()
+        .unwrap();
+    
+    // Stderr arrives through the terminal, read as stdout
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "on a tty\r\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_pty_window_size_and_resize() {
+    let mut proc = ProcessBuilder::new("sh")
+        .pty(PtySize { cols: 100, rows: 40 })
+        .spawn()
+        .unwrap();
+    
+    let size = Pattern::regex(r"(\d+) (\d+)\r\n").unwrap();
+    
+    proc.write_stdin(b"stty size\n").unwrap();
+    let found = proc.wait_for_output(&size, Duration::from_secs(5)).unwrap();
+    assert_eq!(found.text, "40 100\r\n");
+    
+    proc.resize_pty(PtySize { cols: 132, rows: 50 }).unwrap();
+    proc.write_stdin(b"stty size\n").unwrap();
+    let found = proc.wait_for_output(&size, Duration::from_secs(5)).unwrap();
+    assert_eq!(found.text, "50 132\r\n");
+    
+    proc.write_stdin(b"exit 7\n").unwrap();
+    assert_eq!(proc.wait().unwrap(), 7);
+}
+
+#[test]
+fn test_pty_close_stdin_sends_eof() {
+    let mut proc = ProcessBuilder::new("cat").pty(PtySize::default()).spawn().unwrap();
+    
+    proc.write_stdin(b"typed\n").unwrap();
+    proc.wait_for_output(&Pattern::literal("typed"), Duration::from_secs(5)).unwrap();
+    proc.close_stdin();
+    
+    assert_eq!(proc.wait().unwrap(), 0);
+}
+
+#[test]
+fn test_resize_without_pty() {
+    let mut proc = ProcessBuilder::new("sleep").arg("5").spawn().unwrap();
+    assert!(matches!(proc.resize_pty(PtySize::default()), Err(ProcessError::InvalidState)));
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_ffi_pty() {
+    let program = CString::new("cat").unwrap();
+    let builder = unsafe { process_builder_new(program.as_ptr()) };
+    unsafe { process_builder_pty(builder, 80, 24) };
+    let proc = unsafe { process_builder_spawn(builder) };
+    assert!(!proc.is_null());
+    unsafe { process_builder_free(builder) };
+    
+    assert_eq!(unsafe { process_resize_pty(proc, 120, 30) }, 0);
+    unsafe { process_close(proc) };
+    
+    // Processes started without a terminal cannot be resized
+    let cmd = CString::new("sleep 5").unwrap();
+    let proc = unsafe { process_start(cmd.as_ptr()) };
+    assert_eq!(unsafe { process_resize_pty(proc, 120, 30) }, -1);
+    unsafe { process_close(proc) };
+}
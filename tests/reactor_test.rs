@@ -0,0 +1,124 @@
+#![cfg(target_os = "linux")]
+
+use betahub_process_wrapper::process::{Process, ProcessBuilder};
+use betahub_process_wrapper::pty::PtySize;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Names of all threads of this process
+fn thread_names() -> Vec<String> {
+    fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter_map(|task| fs::read_to_string(task.ok()?.path().join("comm")).ok())
+        .map(|name| name.trim_end().to_string())
+        .collect()
+}
+
+/// Number of threads named like the current one, i.e. created without a name from it
+fn unnamed_threads() -> usize {
+    let name = fs::read_to_string("/proc/thread-self/comm").unwrap();
+    thread_names().iter().filter(|other| **other == name.trim_end()).count()
+}
+
+#[test]
+fn test_output_capture_does_not_spawn_threads() {
+    // Make sure the reactor is running before counting
+    let mut first = ProcessBuilder::new("true").capture_stdout(true).spawn().unwrap();
+    first.wait_with_output().unwrap();
+    let before = unnamed_threads();
+    
+    let mut procs: Vec<Process> = (0..16)
+        .map(|_| {
+            ProcessBuilder::new("sh")
+                .args(&["-c", "echo out; echo err 1>&2; sleep 1"])
+                .capture_stdout(true)
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    
+    assert_eq!(unnamed_threads(), before);
+    let reactors = thread_names().iter().filter(|name| *name == "betahub-reactor").count();
+    assert_eq!(reactors, 1);
+    
+    for proc in &mut procs {
+        let output = proc.wait_with_output().unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+}
+
+#[test]
+fn test_concurrent_large_output_is_complete() {
+    let mut procs: Vec<Process> = (0..8)
+        .map(|_| {
+            ProcessBuilder::new("sh")
+                .args(&["-c", "head -c 300000 /dev/zero; head -c 200000 /dev/zero 1>&2"])
+                .capture_stdout(true)
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    
+    for proc in &mut procs {
+        let output = proc.wait_with_output().unwrap();
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.stdout.len(), 300000);
+        assert_eq!(output.stderr.len(), 200000);
+    }
+}
+
+#[test]
+fn test_read_stderr_while_running() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "echo first 1>&2; read line; echo second 1>&2"])
+        .spawn()
+        .unwrap();
+    
+    let mut stderr = Vec::new();
+    let mut buf = [0u8; 64];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while stderr != b"first\n" && Instant::now() < deadline {
+        let n = proc.read_stderr(&mut buf).unwrap();
+        stderr.extend_from_slice(&buf[..n]);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(stderr, b"first\n");
+    
+    proc.write_stdin(b"go\n").unwrap();
+    assert_eq!(proc.wait().unwrap(), 0);
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.stderr, b"second\n");
+}
+
+#[test]
+fn test_pty_large_input() {
+    // Without echo, which the terminal drops when it falls behind, so every byte can be counted
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "stty -echo; echo ready; cat"])
+        .pty(PtySize::default())
+        .spawn()
+        .unwrap();
+    
+    let mut ready = Vec::new();
+    let mut buf = [0u8; 64];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !ready.ends_with(b"ready\r\n") && Instant::now() < deadline {
+        let n = proc.read_stdout(&mut buf).unwrap();
+        ready.extend_from_slice(&buf[..n]);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(ready.ends_with(b"ready\r\n"));
+    
+    // More than the terminal buffers, so writing has to wait for the reactor to drain the output
+    let line = format!("{}\n", "x".repeat(63));
+    for _ in 0..2000 {
+        proc.write_stdin(line.as_bytes()).unwrap();
+    }
+    proc.close_stdin();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(output.stdout.iter().filter(|byte| **byte == b'x').count(), 2000 * 63);
+}
@@ -0,0 +1,109 @@
+use betahub_process_wrapper::process::{Process, ProcessBuilder, ProcessError};
+use betahub_process_wrapper::reaper::wait_any;
+use betahub_process_wrapper::{process_close, process_start, process_wait, process_wait_any};
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+fn sleeper(seconds: &str) -> Process {
+    ProcessBuilder::new("sleep").arg(seconds).spawn().unwrap()
+}
+
+#[test]
+fn test_wait_any_returns_first_exit() {
+    let mut slow = sleeper("10");
+    let mut fast = ProcessBuilder::new("sh").args(&["-c", "sleep 0.2; exit 3"]).spawn().unwrap();
+    let mut other = sleeper("10");
+    
+    let started = Instant::now();
+    let index = wait_any(&mut [&mut slow, &mut fast, &mut other], Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(index, 1);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    
+    // The exit has been collected already
+    assert_eq!(fast.exit_info().unwrap().code, Some(3));
+    assert!(slow.is_running());
+    
+    slow.close().unwrap();
+    other.close().unwrap();
+}
+
+#[test]
+fn test_wait_any_finished_processes_count() {
+    let mut running = sleeper("10");
+    let mut done = ProcessBuilder::new("true").spawn().unwrap();
+    let mut closed = sleeper("10");
+    closed.close().unwrap();
+    done.wait().unwrap();
+    
+    assert_eq!(wait_any(&mut [&mut running, &mut done, &mut closed], None).unwrap(), 1);
+    assert_eq!(wait_any(&mut [&mut running, &mut closed], None).unwrap(), 1);
+    
+    running.close().unwrap();
+}
+
+#[test]
+fn test_wait_any_timeout() {
+    let mut first = sleeper("10");
+    let mut second = sleeper("10");
+    
+    let started = Instant::now();
+    let result = wait_any(&mut [&mut first, &mut second], Some(Duration::from_millis(200)));
+    assert!(matches!(result, Err(ProcessError::Timeout)));
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    
+    assert!(matches!(wait_any(&mut [], None), Err(ProcessError::InvalidState)));
+    
+    first.close().unwrap();
+    second.close().unwrap();
+}
+
+#[test]
+fn test_wait_any_repeatedly() {
+    let mut procs: Vec<Process> = ["0.3", "0.1", "0.2"].iter().map(|seconds| sleeper(seconds)).collect();
+    
+    // Each exited process is taken out of the next wait
+    let mut order = Vec::new();
+    let mut remaining: Vec<usize> = (0..procs.len()).collect();
+    while !remaining.is_empty() {
+        let mut waiting: Vec<&mut Process> = procs
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| remaining.contains(index))
+            .map(|(_, proc)| proc)
+            .collect();
+        let index = wait_any(&mut waiting, Some(Duration::from_secs(5))).unwrap();
+        order.push(remaining.remove(index));
+    }
+    assert_eq!(order, vec![1, 2, 0]);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_exit_time_recorded_without_polling() {
+    let mut proc = ProcessBuilder::new("true").spawn().unwrap();
+    
+    // Reaped long after it exited, yet the wall time ends at the exit
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(proc.wait().unwrap(), 0);
+    assert!(proc.resource_usage().unwrap().wall_time < Duration::from_millis(400));
+}
+
+#[test]
+fn test_ffi_wait_any() {
+    let slow = CString::new("sleep 10").unwrap();
+    let fast = CString::new("sleep 0.1").unwrap();
+    
+    unsafe {
+        let procs = [process_start(slow.as_ptr()), process_start(fast.as_ptr())];
+        assert!(procs.iter().all(|proc| !proc.is_null()));
+        
+        assert_eq!(process_wait_any(procs.as_ptr(), procs.len(), 5000), 1);
+        assert_eq!(process_wait(procs[1]), 0);
+        assert_eq!(process_wait_any(procs.as_ptr(), 1, 100), -1);
+        assert_eq!(process_wait_any(std::ptr::null(), 0, 100), -2);
+        
+        for proc in procs {
+            process_close(proc);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use betahub_process_wrapper::process_replay_start;
+use betahub_process_wrapper::replay::{
+    parse_segment_list, select_segments, ReplayBuffer, ReplayConfig, Segment,
+};
+use std::ffi::CString;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn segment(file: &str, start: f64, end: f64) -> Segment {
+    Segment {
+        file: file.to_string(),
+        start,
+        end,
+    }
+}
+
+#[test]
+fn test_parse_segment_list() {
+    let contents = "replay_000000.ts,0.000000,2.002000\n\
+                    replay_000001.ts,2.002000,4.004000\n\
+                    \"replay,odd.ts\",4.004000,6.000000\n\
+                    garbage\n";
+    let segments = parse_segment_list(contents);
+    assert_eq!(
+        segments,
+        vec![
+            segment("replay_000000.ts", 0.0, 2.002),
+            segment("replay_000001.ts", 2.002, 4.004),
+            segment("replay,odd.ts", 4.004, 6.0),
+        ]
+    );
+}
+
+#[test]
+fn test_select_segments() {
+    let segments = vec![
+        segment("a", 0.0, 2.0),
+        segment("b", 2.0, 4.0),
+        segment("c", 4.0, 6.0),
+    ];
+    
+    assert_eq!(select_segments(&segments, 3.0), &segments[1..]);
+    assert_eq!(select_segments(&segments, 4.0), &segments[1..]);
+    assert_eq!(select_segments(&segments, 30.0), &segments[..]);
+    assert!(select_segments(&segments, 0.0).is_empty());
+    assert!(select_segments(&[], 10.0).is_empty());
+}
+
+#[test]
+fn test_replay_missing_ffmpeg() {
+    let dir = tempdir().unwrap();
+    let config = ReplayConfig::new("nonexistentcommand", &[], dir.path());
+    assert!(ReplayBuffer::start(config).is_err());
+}
+
+#[test]
+fn test_replay_zero_buffer() {
+    let dir = tempdir().unwrap();
+    let mut config = ReplayConfig::new("ffmpeg", &[], dir.path());
+    config.buffer_seconds = 0;
+    assert!(ReplayBuffer::start(config).is_err());
+}
+
+#[test]
+fn test_ffi_replay_null_arguments() {
+    let dir = CString::new("/tmp").unwrap();
+    let replay = unsafe { process_replay_start(std::ptr::null(), std::ptr::null(), 0, dir.as_ptr(), 30, 2) };
+    assert!(replay.is_null());
+}
+
+/// Script standing in for ffmpeg: records one segment, and fails concatenating with lots of stderr
+#[cfg(unix)]
+const FAKE_FFMPEG: &str = r#"#!/bin/sh
+case " $* " in
+*" concat "*)
+    i=0
+    while [ $i -lt 2000 ]; do echo "concat error $i" >&2; i=$((i+1)); done
+    echo "last line" >&2
+    exit 1;;
+esac
+for arg; do pattern=$arg; done
+dir=$(dirname "$pattern")
+touch "$dir/replay_000000.ts"
+echo "replay_000000.ts,0.000000,2.000000" > "$dir/replay_segments.csv"
+cat > /dev/null
+"#;
+
+#[test]
+#[cfg(unix)]
+fn test_replay_save_failure_reports_full_stderr() {
+    use betahub_process_wrapper::process::ProcessError;
+    use std::os::unix::fs::PermissionsExt;
+    
+    let dir = tempdir().unwrap();
+    let ffmpeg = dir.path().join("ffmpeg");
+    std::fs::write(&ffmpeg, FAKE_FFMPEG).unwrap();
+    std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+    
+    let segments = dir.path().join("segments");
+    let replay = ReplayBuffer::start(ReplayConfig::new(ffmpeg.to_str().unwrap(), &[], &segments)).unwrap();
+    while !segments.join("replay_segments.csv").exists() {
+        thread::sleep(Duration::from_millis(10));
+    }
+    
+    match replay.save_last(2, &dir.path().join("clip.ts")) {
+        Err(ProcessError::CommandFailed { code, stderr }) => {
+            assert_eq!(code, 1);
+            assert!(stderr.starts_with("concat error 0\n"));
+            assert!(stderr.ends_with("concat error 1999\nlast line"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    
+    replay.stop().unwrap();
+}
+
+#[test]
+#[ignore] // This test requires ffmpeg to be installed
+fn test_replay_save_last() {
+    let dir = tempdir().unwrap();
+    let mut config = ReplayConfig::new(
+        "ffmpeg",
+        &["-re", "-f", "lavfi", "-i", "testsrc=size=320x240:rate=30", "-c:v", "mpeg2video"],
+        dir.path().join("segments"),
+    );
+    config.buffer_seconds = 4;
+    config.segment_seconds = 1;
+    
+    let replay = ReplayBuffer::start(config).unwrap();
+    thread::sleep(Duration::from_secs(8));
+    
+    let clip = dir.path().join("clip.ts");
+    replay.save_last(3, &clip).unwrap();
+    assert!(clip.metadata().unwrap().len() > 0);
+    
+    // Old segments are pruned while recording
+    let segments = std::fs::read_dir(dir.path().join("segments"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "ts"))
+        .count();
+    assert!(segments <= 7);
+    
+    replay.stop().unwrap();
+}
@@ -0,0 +1,188 @@
+#![cfg(target_os = "linux")]
+
+use betahub_process_wrapper::expect::Pattern;
+use betahub_process_wrapper::process::{ProcessBuilder, Shutdown};
+use betahub_process_wrapper::sandbox::SandboxConfig;
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_builder_sandbox, process_builder_spawn,
+    process_close, process_wait,
+};
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, Write};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
+/// Whether the system lets unprivileged processes create namespaces, reporting the skip of `test` if not
+fn sandbox_available(test: &str) -> bool {
+    let probe = ProcessBuilder::new("true")
+        .sandbox(SandboxConfig::new())
+        .spawn()
+        .and_then(|mut proc| proc.wait());
+    let reason = match probe {
+        Ok(0) => return true,
+        Ok(code) => format!("exited with {}", code),
+        Err(err) => err.to_string(),
+    };
+    
+    // Written past the test harness's capture, so the skip shows even though the test passes
+    let _ = writeln!(io::stderr(), "skipping {}: sandbox unavailable: {}", test, reason);
+    false
+}
+
+#[test]
+fn test_sandbox_isolates_processes_network_and_files() {
+    if !sandbox_available("test_sandbox_isolates_processes_network_and_files") {
+        return;
+    }
+    
+    let output = ProcessBuilder::new("sh")
+        .args(&["-c", "echo $$; cat /proc/net/dev; ls /"])
+        .capture_stdout(true)
+        .sandbox(SandboxConfig::new())
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert_eq!(output.exit_code, 0);
+    
+    // The program runs below a small init
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("2"));
+    
+    // Only loopback, and only the bound directories
+    let interfaces: Vec<&str> = stdout.lines().filter(|line| line.contains(':')).map(|line| line.trim()).collect();
+    assert_eq!(interfaces.len(), 1, "{}", stdout);
+    assert!(interfaces[0].starts_with("lo:"));
+    let entries: Vec<&str> = stdout.lines().collect();
+    for entry in ["usr", "etc", "proc", "tmp", "dev"] {
+        assert!(entries.contains(&entry), "{}", stdout);
+    }
+    for entry in ["root", "home", "var"] {
+        assert!(!entries.contains(&entry), "{}", stdout);
+    }
+}
+
+#[test]
+fn test_sandbox_read_only_and_writable_paths() {
+    if !sandbox_available("test_sandbox_read_only_and_writable_paths") {
+        return;
+    }
+    let output_dir = tempfile::tempdir().unwrap();
+    let hidden_dir = tempfile::tempdir().unwrap();
+    fs::write(hidden_dir.path().join("secret"), "x").unwrap();
+    
+    let script = format!(
+        "echo done > {out}/result; touch /usr/pw_probe || echo read-only; cat {hidden}/secret || echo hidden",
+        out = output_dir.path().display(),
+        hidden = hidden_dir.path().display(),
+    );
+    let output = ProcessBuilder::new("sh")
+        .args(&["-c", &script])
+        .capture_stdout(true)
+        .sandbox(SandboxConfig::new().writable(output_dir.path()))
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "read-only\nhidden\n");
+    assert_eq!(fs::read_to_string(output_dir.path().join("result")).unwrap(), "done\n");
+    assert!(!std::path::Path::new("/usr/pw_probe").exists());
+    
+    // A writable path must exist
+    let missing = ProcessBuilder::new("true")
+        .sandbox(SandboxConfig::new().writable(output_dir.path().join("missing")))
+        .spawn();
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_sandbox_passes_on_signals_and_exit() {
+    if !sandbox_available("test_sandbox_passes_on_signals_and_exit") {
+        return;
+    }
+    
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["exit", "7"])
+        .sandbox(SandboxConfig::new())
+        .spawn()
+        .unwrap();
+    assert_eq!(proc.wait().unwrap(), 7);
+    proc.close().unwrap();
+    
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["out", "ready", "hang"])
+        .capture_stdout(true)
+        .sandbox(SandboxConfig::new())
+        .shutdown(Shutdown::new(Duration::from_secs(5)).signal(libc::SIGTERM))
+        .spawn()
+        .unwrap();
+    proc.wait_for_output(&Pattern::literal("ready"), Duration::from_secs(5)).unwrap();
+    
+    // Reaches the program past init, which would otherwise ignore it
+    let started = Instant::now();
+    proc.close().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(proc.exit_info().unwrap().signal, Some(libc::SIGTERM));
+}
+
+#[test]
+fn test_sandbox_program_by_absolute_path() {
+    if !sandbox_available("test_sandbox_program_by_absolute_path") {
+        return;
+    }
+    
+    // Reached through the bound /usr rather than bound again, even where /bin is a symlink
+    let output = ProcessBuilder::new("/bin/sh")
+        .args(&["-c", "echo ok"])
+        .capture_stdout(true)
+        .sandbox(SandboxConfig::new())
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+    
+    // Bound on its own when outside the bound paths
+    let mut proc = ProcessBuilder::new(TESTCHILD)
+        .args(&["exit", "3"])
+        .sandbox(SandboxConfig::empty().read_only("/lib").read_only("/lib64").read_only("/usr"))
+        .spawn()
+        .unwrap();
+    assert_eq!(proc.wait().unwrap(), 3);
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_ffi_sandbox() {
+    if !sandbox_available("test_ffi_sandbox") {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let dir_path = CString::new(dir.path().to_str().unwrap()).unwrap();
+    let script = CString::new(format!("echo ok > {}/result", dir.path().display())).unwrap();
+    let writable = [dir_path.as_ptr()];
+    let program = CString::new("sh").unwrap();
+    let flag = CString::new("-c").unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, flag.as_ptr());
+        process_builder_arg(builder, script.as_ptr());
+        assert_eq!(process_builder_sandbox(builder, 1, ptr::null(), 0, writable.as_ptr(), 1, 0), 0);
+        assert_eq!(process_builder_sandbox(ptr::null_mut(), 1, ptr::null(), 0, ptr::null(), 0, 0), -1);
+        
+        let proc = process_builder_spawn(builder);
+        assert!(!proc.is_null());
+        assert_eq!(process_wait(proc), 0);
+        process_close(proc);
+        process_builder_free(builder);
+    }
+    
+    assert_eq!(fs::read_to_string(dir.path().join("result")).unwrap(), "ok\n");
+}
@@ -0,0 +1,212 @@
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::supervisor::{RestartPolicy, Supervisor, SupervisorConfig, SupervisorEvent};
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_supervisor_is_finished,
+    process_supervisor_restarts, process_supervisor_start, process_supervisor_stop, ProcessSupervisorEvent,
+    RESTART_ON_FAILURE, SUPERVISOR_EVENT_EXITED, SUPERVISOR_EVENT_GAVE_UP, SUPERVISOR_EVENT_STARTED,
+};
+use crate::common::eventually;
+use libc::c_void;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const BACKOFF: Duration = Duration::from_millis(10);
+
+fn exiting_with(code: i32) -> ProcessBuilder {
+    let mut builder = ProcessBuilder::new("sh");
+    builder.args(&["-c", &format!("exit {}", code)]);
+    builder
+}
+
+fn started_count(events: &[SupervisorEvent]) -> usize {
+    events.iter().filter(|event| matches!(event, SupervisorEvent::Started { .. })).count()
+}
+
+#[test]
+fn test_on_failure_restarts_until_limit() {
+    let config = SupervisorConfig::new(exiting_with(3))
+        .backoff(BACKOFF, Duration::from_secs(1))
+        .max_restarts(2, Duration::from_secs(60));
+    let supervisor = Supervisor::start(config).unwrap();
+    
+    assert!(eventually(|| supervisor.is_finished()));
+    assert_eq!(supervisor.restarts(), 2);
+    
+    let events = supervisor.events();
+    assert_eq!(started_count(&events), 3);
+    assert_eq!(events.last(), Some(&SupervisorEvent::GaveUp { restarts: 2 }));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        SupervisorEvent::Exited { restarts: 1, exit } if exit.code == Some(3)
+    )));
+    
+    supervisor.stop().unwrap();
+}
+
+#[test]
+fn test_on_failure_does_not_restart_success() {
+    let supervisor = Supervisor::start(SupervisorConfig::new(exiting_with(0)).backoff(BACKOFF, BACKOFF)).unwrap();
+    
+    assert!(eventually(|| supervisor.is_finished()));
+    assert_eq!(supervisor.restarts(), 0);
+    assert_eq!(supervisor.events().last(), Some(&SupervisorEvent::Finished { restarts: 0 }));
+}
+
+#[test]
+fn test_never_policy() {
+    let config = SupervisorConfig::new(exiting_with(1)).policy(RestartPolicy::Never);
+    let supervisor = Supervisor::start(config).unwrap();
+    
+    assert!(eventually(|| supervisor.is_finished()));
+    assert_eq!(supervisor.restarts(), 0);
+    assert_eq!(started_count(&supervisor.events()), 1);
+}
+
+#[test]
+fn test_always_restarts_success() {
+    let config = SupervisorConfig::new(exiting_with(0))
+        .policy(RestartPolicy::Always)
+        .backoff(BACKOFF, BACKOFF)
+        .max_restarts(1, Duration::from_secs(60));
+    let supervisor = Supervisor::start(config).unwrap();
+    
+    assert!(eventually(|| supervisor.is_finished()));
+    assert_eq!(supervisor.restarts(), 1);
+    assert_eq!(supervisor.events().last(), Some(&SupervisorEvent::GaveUp { restarts: 1 }));
+}
+
+#[test]
+fn test_backoff_doubles_up_to_max() {
+    let initial = Duration::from_millis(50);
+    let config = SupervisorConfig::new(exiting_with(1))
+        .backoff(initial, initial * 4)
+        .max_restarts(4, Duration::from_secs(60));
+    let supervisor = Supervisor::start(config).unwrap();
+    assert!(eventually(|| supervisor.is_finished()));
+    
+    let delays: Vec<Duration> = supervisor
+        .events()
+        .iter()
+        .filter_map(|event| match event {
+            SupervisorEvent::Restarting { delay, .. } => Some(*delay),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(delays, [initial, initial * 2, initial * 4, initial * 4]);
+}
+
+#[test]
+fn test_spawn_failure_of_first_instance() {
+    let config = SupervisorConfig::new(ProcessBuilder::new("betahub_pw_missing_program"));
+    assert!(Supervisor::start(config).is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_restarts_crashed_process() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&events);
+    
+    let mut builder = ProcessBuilder::new("cat");
+    builder.capture_stdout(true);
+    let config = SupervisorConfig::new(builder)
+        .backoff(BACKOFF, BACKOFF)
+        .on_event(move |event| recorded.lock().unwrap().push(event.clone()));
+    let supervisor = Supervisor::start(config).unwrap();
+    
+    let first = supervisor.id().unwrap();
+    unsafe { libc::kill(first as libc::pid_t, libc::SIGKILL) };
+    assert!(eventually(|| supervisor.restarts() == 1 && supervisor.is_running()));
+    assert_ne!(supervisor.id(), Some(first));
+    
+    // The new instance is reachable through the same handle
+    supervisor.write_stdin(b"hello\n").unwrap();
+    let mut output = Vec::new();
+    assert!(eventually(|| {
+        let mut buf = [0u8; 64];
+        let read = supervisor.read_stdout(&mut buf).unwrap();
+        output.extend_from_slice(&buf[..read]);
+        output == b"hello\n"
+    }));
+    
+    assert!(events.lock().unwrap().iter().any(|event| matches!(
+        event,
+        SupervisorEvent::Exited { restarts: 0, exit } if exit.signal == Some(libc::SIGKILL)
+    )));
+    
+    // Stopping terminates the current instance without restarting it
+    let second = supervisor.id().unwrap();
+    supervisor.stop().unwrap();
+    assert_ne!(unsafe { libc::kill(second as libc::pid_t, 0) }, 0);
+    assert_eq!(started_count(&events.lock().unwrap()), 2);
+}
+
+#[test]
+fn test_events_come_from_supervisor_thread() {
+    let caller = thread::current().id();
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&threads);
+    
+    let config = SupervisorConfig::new(exiting_with(3))
+        .backoff(BACKOFF, BACKOFF)
+        .max_restarts(1, Duration::from_secs(60))
+        .on_event(move |event| recorded.lock().unwrap().push((event.clone(), thread::current().id())));
+    let supervisor = Supervisor::start(config).unwrap();
+    assert!(eventually(|| supervisor.is_finished()));
+    supervisor.stop().unwrap();
+    
+    // The first instance is reported like the restarted one, never from `start`
+    let threads = threads.lock().unwrap();
+    let events: Vec<_> = threads.iter().map(|(event, _)| event.clone()).collect();
+    assert!(matches!(events[0], SupervisorEvent::Started { restarts: 0, .. }));
+    assert_eq!(started_count(&events), 2);
+    assert!(threads.iter().all(|(_, thread)| *thread != caller));
+    assert!(threads.iter().all(|(_, thread)| *thread == threads[0].1));
+}
+
+extern "C" fn record_event(user_data: *mut c_void, event: *const ProcessSupervisorEvent) {
+    let events = unsafe { &*(user_data as *const Mutex<Vec<ProcessSupervisorEvent>>) };
+    events.lock().unwrap().push(unsafe { *event });
+}
+
+#[test]
+fn test_ffi_supervisor() {
+    let events: Mutex<Vec<ProcessSupervisorEvent>> = Mutex::new(Vec::new());
+    let program = CString::new("sh").unwrap();
+    let flag = CString::new("-c").unwrap();
+    let script = CString::new("exit 5").unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, flag.as_ptr());
+        process_builder_arg(builder, script.as_ptr());
+        assert!(process_supervisor_start(builder, 42, 10, 10, 0, 0, None, std::ptr::null_mut()).is_null());
+        
+        let supervisor = process_supervisor_start(
+            builder,
+            RESTART_ON_FAILURE,
+            10,
+            100,
+            1,
+            60_000,
+            Some(record_event),
+            &events as *const Mutex<Vec<ProcessSupervisorEvent>> as *mut c_void,
+        );
+        process_builder_free(builder);
+        assert!(!supervisor.is_null());
+        
+        assert!(eventually(|| process_supervisor_is_finished(supervisor) == 1));
+        assert_eq!(process_supervisor_restarts(supervisor), 1);
+        process_supervisor_stop(supervisor);
+    }
+    
+    let events = events.into_inner().unwrap();
+    assert_eq!(events[0].kind, SUPERVISOR_EVENT_STARTED);
+    assert_ne!(events[0].pid, 0);
+    let exited = events.iter().find(|event| event.kind == SUPERVISOR_EVENT_EXITED).unwrap();
+    assert_eq!(exited.exit.code, 5);
+    assert_eq!(events.last().unwrap().kind, SUPERVISOR_EVENT_GAVE_UP);
+    assert_eq!(events.last().unwrap().restarts, 1);
+}
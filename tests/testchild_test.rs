@@ -0,0 +1,104 @@
+use betahub_process_wrapper::exit::TerminationReason;
+use betahub_process_wrapper::expect::Pattern;
+use betahub_process_wrapper::process::ProcessBuilder;
+use std::time::Duration;
+
+const TESTCHILD: &str = env!("CARGO_BIN_EXE_pw-testchild");
+
+fn testchild(script: &[&str]) -> ProcessBuilder {
+    let mut builder = ProcessBuilder::new(TESTCHILD);
+    builder.args(script);
+    builder
+}
+
+#[test]
+fn test_exact_output_and_exit_code() {
+    let mut proc = testchild(&["out", "started", "emit", "100000", "emit-err", "70000", "exit", "5"])
+        .capture_stdout(true)
+        .spawn()
+        .unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 5);
+    assert_eq!(output.stdout.len(), "started\n".len() + 100000);
+    assert!(output.stdout.starts_with(b"started\nabc"));
+    assert_eq!(output.stderr.len(), 70000);
+}
+
+#[test]
+fn test_echo_round_trip() {
+    let mut proc = testchild(&["echo-err"]).spawn().unwrap();
+    
+    for line in ["first", "second"] {
+        proc.write_stdin(format!("{}\n", line).as_bytes()).unwrap();
+        let found = proc.wait_for_output(&Pattern::literal(line), Duration::from_secs(5)).unwrap();
+        assert_eq!(found.text, line);
+    }
+    
+    proc.close_stdin();
+    assert_eq!(proc.wait().unwrap(), 0);
+}
+
+#[test]
+fn test_malformed_script_is_rejected() {
+    let mut proc = testchild(&["sleep", "soon"]).spawn().unwrap();
+    
+    let output = proc.wait_with_output().unwrap();
+    assert_eq!(output.exit_code, 2);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a number"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_raised_signal() {
+    let mut proc = testchild(&["signal", &libc::SIGTERM.to_string()]).spawn().unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), -1);
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.signal, Some(libc::SIGTERM));
+    assert_eq!(info.reason, TerminationReason::Signaled);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_ignored_sigterm() {
+    let mut proc = testchild(&["ignore-term", "err", "ready", "hang"]).spawn().unwrap();
+    proc.wait_for_output(&Pattern::literal("ready"), Duration::from_secs(5)).unwrap();
+    
+    // Still running after SIGTERM, so only close ends it
+    unsafe { libc::kill(proc.id().unwrap() as libc::pid_t, libc::SIGTERM) };
+    assert!(proc.wait_for_output(&Pattern::literal("never"), Duration::from_millis(200)).is_err());
+    assert!(proc.is_running());
+    
+    proc.close().unwrap();
+    assert_eq!(proc.exit_info().unwrap().signal, Some(libc::SIGKILL));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_grandchildren_end_with_group() {
+    let mut proc = testchild(&["fork", "2", "out", "forked", "hang"])
+        .capture_stdout(true)
+        .process_group(true)
+        .spawn()
+        .unwrap();
+    
+    let ids = Pattern::regex(r"(?s)^(\d+)\n(\d+)\nforked\n").unwrap();
+    let found = proc.wait_for_output(&ids, Duration::from_secs(5)).unwrap();
+    let grandchildren: Vec<String> = found.captures.into_iter().map(Option::unwrap).collect();
+    
+    proc.close().unwrap();
+    
+    // Gone, or left as zombies until their new parent reaps them
+    for id in grandchildren {
+        let ended = || {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", id)).unwrap_or_default();
+            stat.is_empty() || stat.contains(") Z ")
+        };
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !ended() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(ended(), "grandchild {} still running", id);
+    }
+}
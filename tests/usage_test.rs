@@ -0,0 +1,61 @@
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::{process_close, process_resource_usage, process_start, process_wait, ProcessResourceUsage};
+use std::ffi::CString;
+use std::time::Duration;
+
+#[test]
+#[cfg(unix)]
+fn test_resource_usage_after_wait() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; sleep 0.2"])
+        .spawn()
+        .unwrap();
+    
+    assert!(proc.resource_usage().is_none());
+    assert_eq!(proc.wait().unwrap(), 0);
+    
+    let usage = proc.resource_usage().unwrap();
+    assert!(usage.wall_time >= Duration::from_millis(200));
+    assert!(usage.user_time + usage.system_time > Duration::ZERO);
+    assert!(usage.user_time + usage.system_time <= usage.wall_time);
+    assert!(usage.max_rss > 0);
+    assert!(usage.minor_faults > 0);
+    assert!(usage.voluntary_switches > 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_resource_usage_after_is_running_and_close() {
+    let mut proc = ProcessBuilder::new("true").spawn().unwrap();
+    while proc.is_running() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(proc.resource_usage().unwrap().max_rss > 0);
+    
+    // A killed process is reaped with its usage as well
+    let mut proc = ProcessBuilder::new("sleep").arg("10").spawn().unwrap();
+    proc.close().unwrap();
+    let usage = proc.resource_usage().unwrap();
+    assert!(usage.wall_time < Duration::from_secs(10));
+    assert_eq!(proc.wait().unwrap(), -1);
+}
+
+#[test]
+fn test_ffi_resource_usage() {
+    let cmd = CString::new("sleep 0.1").unwrap();
+    
+    unsafe {
+        let proc = process_start(cmd.as_ptr());
+        assert!(!proc.is_null());
+        
+        let mut usage = ProcessResourceUsage::default();
+        assert_eq!(process_resource_usage(proc, &mut usage), 0);
+        assert_eq!(process_resource_usage(proc, std::ptr::null_mut()), -1);
+        
+        assert_eq!(process_wait(proc), 0);
+        assert_eq!(process_resource_usage(proc, &mut usage), 1);
+        assert!(usage.wall_time_us >= 100_000);
+        
+        process_close(proc);
+    }
+}
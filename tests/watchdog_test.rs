@@ -0,0 +1,170 @@
+use betahub_process_wrapper::exit::TerminationReason;
+use betahub_process_wrapper::process::ProcessBuilder;
+use betahub_process_wrapper::watchdog::{Watchdog, WatchdogAction};
+use betahub_process_wrapper::{
+    process_builder_arg, process_builder_free, process_builder_new, process_builder_spawn, process_builder_watchdog,
+    process_close, process_exit_info, process_is_stalled, process_wait, ProcessExitInfo, EXIT_REASON_STALLED,
+    WATCHDOG_KILL,
+};
+use crate::common::eventually;
+use libc::c_void;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[test]
+fn test_watchdog_notifies_on_stall() {
+    let stalls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&stalls);
+    
+    let mut proc = ProcessBuilder::new("cat")
+        .capture_stdout(true)
+        .watchdog(Watchdog::new(TIMEOUT).on_stall(move |stall| {
+            assert!(stall.idle >= TIMEOUT);
+            counter.fetch_add(1, Ordering::SeqCst);
+        }))
+        .spawn()
+        .unwrap();
+    
+    assert!(eventually(|| proc.is_stalled()));
+    assert_eq!(stalls.load(Ordering::SeqCst), 1);
+    assert!(proc.is_running());
+    
+    // Activity ends the stall, and the next one is reported again
+    proc.write_stdin(b"hello\n").unwrap();
+    assert!(eventually(|| !proc.is_stalled()));
+    assert!(eventually(|| proc.is_stalled()));
+    assert_eq!(stalls.load(Ordering::SeqCst), 2);
+    
+    proc.close().unwrap();
+    let info = proc.exit_info().unwrap();
+    assert!(info.stall.is_some());
+    assert_ne!(info.reason, TerminationReason::Stalled);
+}
+
+#[test]
+fn test_watchdog_output_counts_as_activity() {
+    let mut proc = ProcessBuilder::new("sh")
+        .args(&["-c", "while :; do echo tick; sleep 0.05; done"])
+        .capture_stdout(true)
+        .watchdog(Watchdog::new(TIMEOUT * 3).action(WatchdogAction::Kill))
+        .spawn()
+        .unwrap();
+    
+    thread::sleep(TIMEOUT * 6);
+    assert!(proc.is_running());
+    assert!(!proc.is_stalled());
+    assert!(proc.last_stall().is_none());
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_watchdog_stdin_counts_as_activity() {
+    let mut proc = ProcessBuilder::new("cat")
+        .watchdog(Watchdog::new(TIMEOUT * 3).action(WatchdogAction::Kill))
+        .spawn()
+        .unwrap();
+    
+    for _ in 0..24 {
+        proc.write_stdin(b"frame\n").unwrap();
+        thread::sleep(TIMEOUT / 4);
+    }
+    assert!(proc.is_running());
+    assert!(proc.last_stall().is_none());
+    
+    proc.close().unwrap();
+}
+
+#[test]
+fn test_watchdog_kills_stalled_process() {
+    let mut proc = ProcessBuilder::new("sleep")
+        .arg("10")
+        .watchdog(Watchdog::new(TIMEOUT).action(WatchdogAction::Kill))
+        .spawn()
+        .unwrap();
+    
+    let started = Instant::now();
+    assert_eq!(proc.wait().unwrap(), -1);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.reason, TerminationReason::Stalled);
+    assert!(info.stall.unwrap().idle >= TIMEOUT);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_watchdog_sends_signal() {
+    let mut proc = ProcessBuilder::new("sleep")
+        .arg("10")
+        .watchdog(Watchdog::new(TIMEOUT).action(WatchdogAction::Signal(libc::SIGTERM)))
+        .spawn()
+        .unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), -1);
+    let info = proc.exit_info().unwrap();
+    assert_eq!(info.signal, Some(libc::SIGTERM));
+    assert_eq!(info.reason, TerminationReason::Stalled);
+}
+
+#[test]
+fn test_watchdog_stops_after_exit() {
+    let mut proc = ProcessBuilder::new("true")
+        .watchdog(Watchdog::new(TIMEOUT).action(WatchdogAction::Kill))
+        .spawn()
+        .unwrap();
+    
+    assert_eq!(proc.wait().unwrap(), 0);
+    thread::sleep(TIMEOUT * 2);
+    assert!(!proc.is_stalled());
+    assert_eq!(proc.exit_info().unwrap().reason, TerminationReason::Exited);
+}
+
+extern "C" fn count_stall(user_data: *mut c_void, idle_ms: u64) {
+    assert!(idle_ms >= TIMEOUT.as_millis() as u64);
+    let counter = unsafe { &*(user_data as *const AtomicUsize) };
+    counter.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn test_ffi_watchdog() {
+    let stalls = AtomicUsize::new(0);
+    let program = CString::new("sleep").unwrap();
+    let seconds = CString::new("10").unwrap();
+    
+    unsafe {
+        let builder = process_builder_new(program.as_ptr());
+        process_builder_arg(builder, seconds.as_ptr());
+        assert_eq!(process_builder_watchdog(builder, 200, 42, 0, None, std::ptr::null_mut()), -1);
+        assert_eq!(
+            process_builder_watchdog(
+                builder,
+                200,
+                WATCHDOG_KILL,
+                0,
+                Some(count_stall),
+                &stalls as *const AtomicUsize as *mut c_void,
+            ),
+            0
+        );
+        let proc = process_builder_spawn(builder);
+        process_builder_free(builder);
+        assert!(!proc.is_null());
+        assert_eq!(process_is_stalled(proc), 0);
+        
+        assert_eq!(process_wait(proc), -1);
+        assert_eq!(stalls.load(Ordering::SeqCst), 1);
+        
+        let mut info = ProcessExitInfo::default();
+        assert_eq!(process_exit_info(proc, &mut info), 1);
+        assert_eq!(info.reason, EXIT_REASON_STALLED);
+        assert_eq!(info.stalled, 1);
+        
+        process_close(proc);
+    }
+}
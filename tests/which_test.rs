@@ -0,0 +1,130 @@
+use betahub_process_wrapper::process::ProcessError;
+use betahub_process_wrapper::process_which;
+use betahub_process_wrapper::which::{library_dir, which, Resolver};
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+#[cfg(unix)]
+fn create_tool(path: &Path, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    
+    fs::write(path, "#!/bin/sh\nexit 0\n").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_which_path() {
+    let path = which("sh").unwrap();
+    assert!(path.is_absolute());
+    assert_eq!(path.file_name().unwrap(), "sh");
+}
+
+#[test]
+fn test_which_not_found() {
+    let dir = tempdir().unwrap();
+    let err = Resolver::new()
+        .search_paths([dir.path()])
+        .resolve("ffmpeg")
+        .unwrap_err();
+    
+    assert!(matches!(err, ProcessError::NotFound { .. }));
+    assert_eq!(err.to_string(), format!("ffmpeg not found in {}", dir.path().display()));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_which_search_paths() {
+    let empty = tempdir().unwrap();
+    let tools = tempdir().unwrap();
+    create_tool(&tools.path().join("tool"), 0o755);
+    
+    let path = Resolver::new()
+        .search_paths([empty.path(), tools.path()])
+        .resolve("tool")
+        .unwrap();
+    assert_eq!(path, tools.path().join("tool"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_which_bundled_dir_first() {
+    let bundled = tempdir().unwrap();
+    let tools = tempdir().unwrap();
+    create_tool(&bundled.path().join("tool"), 0o755);
+    create_tool(&tools.path().join("tool"), 0o755);
+    
+    let path = Resolver::new()
+        .bundled_dir(bundled.path())
+        .search_paths([tools.path()])
+        .resolve("tool")
+        .unwrap();
+    assert_eq!(path, bundled.path().join("tool"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_which_not_executable() {
+    let dir = tempdir().unwrap();
+    create_tool(&dir.path().join("tool"), 0o644);
+    
+    let err = Resolver::new()
+        .search_paths([dir.path()])
+        .resolve("tool")
+        .unwrap_err();
+    assert!(matches!(err, ProcessError::NotExecutable(path) if path == dir.path().join("tool")));
+    
+    let err = which(dir.path().join("tool").to_str().unwrap()).unwrap_err();
+    assert!(matches!(err, ProcessError::NotExecutable(_)));
+}
+
+#[test]
+fn test_library_dir() {
+    assert!(library_dir().unwrap().is_dir());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_ffi_which() {
+    let dir = tempdir().unwrap();
+    create_tool(&dir.path().join("tool"), 0o755);
+    let expected = dir.path().join("tool");
+    let expected = expected.to_str().unwrap();
+    
+    let program = CString::new("tool").unwrap();
+    let search_dir = CString::new(dir.path().to_str().unwrap()).unwrap();
+    let search_dirs = [search_dir.as_ptr()];
+    
+    let mut buf = [0 as libc::c_char; 512];
+    let len = unsafe {
+        process_which(program.as_ptr(), std::ptr::null(), search_dirs.as_ptr(), 1, buf.as_mut_ptr(), buf.len())
+    };
+    assert_eq!(len as usize, expected.len());
+    assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap(), expected);
+    
+    // Too small a buffer reports the required length without writing
+    let mut small = [0 as libc::c_char; 4];
+    let len = unsafe {
+        process_which(program.as_ptr(), std::ptr::null(), search_dirs.as_ptr(), 1, small.as_mut_ptr(), small.len())
+    };
+    assert_eq!(len as usize, expected.len());
+    assert_eq!(small[0], 0);
+    
+    let missing = CString::new("nonexistentcommand").unwrap();
+    let len = unsafe {
+        process_which(missing.as_ptr(), std::ptr::null(), search_dirs.as_ptr(), 1, buf.as_mut_ptr(), buf.len())
+    };
+    assert_eq!(len, -1);
+    let message = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap();
+    assert!(message.contains("nonexistentcommand"), "{}", message);
+    assert!(message.contains(dir.path().to_str().unwrap()), "{}", message);
+    
+    // The error is truncated to the buffer
+    let len = unsafe {
+        process_which(missing.as_ptr(), std::ptr::null(), search_dirs.as_ptr(), 1, small.as_mut_ptr(), small.len())
+    };
+    assert_eq!(len, -1);
+    assert_eq!(unsafe { CStr::from_ptr(small.as_ptr()) }.to_bytes().len(), small.len() - 1);
+}